@@ -135,7 +135,13 @@ pub struct FuzzerConfig {
     pub dict: Vec<Vec<u8>>,
     pub snapshot_placement: SnapshotPlacement,
     pub dump_python_code_for_inputs: Option<bool>,
-    pub exit_after_first_crash: bool
+    pub exit_after_first_crash: bool,
+    pub calibration_operators: Vec<String>, // 校准使用的探测算子名，为空时使用全部默认算子
+    pub record_edge_deltas: bool,           // 是否为每次探测记录相对基准的边级差异
+    pub minimize_seeds: bool,               // 校准前是否先把每个测试用例最小化
+    pub calibration_sampling: String,       // 校准时的偏移采样策略，如 "all"、"stride:4"、"first:256,adaptive:8"
+    pub calibration_seq_time: Option<Duration>, // 每个序列的校准时间预算
+    pub calibration_seq_execs: Option<u64>, // 每个序列的校准执行次数预算
 }
 impl FuzzerConfig{
     pub fn new_from_loader(sharedir: &str, default: FuzzerConfigLoader, config: FuzzerConfigLoader) -> Self {
@@ -163,6 +169,12 @@ impl FuzzerConfig{
             snapshot_placement: config.snapshot_placement.or(default.snapshot_placement).expect("no snapshot_placement specified"),
             dump_python_code_for_inputs: config.dump_python_code_for_inputs.or(default.dump_python_code_for_inputs),
             exit_after_first_crash: config.exit_after_first_crash.unwrap_or(default.exit_after_first_crash.unwrap_or(false)),
+            calibration_operators: config.calibration_operators.or(default.calibration_operators).unwrap_or_default(),
+            record_edge_deltas: config.record_edge_deltas.or(default.record_edge_deltas).unwrap_or(false),
+            minimize_seeds: config.minimize_seeds.or(default.minimize_seeds).unwrap_or(false),
+            calibration_sampling: config.calibration_sampling.or(default.calibration_sampling).unwrap_or_else(|| "all".to_string()),
+            calibration_seq_time: config.calibration_seq_time.or(default.calibration_seq_time),
+            calibration_seq_execs: config.calibration_seq_execs.or(default.calibration_seq_execs),
         }
     }
 }
@@ -49,6 +49,12 @@ pub struct FuzzerConfigLoader {
     pub snapshot_placement: Option<SnapshotPlacement>,
     pub dump_python_code_for_inputs: Option<bool>,
     pub exit_after_first_crash: Option<bool>,
+    pub calibration_operators: Option<Vec<String>>,
+    pub record_edge_deltas: Option<bool>,
+    pub minimize_seeds: Option<bool>,
+    pub calibration_sampling: Option<String>,
+    pub calibration_seq_time: Option<Duration>,
+    pub calibration_seq_execs: Option<u64>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -26,7 +26,8 @@ use std::error::Error;
 #[derive(Debug,Clone,Eq,PartialEq,Hash)]
 pub struct TestInfo { 
     pub ops_used: u32,
-    pub exitreason: ExitReason 
+    pub exitreason: ExitReason,
+    pub hprintf: String, // 本次执行期间目标通过 hprintf 输出的内容
 }
 
 // redqueen相关的
@@ -153,23 +154,24 @@ impl FuzzRunner for QemuProcess {
     fn run_test(&mut self) -> Result<TestInfo, Box<dyn Error>> {
         self.send_payload();//传送要执行的payload
         let ops_used = self.feedback_data.shared.interpreter.executed_opcode_num;
+        let hprintf = self.take_hprintf();
         if self.aux.result.crash_found != 0 {
-            return Ok(TestInfo {ops_used, exitreason: ExitReason::Crash(self.aux.misc.as_slice().to_vec())});
+            return Ok(TestInfo {ops_used, exitreason: ExitReason::Crash(self.aux.misc.as_slice().to_vec()), hprintf});
         }
         if self.aux.result.payload_write_attempt_found != 0{
-            return Ok(TestInfo {ops_used, exitreason: ExitReason::InvalidWriteToPayload(self.aux.misc.as_slice().to_vec())});
+            return Ok(TestInfo {ops_used, exitreason: ExitReason::InvalidWriteToPayload(self.aux.misc.as_slice().to_vec()), hprintf});
         }
         if self.aux.result.timeout_found != 0 {
-            return Ok(TestInfo {ops_used, exitreason: ExitReason::Timeout});
+            return Ok(TestInfo {ops_used, exitreason: ExitReason::Timeout, hprintf});
         }
         if self.aux.result.asan_found != 0 {
-            return Ok(TestInfo {ops_used, exitreason: ExitReason::Asan});
+            return Ok(TestInfo {ops_used, exitreason: ExitReason::Asan, hprintf});
         }
         if self.aux.result.success != 0{
-            return Ok(TestInfo {ops_used, exitreason: ExitReason::Normal(0)});
+            return Ok(TestInfo {ops_used, exitreason: ExitReason::Normal(0), hprintf});
         }
         println!("unknown exeuction result!!");
-        return Ok(TestInfo {ops_used, exitreason: ExitReason::FuzzerError});
+        return Ok(TestInfo {ops_used, exitreason: ExitReason::FuzzerError, hprintf});
     }
 
     /// 执行，发送payload，以期创建增量快照
@@ -33,6 +33,7 @@ pub struct QemuProcess {
     pub payload: &'static mut [u8],
     pub params: QemuParams,
     hprintf_log: File,
+    hprintf_output: String, // 最近一次 send_payload 期间的 hprintf 输出
 }
 
 /// 通过ctrl管道写入120，命令qemu执行
@@ -243,6 +244,7 @@ impl QemuProcess {
             payload: payload_shared,
             params,
             hprintf_log,
+            hprintf_output: String::new(),
         };
     }
 
@@ -254,6 +256,7 @@ impl QemuProcess {
         //std::thread::sleep(std::time::Duration::from_secs(1));
         //let time = std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap().as_nanos();
         //self.hprintf_log.write_all(&format!("===({})===\n", time).as_bytes()).unwrap();
+        self.hprintf_output.clear();
         // 循环中观察aux_buffer中result的任何事件，某些事件产生均会中断循环
 
         loop {
@@ -264,6 +267,8 @@ impl QemuProcess {
             //hprintf有任何置位，将辅助缓冲区的misc部分的数据写入hprintf_log
             if self.aux.result.hprintf != 0 {
                 self.hprintf_log.write_all(&format!("{}\n", self.aux.misc.as_string()).as_bytes()).unwrap();
+                self.hprintf_output.push_str(&String::from_utf8_lossy(self.aux.misc.as_slice()));
+                self.hprintf_output.push('\n');
                 //println!("HPRINTF {}", self.aux.misc.as_string());
                 let len = self.aux.misc.len;
 
@@ -316,6 +321,11 @@ impl QemuProcess {
         //}
     }
 
+    ///取出最近一次执行的hprintf输出
+    pub fn take_hprintf(&mut self) -> String {
+        std::mem::take(&mut self.hprintf_output)
+    }
+
     ///通过aux_buffer设置超时时间
     pub fn set_timeout(&mut self, timeout: std::time::Duration){
         self.aux.config.timeout_sec = timeout.as_secs() as u8;
@@ -336,23 +346,7 @@ impl QemuProcess {
     //创建工作路径
     pub fn prepare_workdir(workdir: &str, seed_path: Option<String>) {
         Self::clear_workdir(workdir);
-        let folders = vec![
-            "/corpus/normal",
-            "/metadata",
-            "/corpus/crash",
-            "/corpus/kasan",
-            "/corpus/timeout",
-            "/bitmaps",
-            "/imports",
-            "/seeds",
-            "/snapshot",
-            "/forced_imports",
-        ];
-
-        for folder in folders.iter() {
-            fs::create_dir_all(format!("{}/{}", workdir, folder))
-                .expect("couldn't initialize workdir");
-        }
+        Self::create_workdir_folders(workdir);
         OpenOptions::new()
             .create(true)
             .write(true)
@@ -390,6 +384,43 @@ impl QemuProcess {
         }
     }
 
+    ///续跑时使用：保留已有的语料、种子和测量结果，只清理上一次运行遗留的
+    ///共享内存、符号链接、通信接口和快照，使新的qemu实例可以重新创建它们。
+    pub fn prepare_workdir_for_resume(workdir: &str) {
+        Self::clear_shm(workdir);
+        let _ = fs::remove_dir_all(format!("{}/snapshot", workdir));
+        for pattern in ["bitmap_*", "payload_*", "aux_buffer_*", "interface_*", "redqueen_workdir_*"].iter() {
+            for p in glob::glob(&format!("{}/{}", workdir, pattern)).expect("couldn't glob??").flatten() {
+                if p.is_dir() {
+                    let _ = fs::remove_dir_all(&p);
+                } else {
+                    let _ = fs::remove_file(&p);
+                }
+            }
+        }
+        Self::create_workdir_folders(workdir);
+    }
+
+    fn create_workdir_folders(workdir: &str) {
+        let folders = vec![
+            "/corpus/normal",
+            "/metadata",
+            "/corpus/crash",
+            "/corpus/kasan",
+            "/corpus/timeout",
+            "/bitmaps",
+            "/imports",
+            "/seeds",
+            "/snapshot",
+            "/forced_imports",
+        ];
+
+        for folder in folders.iter() {
+            fs::create_dir_all(format!("{}/{}", workdir, folder))
+                .expect("couldn't initialize workdir");
+        }
+    }
+
     //创建redqueen的工作路径
     fn prepare_redqueen_workdir(workdir: &str, qemu_id: usize) {
         //println!("== preparing RQ folder: {}", qemu_id);
@@ -401,7 +432,10 @@ impl QemuProcess {
 
     fn clear_workdir(workdir: &str) {
         let _ = fs::remove_dir_all(workdir);
+        Self::clear_shm(workdir);
+    }
 
+    fn clear_shm(workdir: &str) {
         let project_name = Path::new(workdir)
             .file_name()
             .expect("Couldn't get project name from workdir!")
@@ -2,10 +2,22 @@
 use crate::bitmap::{Bitmap, StorageReason};
 use crate::fuzz_runner::FuzzRunner;
 use crate::fuzz_runner::{ExitReason, TestInfo};
+use crate::inference::{self, InferredPacket};
+use crate::hash;
 use crate::input::Input;
+use crate::live_stats::LiveStats;
 
-use crate::localhashmap::LocalHashmap;
+use crate::checkpoint::{self, PacketCheckpoint};
+use crate::edge_delta::{self, EdgeDelta};
+use crate::ijon_slots::{self, SlotChange};
+use crate::class_registry::{self, ClassRegistry};
+use crate::differential::{OperatorProbe, PacketProbes, ProbeOutcome};
 use crate::queue::Queue;
+use crate::result_stream::{self, ResultRecord};
+use crate::replay;
+use crate::sampling::Sampling;
+use crate::triage::{self, CrashSignature};
+use crate::scheduler::{CalibrationJob, CalibrationScheduler, PacketCalibration, SequenceBudget, SequenceCalibration};
 use crate::structured_fuzzer::graph_mutator::graph_storage::{RefGraph, VecGraph};
 use crate::structured_fuzzer::graph_mutator::spec::GraphSpec;
 use crate::structured_fuzzer::mutator::{Mutator, MutatorSnapshotState};
@@ -16,16 +28,18 @@ use crate::structured_fuzzer::mutator::MutationStrategy;
 use crate::config::FuzzerConfig;
 
 //use std::error::Error;
-use std::fs::{File, OpenOptions};
+use std::collections::BTreeSet;
+use std::fs::OpenOptions;
 use std::io::{self, Write};
+use std::sync::Arc;
 //use std::io::Write;
 
 
 extern crate colored; // not needed in Rust 2018
 
-use serde_json;
 use structured_fuzzer::mutator::DetMutator;
-use crate::{hash, romu::*};
+use structured_fuzzer::primitive_mutator::calibration::{CalibrationOperator, CalibrationRegistry};
+use crate::romu::*;
 use colored::*;
 use std::str;
 use std::fmt; // 引入正确的 trait
@@ -42,47 +56,100 @@ impl<T: FuzzRunner> GetStructStorage for T {
 
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize)]
-struct PacketCalibrationResult {
-    packet_id: usize,             // 包编号
-    offset: usize,                // 偏移量
-    stable: bool,
-    mutation_operator: String,    // 使用的变异算子
-    cf_index: usize,        // CF 索引
-    vf_index: usize,        // VF 索引
-    cfc_index:usize,        //有bucket信息的索引    
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PacketCalibrationResult {
+    pub packet_id: usize,             // 包编号
+    pub offset: usize,                // 偏移量
+    pub stable: bool,
+    pub mutation_operator: String,    // 使用的变异算子
+    pub cf_index: usize,        // CF 索引
+    pub vf_index: usize,        // VF 索引
+    pub cfc_index:usize,        //有bucket信息的索引    
+    #[serde(default)]
+    pub rc_index: usize,        // 响应类：归一化后的 hprintf 输出
+    #[serde(default = "default_width")]
+    pub width: usize,           // 算子覆盖的字节数，单字节算子为1
+    #[serde(default)]
+    pub big_endian: Option<bool>, // 多字节整数算子的字节序
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edge_delta: Option<EdgeDelta>, // 开启 record_edge_deltas 时相对基准的边级差异
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slot_changes: Vec<SlotChange>, // 相对基准取值发生变化的 ijon 槽位
+}
+
+fn default_width() -> usize {
+    1
 }
 
 #[derive(Serialize, Deserialize)]
-struct SequenceCalibrationResults {
-    sequence_id: usize,                      // 序列编号
-    cal_time: f32,
-    pkt_number:usize,
-    raw_data: Option<String>,       // 整个包序列的摘要或统计信息
-    packets_cali_result: Vec<PacketCalibrationResult>,  // 每个包的测量结果
+pub struct SequenceCalibrationResults {
+    pub sequence_id: usize,                      // 序列编号
+    pub cal_time: f32,
+    pub pkt_number:usize,
+    pub raw_data: Option<String>,       // 整个包序列的摘要或统计信息
+    pub packets_cali_result: Vec<PacketCalibrationResult>,  // 每个包的测量结果
+    #[serde(default)]
+    pub var_edges: Vec<PacketVarEdges>,  // 每个包基准执行时波动的 bitmap 下标
+    #[serde(default)]
+    pub probed_offsets: Vec<PacketProbedOffsets>,  // 每个包实际探测过的偏移，缺失的包为全部探测
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PacketVarEdges {
+    pub packet_id: usize,
+    pub edges: Vec<usize>,
+}
+
+/// 按采样策略实际探测过的偏移；truncated 表示因序列预算耗尽提前结束
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PacketProbedOffsets {
+    pub packet_id: usize,
+    pub offsets: Vec<usize>,
+    pub truncated: bool,
+}
+
+/// 0 号线程导入种子期间 panic 时把调度器标记为失败，其余线程据此退出而不是一直等待
+struct ImportGuard(CalibrationScheduler);
+
+impl Drop for ImportGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.0.set_failed();
+        }
+    }
 }
 
 
 pub struct SegmentAnalyzer<Fuzz: FuzzRunner + GetStructStorage> {
     fuzzer: Fuzz,                                   //fuzzer管理器
     queue: Queue,                                   //测试用例队列管理器
+    scheduler: CalibrationScheduler,                //线程间共享的校准任务队列
     master_rng: RomuPrng,                           //随机数生成器
     rng: Distributions,                             //变异概率分布管理器
     mutator: Mutator,                               //spec变异器
     det_mutator: DetMutator,                        //spec 确定性变异器
-    localhashmap: LocalHashmap,                         //bitmap管理器，记录全局的bitmap
+    operators: Vec<Arc<dyn CalibrationOperator>>,   //本次校准使用的探测算子
+    classes: ClassRegistry,                         //所有线程共享的等价类注册表
+    sampling: Sampling,                             //校准时的偏移采样策略
+    stats: LiveStats,                               //所有线程共享的运行统计
+    mask: Vec<usize>,                               //当前计算类编号使用的波动下标快照
+    pending_triage: Vec<Input>,                     //等待快照删除后再分桶的 crash/timeout 输入
     config: FuzzerConfig,                           //fuzz配置
 }
 
 impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
-    pub fn new(fuzzer: Fuzz, config: FuzzerConfig, spec: GraphSpec,queue: Queue,seed:u64) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(fuzzer: Fuzz, config: FuzzerConfig, spec: GraphSpec,queue: Queue, scheduler: CalibrationScheduler, classes: ClassRegistry, stats: LiveStats, seed:u64) -> Self {
         let rng = Distributions::new(config.dict.clone());//根据字典构建随机变异器
 
         //基于specfuzz需要的变异变异算子
         let mutator = Mutator::new(spec.clone());
         let det_mutator = DetMutator::new(spec.clone());
+        let operators = CalibrationRegistry::with_defaults()
+            .select(&config.calibration_operators)
+            .unwrap();
+        let sampling = Sampling::parse(&config.calibration_sampling).unwrap();
         //创建模糊测试需要记录的bitmap管理句柄、随机数生成器、模糊测试统计信息
-        let localhashmap = LocalHashmap::new();
         let master_rng = RomuPrng::new_from_u64(seed);
 
         //配置后续的文件的处理方式：有则打开可读可写，没有则创建
@@ -93,11 +160,17 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
         return Self {
             fuzzer,
             queue,
+            scheduler,
             master_rng,
             rng,
             mutator,
             det_mutator,
-            localhashmap,
+            operators,
+            classes,
+            sampling,
+            stats,
+            mask: vec![],
+            pending_triage: vec![],
             config,
         };
     }
@@ -163,9 +236,9 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
         for _ in 0..MAX_ATTEMPTS {
             // 执行测试并获取结果
             if let Ok(exec_res) = self.fuzzer.run_test() {
+                self.stats.record_exec(self.config.thread_id, &exec_res.exitreason);
                 // 计算当前执行的哈希值
-                let run_bitmap = self.fuzzer.bitmap_buffer();
-                let cur_exec_hash = hash::hash64(run_bitmap, run_bitmap.len());
+                let cur_exec_hash = class_registry::masked_hash(self.fuzzer.bitmap_buffer(), &self.mask);
         
                 // 如果是第一次执行，直接记录当前哈希值
                 if last_exec_hash.is_none() {
@@ -186,9 +259,9 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
                 // 如果连续稳定次数达到预期，则认为结果稳定
                 if stable_counter >= MIN_STABLE_RUNS {
                     // 稳定后，再获取最终返回需要的索引
-                    let cf_index = self.localhashmap.handle_cov_bitmap(self.fuzzer.bitmap_buffer());
-                    let cfc_index = self.localhashmap.handle_run_bitmap(self.fuzzer.bitmap_buffer());
-                    let vf_index = self.localhashmap.handle_ijon_map(self.fuzzer.ijon_max_buffer());
+                    let cf_index = self.classes.handle_cov_bitmap(self.fuzzer.bitmap_buffer(), &self.mask);
+                    let cfc_index = self.classes.handle_run_bitmap(self.fuzzer.bitmap_buffer(), &self.mask);
+                    let vf_index = self.classes.handle_ijon_map(self.fuzzer.ijon_max_buffer());
                     
                     return Some((exec_res_final.unwrap(), cf_index, vf_index, cfc_index,true));
                 }
@@ -197,10 +270,12 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
         
         // 如果达到最大尝试次数后还未稳定，则返回最后一次的执行结果（同样重新计算索引）
         if let Some(res) = exec_res_final {
-            println!("test unstable!");
-            let cf_index = self.localhashmap.handle_cov_bitmap(self.fuzzer.bitmap_buffer());
-            let cfc_index = self.localhashmap.handle_run_bitmap(self.fuzzer.bitmap_buffer());
-            let vf_index = self.localhashmap.handle_ijon_map(self.fuzzer.ijon_max_buffer());
+            if !self.stats.quiet() {
+                println!("test unstable!");
+            }
+            let cf_index = self.classes.handle_cov_bitmap(self.fuzzer.bitmap_buffer(), &self.mask);
+            let cfc_index = self.classes.handle_run_bitmap(self.fuzzer.bitmap_buffer(), &self.mask);
+            let vf_index = self.classes.handle_ijon_map(self.fuzzer.ijon_max_buffer());
             return Some((res, cf_index, vf_index, cfc_index,false));
         }
         
@@ -262,6 +337,7 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
                         self.new_input(&input);
                         //将这个输入添加到queue中
                         self.queue.add(input, &self.mutator.spec);
+                        self.flush_triage();
                     }
                 // }
             return Some(exec_res);
@@ -302,6 +378,12 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
             input.storage_reasons.len(),
             input.found_by
         );
+        // crash/timeout 等按签名分桶保存，不再每个输入写一份；
+        // 分桶要重新执行前缀，可能处于增量快照中，先暂存，由调用者在快照删除后 flush_triage
+        if triage::is_triaged(&input.exit_reason) {
+            self.pending_triage.push(input.clone());
+            return;
+        }
         //设置语料库路径
         fs::create_dir_all(&format!(
             "{}/corpus/{}",
@@ -322,39 +404,57 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
             ),
             &self.mutator.spec,
         );
-        match &input.exit_reason {
-            //处理crash的测试用例的log
-            ExitReason::Crash(desc) => {
-                std::fs::write(
-                    &format!(
-                        "{}/corpus//{}/{}.log",
-                        self.config.workdir_path,
-                        input.exit_reason.name(),
-                        id,
-                    ),
-                    &format!("{}\n", str::from_utf8(&desc).unwrap()),
-                )
-                .unwrap();
-            }
-            //处理invalidinput的测试用例的log
-            ExitReason::InvalidWriteToPayload(desc) => {//invalidinput
-                std::fs::write(
-                    &format!(
-                        "{}/corpus//{}/{}.log",
-                        self.config.workdir_path,
-                        input.exit_reason.name(),
-                        id
-                    ),
-                    desc,
-                )
-                .unwrap();
-            }
-            _ => {}
-        }
 
         //TODO:代码还缺少做minimiz情况的处理
     }
 
+    /// 只在最后一个执行到的包中出现的边：重新执行去掉该包后的前缀，取 input 的 bitmap 中新增的部分（忽略波动下标）
+    ///
+    /// 前缀是从头构造的完整图，必须在没有激活快照时调用；执行会覆盖执行器的 bitmap，
+    /// 调用之后不能再读取 `bitmap_buffer()` 作为之前那次执行的结果
+    fn last_packet_edges(&mut self, input: &Input) -> Vec<usize> {
+        let node_len = input.data.node_len(&self.mutator.spec);
+        let last = std::cmp::min(input.ops_used, node_len).saturating_sub(1);
+        let var_edges = self.classes.var_edges();
+        let crash_edges = input.bitmap.bits().iter().enumerate().filter(|(i, &b)| b != 0 && var_edges.binary_search(i).is_err()).map(|(i, _)| i);
+        if last == 0 {
+            return crash_edges.collect();
+        }
+        {
+            let mut storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
+            storage.copy_from_cutoff(&input.data, last, &self.mutator.spec);
+        }
+        if self.fuzzer.run_test().is_err() {
+            return crash_edges.collect();
+        }
+        let prefix = self.fuzzer.bitmap_buffer();
+        crash_edges.filter(|&i| prefix[i] == 0).collect()
+    }
+
+    /// 对暂存的 crash/timeout 输入分桶，只能在没有激活快照时调用
+    fn flush_triage(&mut self) {
+        for input in std::mem::take(&mut self.pending_triage) {
+            self.triage_crash(&input);
+        }
+    }
+
+    fn triage_crash(&mut self, input: &Input) {
+        let signature = CrashSignature::new(&input.exit_reason, &self.last_packet_edges(input));
+        let raw = triage::raw_description(&input.exit_reason);
+        match self.queue.crashes().record(signature, &input.data, &self.mutator.spec, raw) {
+            Ok((bucket, is_new)) => println!(
+                "[{}] fuzzer: {} bucket {} (hits:{}, {} buckets) {}",
+                self.config.thread_id,
+                if is_new { "new crash" } else { "known crash" },
+                bucket.bucket,
+                bucket.hits,
+                self.queue.crashes().len(),
+                bucket.signature.description
+            ),
+            Err(e) => eprintln!("[{}] fuzzer: failed to record crash: {}", self.config.thread_id, e),
+        }
+    }
+
     fn perform_import(&mut self, seed_import: bool){
         use glob::glob;
 
@@ -369,7 +469,13 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
         for entry in glob(&search_path).expect("Failed to read glob pattern") {
             if let Ok(path) = entry {
                 println!("[!] fuzzer: Trying to import {:?}", path.to_str());
-                let orig = VecGraph::new_from_bin_file(path.to_str().unwrap(), &self.mutator.spec);
+                let orig = match replay::load_bin(&path, &self.mutator.spec) {
+                    Ok(orig) => orig,
+                    Err(e) => {
+                        println!("[!] fuzzer: skipping {:?}: {}", path, e);
+                        continue;
+                    }
+                };
 
                 //完整测一下
                 self.perform_run_import(|det_mutator, rng, storage| {
@@ -406,81 +512,66 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
         None
     }
 
-    fn perform_calibrate_full_bit_flip(
-        &mut self,
-        m1_m2_vec: &VecGraph,
-        snapshot_state: &MutatorSnapshotState,
-        offset: usize,
-    ) -> Option<(TestInfo, usize, usize,usize,bool)> {
-        // 0xff 测量
-        if let Some((test_info, cf_index, vf_index,cfc_index,isstable)) = self.perform_run_get_testinfo(
-            |def_mutator, rng, storage| {
-                // 调用 mutate_data_full_bit_flip 进行变异操作
-                def_mutator.mutate_data_full_bit_flip(m1_m2_vec, snapshot_state, storage, rng, offset)
-            },
-        ) {
-            // 打印测试结果和索引信息
-            // println!("Fullbit Flip Info: {:?}", test_info);
-            // println!("CALIBRATE OFFSET:{}",offset);
-            // println!("CF Index: {}", cf_index);
-            // println!("VF Index: {}", vf_index);
-            // 返回三元组
-            return Some((test_info, cf_index, vf_index, cfc_index, isstable));
-        } else {
-            // 如果测试失败或不有趣，记录相应信息
-            println!("Test failed or was not interesting.");
+    /// 最近一次执行相对基准的边级差异，未开启 record_edge_deltas 时为 None
+    fn probe_edge_delta(&self, baseline: &[(usize, u8)]) -> Option<EdgeDelta> {
+        if !self.config.record_edge_deltas {
+            return None;
         }
-    
-        None
+        let bitmap = class_registry::mask_edges(self.fuzzer.bitmap_buffer(), &self.mask);
+        Some(EdgeDelta::between(baseline, &bitmap))
+    }
+
+    /// 最近一次执行相对基准发生变化的 ijon 槽位，基准槽位未知时为空
+    fn probe_slot_changes(&self, baseline: &Option<Vec<(usize, u64)>>) -> Vec<SlotChange> {
+        baseline
+            .as_ref()
+            .map(|base| ijon_slots::changes(base, self.fuzzer.ijon_max_buffer()))
+            .unwrap_or_default()
     }
 
-    fn perform_calibrate_lowest_bit_flip(
+    /// 多次执行未变异的包，找出取值波动的 bitmap 下标并加入全局屏蔽集合，
+    /// 返回该包基准上观察到的波动下标
+    fn calibrate_var_edges(
         &mut self,
         m1_m2_vec: &VecGraph,
         snapshot_state: &MutatorSnapshotState,
-        offset: usize,
-    ) -> Option<(TestInfo, usize, usize,usize,bool)> {
-        // 0xff 测量
-        if let Some((test_info, cf_index, vf_index,cfc_index,isstable)) = self.perform_run_get_testinfo(
-            |def_mutator, rng, storage| {
-                // 调用 mutate_data_full_bit_flip 进行变异操作
-                def_mutator.mutate_data_lowest_bit_flip(m1_m2_vec, snapshot_state, storage, rng, offset)
-            },
-        ) {
-            // 打印测试结果和索引信息
-            // println!("LOWbit Flip Info: {:?}", test_info);
-            // println!("CALIBRATE OFFSET:{}",offset);
-            // println!("CF Index: {}", cf_index);
-            // println!("VF Index: {}", vf_index);
-            // 返回三元组
-            return Some((test_info, cf_index, vf_index, cfc_index, isstable));
-        } else {
-            // 如果测试失败或不有趣，记录相应信息
-            println!("Test failed or was not interesting.");
+    ) -> Vec<usize> {
+        // 基准重复执行的次数
+        const VAR_CALIBRATION_RUNS: usize = 8;
+        let mut storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
+        self.det_mutator.append_unmutate(m1_m2_vec, snapshot_state, &mut storage, &self.rng);
+
+        let mut runs = vec![];
+        for _ in 0..VAR_CALIBRATION_RUNS {
+            if self.fuzzer.run_test().is_ok() {
+                runs.push(self.fuzzer.bitmap_buffer().to_vec());
+            }
         }
-    
-        None
+        let edges = class_registry::variable_edges(&runs);
+        if self.classes.add_var_edges(&edges) > 0 {
+            checkpoint::save_var_edges(&self.config.workdir_path, &self.classes.var_edges());
+        }
+        edges
     }
 
-    fn perform_calibrate_addition(
+    fn perform_calibrate_operator(
         &mut self,
         m1_m2_vec: &VecGraph,
         snapshot_state: &MutatorSnapshotState,
+        op: &dyn CalibrationOperator,
         offset: usize,
     ) -> Option<(TestInfo, usize, usize,usize,bool)> {
-        // 0xff 测量
         if let Some((test_info, cf_index, vf_index,cfc_index,isstable)) = self.perform_run_get_testinfo(
             |def_mutator, rng, storage| {
-                // 调用 mutate_data_full_bit_flip 进行变异操作
-                def_mutator.mutate_data_addition(m1_m2_vec, snapshot_state, storage, rng, offset)
+                // 调用 mutate_data_calibrated 按算子进行变异操作
+                def_mutator.mutate_data_calibrated(m1_m2_vec, snapshot_state, storage, rng, op, offset)
             },
         ) {
             // 打印测试结果和索引信息
-            // println!("ADD Info: {:?}", test_info);
+            // println!("{} Info: {:?}", op.name(), test_info);
             // println!("CALIBRATE OFFSET:{}",offset);
             // println!("CF Index: {}", cf_index);
             // println!("VF Index: {}", vf_index);
-            // 返回三元组
             return Some((test_info, cf_index, vf_index, cfc_index, isstable));
         } else {
             // 如果测试失败或不有趣，记录相应信息
@@ -490,117 +581,415 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
         None
     }
 
-    fn perform_calibrate_subtraction(
-        &mut self,
-        m1_m2_vec: &VecGraph,
-        snapshot_state: &MutatorSnapshotState,
-        offset: usize,
-    ) -> Option<(TestInfo, usize, usize,usize,bool)> {
-        // 0xff 测量
-        if let Some((test_info, cf_index, vf_index,cfc_index,isstable)) = self.perform_run_get_testinfo(
-            |def_mutator, rng, storage| {
-                // 调用 mutate_data_full_bit_flip 进行变异操作
-                def_mutator.mutate_data_subtraction(m1_m2_vec, snapshot_state, storage, rng, offset)
-            },
-        ) {
-            // // 打印测试结果和索引信息
-            // println!("SUB Info: {:?}", test_info);
-            // println!("CALIBRATE OFFSET:{}",offset);
-            // println!("CF Index: {}", cf_index);
-            // println!("VF Index: {}", vf_index);
-            // 返回三元组
-            return Some((test_info, cf_index, vf_index, cfc_index, isstable));
-        } else {
-            // 如果测试失败或不有趣，记录相应信息
-            println!("Test failed or was not interesting.");
+    /// 长度为 len 的负载在 offsets 上的探测数（多字节算子放不下的偏移不计）
+    fn probe_count(&self, len: usize, offsets: &[usize]) -> u64 {
+        offsets
+            .iter()
+            .map(|&offset| self.operators.iter().filter(|op| offset + op.span() <= len).count() as u64)
+            .sum()
+    }
+
+    /// 按采样策略首轮要探测的探测数
+    fn planned_probes(&self, len: usize, sequence_id: usize, packet_id: usize) -> u64 {
+        self.probe_count(len, &self.sampling.initial_offsets(len, Self::packet_seed(sequence_id, packet_id)))
+    }
+
+    /// 每个包节点的负载（去掉 DataVec 的两字节长度头），下标与 packet_id 对应
+    fn packet_payloads(&self, data: &VecGraph) -> Vec<Vec<u8>> {
+        data.node_iter(&self.mutator.spec)
+            .map(|node| if node.data.len() >= 2 { node.data[2..].to_vec() } else { vec![] })
+            .collect()
+    }
+
+    /// 对刚测完的包做字段推断
+    fn infer_packet(results: &[PacketCalibrationResult], packet_id: usize, payload: &[u8], probed: &[usize]) -> Option<InferredPacket> {
+        let results: Vec<&PacketCalibrationResult> = results.iter().filter(|r| r.packet_id == packet_id).collect();
+        inference::infer_packet(packet_id, payload, &results, Some(probed))
+    }
+
+
+    /// 执行 graph，返回 (cov_bitmap 哈希, 退出类型, 使用的操作数)，作为最小化的判定依据
+    fn minimize_oracle(&mut self, graph: &VecGraph) -> Option<(u64, String, usize)> {
+        {
+            let mut storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
+            storage.copy_from(graph);
         }
-    
-        None
+        let res = self.fuzzer.run_test().ok()?;
+        if res.exitreason == ExitReason::FuzzerError {
+            return None;
+        }
+        let ops_used = std::cmp::min(res.ops_used as usize, graph.node_len(&self.mutator.spec));
+        Some((class_registry::cov_hash(self.fuzzer.bitmap_buffer(), &self.mask), res.exitreason.name().to_string(), ops_used))
     }
 
-    fn save_results_to_json(results: &SequenceCalibrationResults, file_name: &str) -> std::io::Result<()> {
-        let json_output = serde_json::to_string_pretty(results)?;
-        let mut file = File::create(file_name)?;
-        file.write_all(json_output.as_bytes())?;
-        Ok(())
+    /// 逐步删除节点区间（从一半长度到单个节点），保留覆盖率类与退出类型不变的最短节点序列。
+    /// 原始输入两次执行结果不一致时不做最小化
+    fn minimize_graph(&mut self, data: &VecGraph) -> (VecGraph, usize) {
+        // 整个最小化过程使用同一个掩码，其他线程新增的波动下标不影响判定
+        self.mask = self.classes.var_edges();
+        let target = match (self.minimize_oracle(data), self.minimize_oracle(data)) {
+            (Some(a), Some(b)) if a.0 == b.0 && a.1 == b.1 => a,
+            _ => {
+                println!("[Analyzer] input is unstable, skipping minimization");
+                return (data.clone(), data.node_len(&self.mutator.spec));
+            }
+        };
+        let mut ops_used = target.2;
+        let mut cur = data.clone();
+        let mut chunk = std::cmp::max(cur.node_len(&self.mutator.spec) / 2, 1);
+        loop {
+            let mut end = cur.node_len(&self.mutator.spec);
+            while end > 0 {
+                let start = end.saturating_sub(chunk);
+                let mut candidate = VecGraph::empty();
+                self.mutator.drop_range(&cur, start..end, &mut candidate, &self.rng);
+                if candidate.node_len(&self.mutator.spec) > 0 {
+                    if let Some(res) = self.minimize_oracle(&candidate) {
+                        if res.0 == target.0 && res.1 == target.1 {
+                            cur = candidate;
+                            ops_used = res.2;
+                        }
+                    }
+                }
+                end = start;
+            }
+            if chunk == 1 {
+                break;
+            }
+            chunk /= 2;
+        }
+        (cur, ops_used)
     }
-    
 
-    //测量队列所有测试用例
-    pub fn calibrate_all_queue(&mut self) {
-        if self.queue.len() == 0 {
-            eprintln!("Queue is empty. No test cases to calibrate.");
-            return;
+    /// 最小化测试用例，结果保存为 minimized/sequence_{id}.bin（原始输入为 .orig.bin）；
+    /// 续跑时直接使用已保存的最小化结果，保证与断点一致
+    fn minimized_entry(&mut self, id: usize, entry: &Input) -> Input {
+        let dir = format!("{}/minimized", self.config.workdir_path);
+        let path = format!("{}/sequence_{}.bin", dir, id);
+        let spec = self.mutator.spec.clone();
+        // 断点续测时复用上次的最小化结果，文件过期或由其他 spec 写出时重新最小化
+        let resumed = if self.scheduler.resume() && std::path::Path::new(&path).exists() {
+            replay::load_bin(std::path::Path::new(&path), &spec)
+                .map_err(|e| eprintln!("[Analyzer] Failed to load {}: {}, minimizing again", path, e))
+                .ok()
+        } else {
+            None
+        };
+        let (data, ops_used) = if let Some(data) = resumed {
+            let ops_used = self.minimize_oracle(&data).map(|r| r.2).unwrap_or_else(|| data.node_len(&spec));
+            (data, ops_used)
+        } else {
+            let (data, ops_used) = self.minimize_graph(&entry.data);
+            if let Err(e) = std::fs::create_dir_all(&dir) {
+                eprintln!("[Analyzer] Failed to create {}: {}", dir, e);
+            } else {
+                entry.data.write_to_file(&format!("{}/sequence_{}.orig.bin", dir, id), &spec);
+                data.write_to_file(&path, &spec);
+            }
+            (data, ops_used)
+        };
+        println!(
+            "[Analyzer] Minimized test case {}: {} -> {} nodes",
+            id,
+            entry.data.node_len(&spec),
+            data.node_len(&spec)
+        );
+        let mut minimized = entry.clone();
+        minimized.data = Arc::new(data);
+        minimized.ops_used = ops_used;
+        minimized
+    }
+
+    /// 差分校准：导入种子，返回队列中的全部测试用例
+    pub fn import_queue(&mut self) -> Vec<Input> {
+        self.import_seeds();
+        (0..self.queue.len())
+            .filter_map(|id| self.queue.schedule(id).read().ok().map(|entry| entry.clone()))
+            .collect()
+    }
+
+    /// 测试用例中需要校准的包数
+    pub fn num_packets(&self, entry: &Input) -> usize {
+        std::cmp::min(entry.ops_used, entry.data.node_len(&self.mutator.spec))
+    }
+
+    /// 按采样策略，entry 第 packet_id 个包首轮要探测的偏移
+    pub fn sample_offsets(&self, entry: &Input, sequence_id: usize, packet_id: usize) -> Vec<usize> {
+        let len = self.packet_payloads(&entry.data).get(packet_id).map(|p| p.len()).unwrap_or(0);
+        self.sampling.initial_offsets(len, Self::packet_seed(sequence_id, packet_id))
+    }
+
+    /// 差分校准：在 entry 第 packet_id 个包的快照上执行基准与 offsets 上全部算子的探测，
+    /// 返回每次执行的类编号与退出原因，不写断点与结果流；快照创建失败时返回 None
+    pub fn probe_packet(&mut self, entry: &Input, packet_id: usize, offsets: &[usize]) -> Option<PacketProbes> {
+        let mut storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
+        let mutator_state = self.mutator.prepare_snapshot(packet_id, &entry.data, &mut storage, &self.rng);
+        if !self.fuzzer.run_create_snapshot() {
+            return None;
         }
-    
+        let mut m1_m2_vec = VecGraph::empty();
+        m1_m2_vec.copy_from_cutoff(&entry.data, mutator_state.skip_nodes + 1, &self.mutator.spec);
+        let len = m1_m2_vec.get_last_node_data_length(&self.mutator.spec);
+        // 在本构建的基准上标定波动边，整个包使用同一份下标快照
+        self.calibrate_var_edges(&m1_m2_vec, &mutator_state);
+        self.mask = self.classes.var_edges();
+        let baseline = self.perform_calibrate_no_mutation(&m1_m2_vec, &mutator_state).map(ProbeOutcome::from);
+        let mut probes = vec![];
+        let operators = self.operators.clone();
+        for &offset in offsets.iter() {
+            for op in operators.iter().filter(|op| offset + op.span() <= len) {
+                let outcome = self
+                    .perform_calibrate_operator(&m1_m2_vec, &mutator_state, op.as_ref(), offset)
+                    .map(ProbeOutcome::from);
+                probes.push(OperatorProbe { offset, operator: op.name().to_string(), outcome });
+            }
+        }
+        self.fuzzer.delete_snapshot().unwrap();
+        Some(PacketProbes { baseline, probes })
+    }
+
+    /// 把队列中所有测试用例登记到调度器，每个包一个任务
+    fn schedule_all_queue(&mut self) {
         for id in 0..self.queue.len() {
             if let Ok(entry) = self.queue.schedule(id).read() {
-                let entry = entry.clone();
-    
+                let mut entry = entry.clone();
+
+                if self.scheduler.resume() && checkpoint::sequence_done(&self.config.workdir_path, id) {
+                    println!("[Analyzer] Skipping test case {}: already calibrated", id);
+                    continue;
+                }
+
+                // 校准在最小化后的输入上进行
+                if self.config.minimize_seeds {
+                    entry = self.minimized_entry(id, &entry);
+                }
+
                 let num_ops = std::cmp::min(
                     entry.ops_used as usize,
                     entry.data.node_len(&self.mutator.spec),
                 );
 
                 // 获取测试用例的数据
-                // 打印
-                let packet_data_bytes = entry.data.data_as_slice(); // 获取数据切片
+                let packet_data_bytes = entry.data.data_as_slice().to_vec(); // 获取数据切片
                 // 将字节数组转换为十六进制字符串
                 let mut hex_encoded_data = String::new();
-                for byte in packet_data_bytes {
+                for byte in packet_data_bytes.iter() {
                     fmt::write(
                         &mut hex_encoded_data,
                         format_args!("{:02x}", byte), // 使用 format_args 进行格式化
                     ).unwrap(); // 转换为两位的十六进制字符串
                 }
-                
-                // println!("packet_data:{:?}",packet_data_bytes);
-                let mut sequence_results = SequenceCalibrationResults {
-                    sequence_id: id, // 假设队列中每一个测试用例是一个序列
-                    cal_time: 0.0,
-                    pkt_number: num_ops,
-                    packets_cali_result: Vec::new(),
-                    raw_data: Some(hex_encoded_data),
-                };
 
-                let start_time = self.queue.get_runtime_as_secs_f32();
+                // 未完成的序列重新开始写结果流，断点中已测完的包会被重新追加
+                if num_ops > 0 {
+                    let path = result_stream::stream_path(&self.config.workdir_path, id);
+                    if let Err(e) = result_stream::create(&path, id, num_ops, Some(packet_data_bytes)) {
+                        eprintln!("[Analyzer] Failed to create {:?}: {}", path, e);
+                    }
+                }
+
+                let probes: u64 = self
+                    .packet_payloads(&entry.data)
+                    .iter()
+                    .take(num_ops)
+                    .enumerate()
+                    .map(|(packet_id, p)| self.planned_probes(p.len(), id, packet_id))
+                    .sum();
+                self.stats.plan_probes(probes);
+                println!("[Analyzer] Scheduling test case {} with {} packets", id, num_ops);
+                self.scheduler.add_sequence(id, entry, num_ops, Some(hex_encoded_data));
+            } else {
+                eprintln!("[Analyzer] Failed to read entry for id {}", id);
+            }
+        }
+    }
+
+    //测量调度器分配的所有包，直到任务队列为空
+    pub fn calibrate_all_queue(&mut self) {
+        if self.queue.len() == 0 {
+            eprintln!("Queue is empty. No test cases to calibrate.");
+            return;
+        }
+
+        while let Some(job) = self.scheduler.next_job() {
+            println!(
+                "[Analyzer] Thread {} calibrating test case {} packet {}/{} ({} jobs left)",
+                self.config.thread_id,
+                job.sequence_id,
+                job.packet_id + 1,
+                job.num_ops,
+                self.scheduler.num_pending_jobs()
+            );
+
+            let mut state = self.load_packet_checkpoint(&job);
+            if !state.probed.is_empty() || state.done {
+                let len = self.packet_payloads(&job.entry.data).get(job.packet_id).map(|p| p.len()).unwrap_or(0);
+                let planned = self.planned_probes(len, job.sequence_id, job.packet_id);
+                let done = if state.done { planned } else { std::cmp::min(planned, self.probe_count(len, &state.probed)) };
+                self.stats.skip_probes(done);
+            }
+            let execs = self.stats.execs(self.config.thread_id);
+            if !state.done {
+                self.calibrate_with_snap(&job, &mut state);
+            }
+            let execs = self.stats.execs(self.config.thread_id) - execs;
+            let PacketCheckpoint { results, cal_time, var_edges, probed, truncated, .. } = state;
+
+            // 包测完即推断，无需等待整个序列
+            let payloads = self.packet_payloads(&job.entry.data);
+            let payload = payloads.get(job.packet_id).map(|p| &p[..]).unwrap_or(&[]);
+            let inferred = Self::infer_packet(&results, job.packet_id, payload, &probed);
+            if let Some(packet) = &inferred {
+                println!("\n[Analyzer] sequence {} packet {}: {}", job.sequence_id, job.packet_id, packet.layout());
+            }
+
+            // 包测完即追加到该序列的结果流
+            let path = result_stream::stream_path(&self.config.workdir_path, job.sequence_id);
+            let record = ResultRecord::Packet {
+                packet_id: job.packet_id,
+                cal_time,
+                results: results.clone(),
+                var_edges: var_edges.clone(),
+                probed: Some(probed.clone()),
+                truncated,
+            };
+            if let Err(e) = result_stream::append(&path, &record) {
+                eprintln!("[Analyzer] Failed to append packet {} to {:?}: {}", job.packet_id, path, e);
+            }
+
+            let packet = PacketCalibration { results, inferred, cal_time, var_edges, probed, truncated, execs };
+            if let Some(sequence) = self.scheduler.finish_packet(&job, packet) {
+                self.save_sequence(&sequence);
+            }
+        }
+        self.stats.update(self.config.thread_id, |stats| stats.sequence_id = None);
+    }
 
+    /// 续跑时读取该包的断点：已完成的直接复用，未测完的从断点继续。
+    /// 类编号由所有线程共享，任意线程都可以接着测
+    fn load_packet_checkpoint(&self, job: &CalibrationJob) -> PacketCheckpoint {
+        if self.scheduler.resume() {
+            if let Some(mut cp) = checkpoint::load_packet(&self.config.workdir_path, job.sequence_id, job.packet_id) {
+                // 旧版断点没有掩码快照，无法保证后续探测与已有结果的类编号可比，重新测量该包
+                if cp.mask.is_none() && !cp.done {
+                    println!(
+                        "[Analyzer] Checkpoint of test case {} packet {} predates edge mask snapshots, recalibrating",
+                        job.sequence_id, job.packet_id
+                    );
+                    return PacketCheckpoint::new(job.sequence_id, job.packet_id);
+                }
+                // 旧版断点只记录了 next_offset，之前的偏移都已测完
+                if cp.probed.is_empty() {
+                    cp.probed = (0..cp.next_offset).collect();
+                }
                 println!(
-                    "[Analyzer] Calibrating test case {} with {} packets...",
-                    id, num_ops
+                    "[Analyzer] Resuming test case {} packet {} at offset {}",
+                    job.sequence_id, job.packet_id, cp.next_offset
                 );
+                return cp;
+            }
+        }
+        PacketCheckpoint::new(job.sequence_id, job.packet_id)
+    }
 
-                for snap_point in 0..num_ops {
-                    print!("\r\x1B[Kpacket: {}/{}", snap_point+1, num_ops);  // \x1B[K 清除整行
-                    io::stdout().flush().unwrap();
-                    self.calibrate_with_snap(&entry, snap_point, &mut sequence_results,num_ops);
-                    // self.calibrate_with_no_snap(&entry, snap_point, &mut sequence_results);
-                }
+    /// 先写等价类注册表再写包断点，保证断点引用的类编号一定已落盘
+    fn save_packet_checkpoint(&self, state: &mut PacketCheckpoint, start_time: f32, base_time: f32) {
+        state.cal_time = base_time + (self.queue.get_runtime_as_secs_f32() - start_time);
+        self.classes.save();
+        checkpoint::save_packet(&self.config.workdir_path, state);
+    }
 
-                let end_time = self.queue.get_runtime_as_secs_f32();
-                sequence_results.cal_time = end_time - start_time;
-                let file_name = format!("calibration_results_sequence_{}.json", id);
-                let output_path = std::path::Path::new(&self.config.workdir_path).join(file_name);
-    
-                if let Err(e) = Self::save_results_to_json(&sequence_results,  output_path.to_str().unwrap()) {
-                    eprintln!("\n[Analyzer] Failed to save results for sequence {}: {}", id, e);
-                } else {
-                    println!("\n[Analyzer] Successfully saved results to {:?}", output_path);
-                }
-            } else {
-                eprintln!("\n[Analyzer] Failed to read entry for id {}", id);
+    /// 写出一个完整序列的测量结果与推断结果
+    fn save_sequence(&self, sequence: &SequenceCalibration) {
+        let id = sequence.results.sequence_id;
+        let output_path = result_stream::stream_path(&self.config.workdir_path, id);
+        let footer = ResultRecord::Footer { cal_time: sequence.results.cal_time };
+        if let Err(e) = result_stream::append(&output_path, &footer) {
+            eprintln!("\n[Analyzer] Failed to save results for sequence {}: {}", id, e);
+        } else {
+            println!("\n[Analyzer] Successfully saved results to {:?}", output_path);
+            checkpoint::remove_sequence(&self.config.workdir_path, id, sequence.results.pkt_number);
+        }
+
+        match sequence.inferred.write_reports(std::path::Path::new(&self.config.workdir_path)) {
+            Ok(files) => println!("[Analyzer] Inferred fields saved to {:?}", files),
+            Err(e) => eprintln!("[Analyzer] Failed to save inferred fields for sequence {}: {}", id, e),
+        }
+    }
+
+    /// 在 offset 处依次执行每个算子的探测，结果追加到 state
+    fn probe_offset(
+        &mut self,
+        m1_m2_vec: &VecGraph,
+        mutator_state: &MutatorSnapshotState,
+        state: &mut PacketCheckpoint,
+        offset: usize,
+        calibrate_len: usize,
+    ) {
+        let operators = self.operators.clone();
+        for op in operators.iter() {
+            // 多字节算子在负载末尾放不下时不测量
+            if offset + op.span() > calibrate_len {
+                continue;
+            }
+            let res = self.perform_calibrate_operator(m1_m2_vec, mutator_state, op.as_ref(), offset);
+            self.stats.update(self.config.thread_id, |stats| {
+                stats.probes_done += 1;
+                stats.packet_probes_left = stats.packet_probes_left.saturating_sub(1);
+                stats.unstable_probes += res.as_ref().map(|r| !r.4 as u64).unwrap_or(0);
+            });
+            if let Some((test_info, cf, vf, cfc, st)) = res {
+                state.results.push(PacketCalibrationResult {
+                    packet_id: state.packet_id,
+                    offset,
+                    stable: st,
+                    mutation_operator: op.name().to_string(),
+                    cf_index: cf,
+                    vf_index: vf,
+                    cfc_index: cfc,
+                    rc_index: self.classes.handle_response(&test_info.hprintf),
+                    width: op.span(),
+                    big_endian: op.big_endian(),
+                    edge_delta: self.probe_edge_delta(&state.baseline_edges),
+                    slot_changes: self.probe_slot_changes(&state.baseline_slots),
+                });
             }
         }
     }
-    
+
+    /// offset 处各算子探测得到的类编号，adaptive 采样据此判断相邻偏移的行为是否变化
+    fn offset_signature(results: &[PacketCalibrationResult], offset: usize) -> Vec<(&str, usize, usize, usize, usize)> {
+        results
+            .iter()
+            .filter(|r| r.offset == offset && r.mutation_operator != inference::BASELINE_OPERATOR)
+            .map(|r| (r.mutation_operator.as_str(), r.cf_index, r.vf_index, r.cfc_index, r.rc_index))
+            .collect()
+    }
+
+    /// 随机采样的种子，同一个包在每次运行（包括续跑）中抽到相同的偏移
+    fn packet_seed(sequence_id: usize, packet_id: usize) -> u64 {
+        let mut buf = (sequence_id as u64).to_le_bytes().to_vec();
+        buf.extend_from_slice(&(packet_id as u64).to_le_bytes());
+        hash::hash64(&buf, buf.len())
+    }
+
+    /// 本包分到的预算是否已经用完
+    fn budget_spent(&self, budget: &SequenceBudget, start: (std::time::Instant, u64)) -> bool {
+        let (since, execs) = start;
+        budget.secs.map(|secs| since.elapsed().as_secs_f32() >= secs).unwrap_or(false)
+            || budget.execs.map(|max| self.stats.execs(self.config.thread_id) - execs >= max).unwrap_or(false)
+    }
 
     #[inline]
     fn calibrate_with_snap(
-        &mut self, entry: &Input,
-        snapshot_cutoff: usize, 
-        sequence_results: &mut SequenceCalibrationResults,
-        num_ops:usize,
+        &mut self, job: &CalibrationJob,
+        state: &mut PacketCheckpoint,
     ) {
+        let entry = &job.entry;
+        let snapshot_cutoff = job.packet_id;
+        let num_ops = job.num_ops;
+        let start_time = self.queue.get_runtime_as_secs_f32();
+        let base_time = state.cal_time;
         let mut storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
         let mutator_state = self.mutator.prepare_snapshot(snapshot_cutoff, &entry.data, &mut storage, &self.rng);
         //create the snapshot
@@ -615,86 +1004,124 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
             let m1_m2_len = mutator_state.skip_nodes + 1;
             m1_m2_vec.copy_from_cutoff(&entry.data,m1_m2_len, &self.mutator.spec);
             let calibrate_len = m1_m2_vec.get_last_node_data_length(&self.mutator.spec);
+            self.stats.update(self.config.thread_id, |stats| {
+                stats.sequence_id = Some(job.sequence_id);
+                stats.packet_id = snapshot_cutoff;
+                stats.num_packets = num_ops;
+                stats.offset = state.next_offset;
+                stats.packet_len = calibrate_len;
+            });
             // let tested_packet = 
             // println!("START CALIBRATE");
-            let standard =self.perform_calibrate_no_mutation(&m1_m2_vec, &mutator_state);
-            if let Some((_, cf, vf,cfc,st)) = standard {
-                let standard_packet = PacketCalibrationResult {
-                    packet_id: snapshot_cutoff, // 当前包ID
-                    offset: 0, // 标准结果不依赖偏移量
-                    stable: st,
-                    mutation_operator: "None".to_string(),    // 使用的变异算子
-                    cf_index: cf,
-                    vf_index: vf,
-                    cfc_index: cfc,
-                };
-                sequence_results.packets_cali_result.push(standard_packet);
-            } else {
-                println!("Standard calibration failed or returned no result.");
-            }            
-
-            for offset in 0..calibrate_len {
-                print!("\r\x1B[K packet:{}/{} offset: {}/{}",snapshot_cutoff+1 ,num_ops,offset, calibrate_len);  // \x1B[K 清除整行
-                io::stdout().flush().unwrap();
-
-                if let Some((_test_info, cf, vf,cfc,st)) =
-                    self.perform_calibrate_lowest_bit_flip(&m1_m2_vec, &mutator_state, offset)
-                    {
-                        sequence_results.packets_cali_result.push(PacketCalibrationResult {
-                            packet_id: snapshot_cutoff,
-                            offset,
-                            stable: st,
-                            mutation_operator:"LBF".to_string(),
-                            cf_index:cf,
-                            vf_index:vf,
-                            cfc_index: cfc,
-                        });
+            // 续跑时基准测量与掩码快照已在断点中；基准与该包的全部探测使用同一个掩码快照，
+            // 其他线程此后新增的波动下标不会改变本包的类编号
+            if let Some(mask) = &state.mask {
+                self.mask = mask.clone();
+            }
+            if !state.results.iter().any(|r| r.mutation_operator == inference::BASELINE_OPERATOR) {
+                state.var_edges = self.calibrate_var_edges(&m1_m2_vec, &mutator_state);
+                self.mask = self.classes.var_edges();
+                state.mask = Some(self.mask.clone());
+                if !state.var_edges.is_empty() {
+                    println!(
+                        "\n[Analyzer] sequence {} packet {}: {} variable edges",
+                        job.sequence_id, snapshot_cutoff, state.var_edges.len()
+                    );
+                }
+                let standard =self.perform_calibrate_no_mutation(&m1_m2_vec, &mutator_state);
+                if let Some((test_info, cf, vf,cfc,st)) = standard {
+                    let standard_packet = PacketCalibrationResult {
+                        packet_id: snapshot_cutoff, // 当前包ID
+                        offset: 0, // 标准结果不依赖偏移量
+                        stable: st,
+                        mutation_operator: inference::BASELINE_OPERATOR.to_string(),    // 使用的变异算子
+                        cf_index: cf,
+                        vf_index: vf,
+                        cfc_index: cfc,
+                        rc_index: self.classes.handle_response(&test_info.hprintf),
+                        width: 1,
+                        big_endian: None,
+                        edge_delta: None,
+                        slot_changes: vec![],
+                    };
+                    state.baseline_slots = Some(ijon_slots::sparse(self.fuzzer.ijon_max_buffer()));
+                    if self.config.record_edge_deltas {
+                        state.baseline_edges = edge_delta::sparse(&class_registry::mask_edges(self.fuzzer.bitmap_buffer(), &self.mask));
                     }
+                    state.results.push(standard_packet);
+                    self.save_packet_checkpoint(state, start_time, base_time);
+                } else {
+                    println!("Standard calibration failed or returned no result.");
+                }
+            }
 
-                    if let Some((_test_info, cf, vf,cfc,st)) =
-                self.perform_calibrate_full_bit_flip(&m1_m2_vec, &mutator_state, offset)
-                    {
-                        sequence_results.packets_cali_result.push(PacketCalibrationResult {
-                            packet_id: snapshot_cutoff,
-                            offset,
-                            stable: st,
-                            mutation_operator:"FBF".to_string(),
-                            cf_index:cf,
-                            vf_index:vf,
-                            cfc_index: cfc,
-                        });
+            // 按采样策略选出首轮偏移，adaptive 在首轮之后对行为变化的区间加密一次；
+            // 序列预算耗尽时停止，已测的偏移记录在 probed 中
+            let budget = self.scheduler.packet_budget(job);
+            let budget_start = (std::time::Instant::now(), self.stats.execs(self.config.thread_id));
+            let seed = Self::packet_seed(job.sequence_id, snapshot_cutoff);
+            let mut probed: BTreeSet<usize> = state.probed.iter().copied().collect();
+            let mut pending: Vec<usize> = self
+                .sampling
+                .initial_offsets(calibrate_len, seed)
+                .into_iter()
+                .filter(|o| !probed.contains(o))
+                .collect();
+            let probes_left = self.probe_count(calibrate_len, &pending);
+            self.stats.update(self.config.thread_id, |stats| stats.packet_probes_left = probes_left);
+            state.truncated = false;
+            let mut densified = false;
+            // 断点按时间间隔落盘：每个偏移都重写整个 results 的代价随偏移数平方增长
+            const CHECKPOINT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(10);
+            let mut last_checkpoint = std::time::Instant::now();
+            loop {
+                for (i, &offset) in pending.iter().enumerate() {
+                    if self.budget_spent(&budget, budget_start) {
+                        state.truncated = true;
+                        self.stats.skip_probes(self.probe_count(calibrate_len, &pending[i..]));
+                        println!(
+                            "\n[Analyzer] sequence {} packet {}: budget exhausted after {} offsets",
+                            job.sequence_id, snapshot_cutoff, probed.len()
+                        );
+                        break;
                     }
-
-                    if let Some((_test_info, cf, vf,cfc,st)) =
-                self.perform_calibrate_addition(&m1_m2_vec, &mutator_state, offset)
-                    {
-                        sequence_results.packets_cali_result.push(PacketCalibrationResult {
-                            packet_id: snapshot_cutoff,
-                            offset,
-                            stable: st,
-                            mutation_operator:"ADD".to_string(),
-                            cf_index:cf,
-                            vf_index:vf,
-                            cfc_index: cfc,
-                        });
+                    if !self.stats.quiet() {
+                        print!("\r\x1B[K [{}] packet:{}/{} offset: {}/{}",self.config.thread_id,snapshot_cutoff+1 ,num_ops,offset, calibrate_len);  // \x1B[K 清除整行
+                        io::stdout().flush().unwrap();
                     }
-
-                    if let Some((_test_info, cf, vf,cfc,st)) =
-                    self.perform_calibrate_subtraction(&m1_m2_vec, &mutator_state, offset)
-                    {
-                        sequence_results.packets_cali_result.push(PacketCalibrationResult {
-                            packet_id: snapshot_cutoff,
-                            offset,
-                            stable: st,
-                            mutation_operator:"SUB".to_string(),
-                            cf_index:cf,
-                            vf_index:vf,
-                            cfc_index: cfc,
-                        });
+                    self.stats.update(self.config.thread_id, |stats| stats.offset = offset);
+                    self.probe_offset(&m1_m2_vec, &mutator_state, state, offset, calibrate_len);
+                    probed.insert(offset);
+                    state.probed.push(offset);
+                    state.next_offset = std::cmp::max(state.next_offset, offset + 1);
+                    if last_checkpoint.elapsed() >= CHECKPOINT_INTERVAL {
+                        self.save_packet_checkpoint(state, start_time, base_time);
+                        last_checkpoint = std::time::Instant::now();
                     }
+                }
+                if densified || state.truncated {
+                    break;
+                }
+                densified = true;
+                let results = &state.results;
+                pending = self.sampling.densify(&probed, |a, b| Self::offset_signature(results, a) != Self::offset_signature(results, b));
+                if pending.is_empty() {
+                    break;
+                }
+                let extra = self.probe_count(calibrate_len, &pending);
+                self.stats.plan_probes(extra);
+                self.stats.update(self.config.thread_id, |stats| stats.packet_probes_left += extra);
             }
             // println!("Calibration completed for pkt: {}", snapshot_cutoff);
             self.fuzzer.delete_snapshot().unwrap();
+            // 只有真正测过的包才标记完成，快照失败的包续跑时重新测量
+            state.done = true;
+            self.save_packet_checkpoint(state, start_time, base_time);
+        } else {
+            eprintln!(
+                "\n[Analyzer] sequence {} packet {}: couldn't create snapshot, packet left unfinished",
+                job.sequence_id, snapshot_cutoff
+            );
         }
     }
 
@@ -725,7 +1152,7 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
     //                 vf_index: vf,
     //                 cfc_index: cfc,
     //             };
-    //             sequence_results.packets_cali_result.push(standard_packet);
+    //             packet_results.push(standard_packet);
     //         } else {
     //             println!("Standard calibration failed or returned no result.");
     //         }            
@@ -735,7 +1162,7 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
     //             if let Some((_test_info, cf, vf,cfc)) =
     //             self.perform_calibrate_lowest_bit_flip(&m1_m2_vec, &MutatorSnapshotState::none(), offset)
     //             {
-    //                 sequence_results.packets_cali_result.push(PacketCalibrationResult {
+    //                 packet_results.push(PacketCalibrationResult {
     //                     packet_id: snapshot_cutoff,
     //                     offset,
     //                     mutation_operator:"LBF".to_string(),
@@ -748,7 +1175,7 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
     //             if let Some((_test_info, cf, vf,cfc)) =
     //             self.perform_calibrate_full_bit_flip(&m1_m2_vec, &MutatorSnapshotState::none(), offset)
     //             {
-    //                 sequence_results.packets_cali_result.push(PacketCalibrationResult {
+    //                 packet_results.push(PacketCalibrationResult {
     //                     packet_id: snapshot_cutoff,
     //                     offset,
     //                     mutation_operator:"FBF".to_string(),
@@ -761,7 +1188,7 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
     //             if let Some((_test_info, cf, vf,cfc)) =
     //             self.perform_calibrate_addition(&m1_m2_vec, &MutatorSnapshotState::none(), offset)
     //             {
-    //                 sequence_results.packets_cali_result.push(PacketCalibrationResult {
+    //                 packet_results.push(PacketCalibrationResult {
     //                     packet_id: snapshot_cutoff,
     //                     offset,
     //                     mutation_operator:"ADD".to_string(),
@@ -774,7 +1201,7 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
     //             if let Some((_test_info, cf, vf,cfc)) =
     //             self.perform_calibrate_subtraction(&m1_m2_vec, &MutatorSnapshotState::none(), offset)
     //             {
-    //                 sequence_results.packets_cali_result.push(PacketCalibrationResult {
+    //                 packet_results.push(PacketCalibrationResult {
     //                     packet_id: snapshot_cutoff,
     //                     offset,
     //                     mutation_operator:"SUB".to_string(),
@@ -791,14 +1218,20 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
 
     //开始
     pub fn run(&mut self) {
-        use glob::glob;
         use std::time::Duration;
-        //0号线程对应的fuzzer先导入测试用例：perform_import(true)
+        //0号线程对应的fuzzer先导入测试用例：perform_import(true)，并把测量任务登记到调度器
         if self.config.thread_id == 0 {
-            self.perform_import(true);
+            let _guard = ImportGuard(self.scheduler.clone());
+            self.import_seeds();
+            self.schedule_all_queue();
+            self.scheduler.set_ready();
         }
         else{
-            while glob(&format!("{}/seeds/*.bin",self.config.workdir_path)).expect("Failed to read glob pattern").count() != 0{
+            while !self.scheduler.is_ready() {
+                if self.scheduler.is_failed() {
+                    eprintln!("[Analyzer] thread {}: seed import failed, exiting", self.config.thread_id);
+                    return;
+                }
                 std::thread::sleep(Duration::from_millis(1000));
             }
         }
@@ -807,6 +1240,151 @@ impl<Fuzz: FuzzRunner + GetStructStorage> SegmentAnalyzer<Fuzz> {
     
     }
 
+    /// 对 entry 做一次 havoc 变异并执行，发现新覆盖率时保存并加入队列，返回是否加入
+    fn perform_havoc(&mut self, entry: &Input, snapshot: &MutatorSnapshotState) -> bool {
+        let (seed_x, seed_y) = (self.master_rng.next_u64(), self.master_rng.next_u64());
+        self.rng.set_full_seed(seed_x, seed_y);
+        let strategy = {
+            let mut storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
+            self.mutator.mutate(&entry.data, entry.ops_used, &entry.custom_dict, snapshot, &self.queue, &mut storage, &self.rng)
+        };
+        let start = std::time::Instant::now();
+        let exec_res = match self.fuzzer.run_test() {
+            Ok(exec_res) => exec_res,
+            Err(_) => return false,
+        };
+        let time = start.elapsed();
+        self.stats.record_exec(self.config.thread_id, &exec_res.exitreason);
+        let new_bytes = match self.queue.check_new_bytes(self.fuzzer.bitmap_buffer(), &exec_res.exitreason, strategy) {
+            Some(new_bytes) => new_bytes,
+            None => return false,
+        };
+        let data = {
+            let storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
+            self.mutator.dump_graph(&storage)
+        };
+        let ops_used = std::cmp::min(exec_res.ops_used as usize, data.node_len(&self.mutator.spec));
+        let mut input = Input::new(
+            data,
+            strategy,
+            new_bytes,
+            Bitmap::new_from_buffer(self.fuzzer.bitmap_buffer()),
+            exec_res.exitreason.clone(),
+            ops_used,
+            time,
+        );
+        input.parent_snapshot_position = snapshot.skip_nodes;
+        input.parent_id = entry.id;
+        self.new_input(&input);
+        self.queue.add(input, &self.mutator.spec).is_some()
+    }
+
+    /// 一轮模糊测试：从队列中选出一个输入，随机选择快照位置，在快照之后做若干次 havoc 变异
+    fn fuzz_round(&mut self) {
+        // 每轮（每个快照）执行的变异次数
+        const HAVOC_ITERS: usize = 50;
+        let entry = match self.queue.select_for_fuzzing(&self.rng) {
+            Some(entry) => entry.read().unwrap().clone(),
+            None => return,
+        };
+        let ops_used = std::cmp::min(entry.ops_used, entry.data.node_len(&self.mutator.spec));
+        let snapshot_cutoff = if ops_used > 1 && self.mutator.spec.snapshot_node_id.is_some() && self.rng.gen_range(0, 2) == 0 {
+            self.rng.gen_range(1, ops_used)
+        } else {
+            0
+        };
+        self.stats.update(self.config.thread_id, |stats| {
+            stats.sequence_id = Some(entry.id.as_usize());
+            stats.packet_id = snapshot_cutoff;
+            stats.num_packets = ops_used;
+        });
+
+        let mut found = 0;
+        let mut iters = 0;
+        if snapshot_cutoff > 0 {
+            let snapshot = {
+                let mut storage = self.fuzzer.get_struct_storage(self.mutator.spec.checksum);
+                self.mutator.prepare_snapshot(snapshot_cutoff, &entry.data, &mut storage, &self.rng)
+            };
+            if self.fuzzer.run_create_snapshot() {
+                for _ in 0..HAVOC_ITERS {
+                    if self.perform_havoc(&entry, &snapshot) {
+                        found += 1;
+                    }
+                }
+                iters = HAVOC_ITERS;
+                self.fuzzer.delete_snapshot().unwrap();
+            }
+        } else {
+            let snapshot = MutatorSnapshotState::none();
+            for _ in 0..HAVOC_ITERS {
+                if self.perform_havoc(&entry, &snapshot) {
+                    found += 1;
+                }
+            }
+            iters = HAVOC_ITERS;
+        }
+        self.flush_triage();
+        self.queue.update_total_execs(iters as u64);
+        self.queue.report_fuzz_round(entry.id, iters, found);
+    }
+
+    /// 覆盖率引导的模糊测试：0号线程导入种子后所有线程从共享队列中选取输入变异，
+    /// 给定 budget 时到时退出，否则一直运行
+    pub fn fuzz(&mut self, budget: Option<std::time::Duration>) {
+        use std::time::{Duration, Instant};
+        // 写统计信息的间隔
+        const STATS_INTERVAL: Duration = Duration::from_secs(10);
+        if self.config.thread_id == 0 {
+            let _guard = ImportGuard(self.scheduler.clone());
+            self.import_seeds();
+            self.scheduler.set_ready();
+        } else {
+            while !self.scheduler.is_ready() {
+                if self.scheduler.is_failed() {
+                    eprintln!("[Analyzer] thread {}: seed import failed, exiting", self.config.thread_id);
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(1000));
+            }
+        }
+        if self.queue.len() == 0 {
+            eprintln!("Queue is empty. No test cases to fuzz.");
+            return;
+        }
+
+        let start = Instant::now();
+        let mut last_stats = start;
+        while budget.map(|b| start.elapsed() < b).unwrap_or(true) {
+            self.fuzz_round();
+            if self.config.thread_id == 0 && last_stats.elapsed() >= STATS_INTERVAL {
+                self.queue.write_stats();
+                self.save_queue();
+                last_stats = Instant::now();
+            }
+        }
+        if self.config.thread_id == 0 {
+            self.queue.write_stats();
+            self.save_queue();
+        }
+    }
+
+    fn save_queue(&self) {
+        if let Err(e) = self.queue.save(&self.mutator.spec) {
+            eprintln!("[{}] fuzzer: failed to save the queue: {}", self.config.thread_id, e);
+        }
+    }
+
+    /// 导入 workdir/seeds 中的种子并保存队列；队列已从 queue_state.msgp 恢复时跳过
+    fn import_seeds(&mut self) {
+        if self.queue.len() > 0 {
+            println!("[!] fuzzer: continuing with {} restored inputs", self.queue.len());
+            return;
+        }
+        self.perform_import(true);
+        self.save_queue();
+    }
+
     pub fn shutdown(&mut self) {
         self.fuzzer.shutdown().unwrap();
     }
@@ -1,6 +1,9 @@
+use crate::edge_delta;
 use crate::fuzz_runner::ExitReason;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub enum StorageReason{
     Bitmap(BitmapStorageReason),
     // IjonMax(IjonMaxStorageReason),
@@ -30,7 +33,7 @@ impl StorageReason{
 /// 
 /// 发现新的代码后，记录：覆盖率bitmap偏移、旧值old、新值new
 /// 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
 pub struct BitmapStorageReason {
     pub index: usize,
     pub old: u8,
@@ -94,6 +97,37 @@ impl BitmapHandler {
     pub fn normal_bitmap(&self) -> &Bitmap{ //返回bitmap的noramal
         return &self.normal
     }
+
+    /// 四种全局bitmap的稀疏表示，用于保存队列
+    pub fn state(&self) -> BitmapHandlerState {
+        BitmapHandlerState {
+            size: self.size,
+            normal: self.normal.sparse(),
+            crash: self.crash.sparse(),
+            timeout: self.timeout.sparse(),
+            invalid_write_to_payload: self.invalid_write_to_payload.sparse(),
+        }
+    }
+
+    pub fn from_state(state: &BitmapHandlerState) -> Self {
+        Self {
+            normal: Bitmap::from_sparse(state.size, &state.normal),
+            crash: Bitmap::from_sparse(state.size, &state.crash),
+            timeout: Bitmap::from_sparse(state.size, &state.timeout),
+            invalid_write_to_payload: Bitmap::from_sparse(state.size, &state.invalid_write_to_payload),
+            size: state.size,
+        }
+    }
+}
+
+/// BitmapHandler 的可序列化形式，bitmap 只记录非零项 (下标, 值)
+#[derive(Serialize, Deserialize)]
+pub struct BitmapHandlerState {
+    pub size: usize,
+    pub normal: Vec<(usize, u8)>,
+    pub crash: Vec<(usize, u8)>,
+    pub timeout: Vec<(usize, u8)>,
+    pub invalid_write_to_payload: Vec<(usize, u8)>,
 }
 
 #[derive(Clone)]
@@ -166,6 +200,20 @@ impl Bitmap {
         return &self.bits;
     }
 
+    /// 非零项 (下标, 值)
+    pub fn sparse(&self) -> Vec<(usize, u8)> {
+        edge_delta::sparse(&self.bits)
+    }
+
+    /// 由 sparse 的结果恢复大小为 size 的 bitmap，越界的下标被忽略
+    pub fn from_sparse(size: usize, entries: &[(usize, u8)]) -> Self {
+        let mut bits = vec![0; size];
+        for &(i, v) in entries.iter().filter(|(i, _)| *i < size) {
+            bits[i] = v;
+        }
+        Self { bits }
+    }
+
     // pub fn ijon_max_vals(&self) -> &[u64] {
     //     return &self.ijon_max;
     // }
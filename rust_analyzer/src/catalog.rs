@@ -0,0 +1,422 @@
+//! 消息类型目录：把所有结果文件中的包按「前若干字节 + 基准 cf 类 + 长度档位」聚成消息类型，
+//! 同一类型的各个包分别做字段推断后逐偏移投票，得到合并的字段布局与每个偏移的置信度
+//! （多数类型的票数 / 测量过该偏移的包数），并保留若干示例包。
+//!
+//! 长度档位按 2 的幂划分，同一类型的包长度可以不同；未测量的偏移（UNKNOWN）不参与投票。
+
+use crate::analyzer::SequenceCalibrationResults;
+use crate::inference::{self, FieldType};
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 默认参与聚类的前缀字节数
+pub const DEFAULT_PREFIX_LEN: usize = 4;
+/// 每种类型保留的示例包个数
+const MAX_EXAMPLES: usize = 5;
+/// 参与投票的字段类型，平票时取靠前的
+const VOTE_TYPES: [FieldType; 4] = [FieldType::Control, FieldType::Delimiter, FieldType::Flow, FieldType::Data];
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 长度档位：0 为空包，否则为 floor(log2(len)) + 1
+fn length_class(len: usize) -> u32 {
+    usize::BITS - len.leading_zeros()
+}
+
+/// 聚类键：(前缀, 基准 cf 类, 长度档位)
+type ClusterKey = (Vec<u8>, usize, u32);
+
+fn cluster_key(data: &[u8], baseline_cf: usize, prefix_len: usize) -> ClusterKey {
+    (data[..std::cmp::min(prefix_len, data.len())].to_vec(), baseline_cf, length_class(data.len()))
+}
+
+/// 合并后某个偏移的推断结果
+#[derive(Serialize, Clone, Debug)]
+pub struct OffsetProfile {
+    pub offset: usize,
+    pub field_type: FieldType,
+    pub confidence: f32,   // 多数类型的票数 / measured
+    pub measured: usize,   // 长度覆盖该偏移且测量过的包数
+    pub cf_sensitive: f32, // 所有算子都改变了 cf 的包所占比例（cf_mask）
+}
+
+/// 合并后的字段，confidence 为字段内各偏移置信度的平均值
+#[derive(Serialize, Clone, Debug)]
+pub struct CatalogField {
+    pub start: usize,
+    pub end: usize,
+    pub field_type: FieldType,
+    pub confidence: f32,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ExamplePacket {
+    pub source: PathBuf,
+    pub sequence_id: usize,
+    pub packet_id: usize,
+    pub data: String, // 十六进制
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TypeProfile {
+    pub name: String,
+    pub prefix: String, // 十六进制
+    pub baseline_cf: usize,
+    pub min_len: usize,
+    pub max_len: usize,
+    pub members: usize,
+    pub fields: Vec<CatalogField>,
+    pub profile: Vec<OffsetProfile>,
+    pub examples: Vec<ExamplePacket>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Catalog {
+    pub prefix_len: usize,
+    pub packets: usize,
+    pub types: Vec<TypeProfile>,
+    #[serde(skip)]
+    index: BTreeMap<ClusterKey, usize>, // 聚类键 -> types 下标
+}
+
+/// 聚类过程中一个类型的累计量
+#[derive(Default)]
+struct Cluster {
+    min_len: usize,
+    max_len: usize,
+    members: usize,
+    votes: Vec<[usize; VOTE_TYPES.len()]>,
+    cf_sensitive: Vec<usize>,
+    examples: Vec<ExamplePacket>,
+}
+
+impl Cluster {
+    fn add(&mut self, packet: &inference::InferredPacket, example: ExamplePacket) {
+        let len = packet.data.len();
+        self.min_len = if self.members == 0 { len } else { std::cmp::min(self.min_len, len) };
+        self.max_len = std::cmp::max(self.max_len, len);
+        self.members += 1;
+        if self.votes.len() < len {
+            self.votes.resize(len, [0; VOTE_TYPES.len()]);
+            self.cf_sensitive.resize(len, 0);
+        }
+        for field in packet.fields.iter() {
+            if let Some(ty) = VOTE_TYPES.iter().position(|t| *t == field.field_type) {
+                for offset in field.start..=std::cmp::min(field.end, len - 1) {
+                    self.votes[offset][ty] += 1;
+                }
+            }
+        }
+        for (offset, &mask) in packet.cf_mask.iter().enumerate().take(len) {
+            self.cf_sensitive[offset] += mask as usize;
+        }
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push(example);
+        }
+    }
+
+    fn profile(&self) -> Vec<OffsetProfile> {
+        self.votes
+            .iter()
+            .enumerate()
+            .map(|(offset, votes)| {
+                let measured: usize = votes.iter().sum();
+                // max_by_key 在平票时取最后一个，倒序遍历使平票时取靠前的类型
+                let (ty, &count) = votes.iter().enumerate().rev().max_by_key(|&(_, c)| *c).unwrap();
+                let field_type = if measured == 0 { FieldType::Unknown } else { VOTE_TYPES[ty] };
+                let ratio = |n: usize| if measured == 0 { 0.0 } else { n as f32 / measured as f32 };
+                OffsetProfile {
+                    offset,
+                    field_type,
+                    confidence: ratio(count),
+                    measured,
+                    cf_sensitive: ratio(self.cf_sensitive[offset]),
+                }
+            })
+            .collect()
+    }
+}
+
+/// 相邻且多数类型相同的偏移合并为字段
+fn merge_fields(profile: &[OffsetProfile]) -> Vec<CatalogField> {
+    let mut fields: Vec<CatalogField> = vec![];
+    let mut sum = 0.0;
+    for p in profile.iter() {
+        match fields.last_mut() {
+            Some(field) if field.field_type == p.field_type => {
+                field.end = p.offset;
+                sum += p.confidence;
+            }
+            _ => {
+                if let Some(field) = fields.last_mut() {
+                    field.confidence = sum / (field.end - field.start + 1) as f32;
+                }
+                fields.push(CatalogField { start: p.offset, end: p.offset, field_type: p.field_type, confidence: 0.0 });
+                sum = p.confidence;
+            }
+        }
+    }
+    if let Some(field) = fields.last_mut() {
+        field.confidence = sum / (field.end - field.start + 1) as f32;
+    }
+    fields
+}
+
+/// 对所有序列的包聚类并合并各类型的推断结果；没有负载或基准测量的包不参与聚类
+pub fn build(sequences: &[(PathBuf, SequenceCalibrationResults)], prefix_len: usize) -> Catalog {
+    let mut clusters: BTreeMap<(Vec<u8>, usize, u32), Cluster> = BTreeMap::new();
+    let mut packets = 0;
+    for (source, results) in sequences.iter() {
+        let inferred = inference::infer_sequence(results);
+        for packet in inferred.packets.iter().filter(|p| !p.data.is_empty()) {
+            let baseline_cf = match inference::baseline(results, packet.packet_id) {
+                Some(r) => r.cf_index,
+                None => continue,
+            };
+            let example = ExamplePacket {
+                source: source.clone(),
+                sequence_id: results.sequence_id,
+                packet_id: packet.packet_id,
+                data: hex(&packet.data),
+            };
+            clusters
+                .entry(cluster_key(&packet.data, baseline_cf, prefix_len))
+                .or_default()
+                .add(packet, example);
+            packets += 1;
+        }
+    }
+
+    let mut types: Vec<(ClusterKey, TypeProfile)> = clusters
+        .into_iter()
+        .map(|(key, cluster)| {
+            let profile = cluster.profile();
+            let (prefix, baseline_cf) = (hex(&key.0), key.1);
+            let ty = TypeProfile {
+                name: String::new(),
+                prefix,
+                baseline_cf,
+                min_len: cluster.min_len,
+                max_len: cluster.max_len,
+                members: cluster.members,
+                fields: merge_fields(&profile),
+                profile,
+                examples: cluster.examples,
+            };
+            (key, ty)
+        })
+        .collect();
+    // 成员多的类型在前，同样多时按前缀排列
+    types.sort_by(|(_, a), (_, b)| b.members.cmp(&a.members).then_with(|| a.prefix.cmp(&b.prefix)));
+    let mut index = BTreeMap::new();
+    for (i, (key, ty)) in types.iter_mut().enumerate() {
+        ty.name = format!("type{}", i);
+        index.insert(key.clone(), i);
+    }
+    let types = types.into_iter().map(|(_, ty)| ty).collect();
+    Catalog { prefix_len, packets, types, index }
+}
+
+impl TypeProfile {
+    /// 单行描述合并后的字段布局
+    pub fn layout(&self) -> String {
+        self.fields
+            .iter()
+            .map(|f| format!("{}[{}..{}]@{:.2}", f.field_type.name(), f.start, f.end, f.confidence))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl Catalog {
+    /// 负载为 data、基准 cf 类为 baseline_cf 的包所属的消息类型
+    pub fn type_of(&self, data: &[u8], baseline_cf: usize) -> Option<&TypeProfile> {
+        self.index.get(&cluster_key(data, baseline_cf, self.prefix_len)).map(|&i| &self.types[i])
+    }
+
+    /// 在 dir 下写出 message_catalog.json 与每种类型一行的 message_catalog.csv，返回写出的文件
+    pub fn write(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let json_path = dir.join("message_catalog.json");
+        serde_json::to_writer_pretty(File::create(&json_path)?, self)?;
+        let csv_path = dir.join("message_catalog.csv");
+        let mut file = File::create(&csv_path)?;
+        writeln!(file, "type,members,prefix,baseline_cf,min_len,max_len,layout")?;
+        for ty in self.types.iter() {
+            writeln!(
+                file,
+                "{},{},{},{},{},{},{}",
+                ty.name, ty.members, ty.prefix, ty.baseline_cf, ty.min_len, ty.max_len, ty.layout()
+            )?;
+        }
+        Ok(vec![json_path, csv_path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::PacketCalibrationResult;
+    use crate::inference::BASELINE_OPERATOR;
+
+    const OPS: [&str; 4] = ["LBF", "FBF", "ADD", "SUB"];
+
+    fn result(packet_id: usize, offset: usize, op: &str, cf_index: usize) -> PacketCalibrationResult {
+        PacketCalibrationResult {
+            packet_id,
+            offset,
+            stable: true,
+            mutation_operator: op.to_string(),
+            cf_index,
+            vf_index: 0,
+            cfc_index: cf_index,
+            rc_index: 0,
+            width: 1,
+            big_endian: None,
+            edge_delta: None,
+            slot_changes: vec![],
+        }
+    }
+
+    /// 前 4 字节为命令字（改动即改变 cf），其余为不敏感的负载；baseline 为 false 时不带基准测量
+    fn packet_results(packet_id: usize, len: usize, baseline_cf: usize, baseline: bool) -> Vec<PacketCalibrationResult> {
+        let mut results = vec![];
+        if baseline {
+            results.push(result(packet_id, 0, BASELINE_OPERATOR, baseline_cf));
+        }
+        for offset in 0..len {
+            let cf = if offset < 4 { 10 + offset } else { baseline_cf };
+            results.extend(OPS.iter().map(|op| result(packet_id, offset, op, cf)));
+        }
+        results
+    }
+
+    fn sequence(sequence_id: usize, payloads: &[&[u8]], results: Vec<PacketCalibrationResult>) -> SequenceCalibrationResults {
+        let mut raw = vec![];
+        for p in payloads.iter() {
+            raw.extend_from_slice(&(p.len() as u16).to_le_bytes());
+            raw.extend_from_slice(p);
+        }
+        SequenceCalibrationResults {
+            sequence_id,
+            cal_time: 0.0,
+            pkt_number: payloads.len(),
+            raw_data: Some(hex(&raw)),
+            packets_cali_result: results,
+            var_edges: vec![],
+            probed_offsets: vec![],
+        }
+    }
+
+    fn offset(offset: usize, field_type: FieldType, confidence: f32) -> OffsetProfile {
+        OffsetProfile { offset, field_type, confidence, measured: 1, cf_sensitive: 0.0 }
+    }
+
+    #[test]
+    fn test_length_class() {
+        let classes: Vec<u32> = [0, 1, 2, 3, 4, 7, 8, 255, 256].iter().map(|&l| length_class(l)).collect();
+        assert_eq!(classes, vec![0, 1, 2, 2, 3, 3, 4, 8, 9]);
+    }
+
+    #[test]
+    fn test_profile_votes() {
+        let cluster = Cluster {
+            votes: vec![[2, 0, 0, 1], [1, 1, 0, 0], [0, 0, 0, 0], [0, 0, 1, 1], [0, 1, 2, 2]],
+            cf_sensitive: vec![3, 0, 0, 1, 0],
+            ..Default::default()
+        };
+        let profile = cluster.profile();
+        let summary: Vec<(FieldType, f32, usize, f32)> =
+            profile.iter().map(|p| (p.field_type, p.confidence, p.measured, p.cf_sensitive)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                (FieldType::Control, 2.0 / 3.0, 3, 1.0),
+                // 平票时取 VOTE_TYPES 中靠前的类型
+                (FieldType::Control, 0.5, 2, 0.0),
+                // 没有包测量过该偏移
+                (FieldType::Unknown, 0.0, 0, 0.0),
+                (FieldType::Flow, 0.5, 2, 0.5),
+                (FieldType::Flow, 0.4, 5, 0.0),
+            ]
+        );
+        assert!(profile.iter().enumerate().all(|(i, p)| p.offset == i));
+    }
+
+    #[test]
+    fn test_merge_fields() {
+        assert!(merge_fields(&[]).is_empty());
+        let profile = vec![
+            offset(0, FieldType::Control, 1.0),
+            offset(1, FieldType::Control, 0.5),
+            offset(2, FieldType::Delimiter, 0.75),
+            offset(3, FieldType::Data, 1.0),
+            offset(4, FieldType::Data, 0.5),
+            offset(5, FieldType::Data, 0.0),
+            offset(6, FieldType::Control, 0.25),
+        ];
+        let fields: Vec<(usize, usize, FieldType, f32)> =
+            merge_fields(&profile).iter().map(|f| (f.start, f.end, f.field_type, f.confidence)).collect();
+        assert_eq!(
+            fields,
+            vec![
+                (0, 1, FieldType::Control, 0.75),
+                (2, 2, FieldType::Delimiter, 0.75),
+                (3, 5, FieldType::Data, 0.5),
+                (6, 6, FieldType::Control, 0.25),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_build() {
+        let mut first = packet_results(0, 7, 1, true);
+        first.extend(packet_results(1, 4, 1, true));
+        first.extend(packet_results(2, 7, 1, false));
+        let mut second = packet_results(0, 7, 1, true);
+        second.extend(packet_results(1, 12, 1, true));
+        second.extend(packet_results(2, 7, 2, true));
+        let sequences = vec![
+            (PathBuf::from("a.json"), sequence(0, &[b"GET:abc", b"QUIT", b"GET:nob"], first)),
+            (PathBuf::from("b.json"), sequence(1, &[b"GET:xyz", b"GET:abcdefgh", b"GET:abc"], second)),
+        ];
+        let catalog = build(&sequences, DEFAULT_PREFIX_LEN);
+        // 没有基准测量的包不参与聚类
+        assert_eq!(catalog.packets, 5);
+        // 前缀、基准 cf 类、长度档位任一不同都分成不同类型；成员多的在前，同样多时按前缀排列
+        let summary: Vec<(&str, &str, usize, usize, usize, usize)> = catalog
+            .types
+            .iter()
+            .map(|t| (t.name.as_str(), t.prefix.as_str(), t.baseline_cf, t.members, t.min_len, t.max_len))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("type0", "4745543a", 1, 2, 7, 7),
+                ("type1", "4745543a", 1, 1, 12, 12),
+                ("type2", "4745543a", 2, 1, 7, 7),
+                ("type3", "51554954", 1, 1, 4, 4),
+            ]
+        );
+        let get = &catalog.types[0];
+        let sources: Vec<(&Path, usize, usize, &str)> =
+            get.examples.iter().map(|e| (e.source.as_path(), e.sequence_id, e.packet_id, e.data.as_str())).collect();
+        assert_eq!(sources, vec![(Path::new("a.json"), 0, 0, "4745543a616263"), (Path::new("b.json"), 1, 0, "4745543a78797a")]);
+        // 两个成员的布局相同，合并后与单个包的推断一致，且每个偏移都是全票
+        let single = &inference::infer_sequence(&sequences[0].1).packets[0];
+        let layout: Vec<(usize, usize, FieldType)> = get.fields.iter().map(|f| (f.start, f.end, f.field_type)).collect();
+        let expected: Vec<(usize, usize, FieldType)> = single.fields.iter().map(|f| (f.start, f.end, f.field_type)).collect();
+        assert_eq!(layout, expected);
+        assert!(get.profile.iter().all(|p| p.measured == 2 && p.confidence == 1.0));
+
+        assert_eq!(catalog.type_of(b"GET:zzz", 1).map(|t| t.name.as_str()), Some("type0"));
+        assert_eq!(catalog.type_of(b"GET:zzz", 2).map(|t| t.name.as_str()), Some("type2"));
+        assert_eq!(catalog.type_of(b"GET:zzzzz", 1).map(|t| t.name.as_str()), Some("type1"));
+        assert!(catalog.type_of(b"POST", 1).is_none());
+    }
+}
@@ -0,0 +1,116 @@
+//! 校准断点：测量过程中定期（以及基准测完、整个包测完时）把该包已有的结果写入
+//! workdir/checkpoints（等价类注册表另行落盘），`--resume` 时据此跳过已完成的工作。
+
+use crate::analyzer::PacketCalibrationResult;
+use crate::result_stream;
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::path::Path;
+
+#[derive(Serialize, Deserialize)]
+pub struct PacketCheckpoint {
+    pub sequence_id: usize,
+    pub packet_id: usize,
+    pub next_offset: usize, // 下一个待测偏移
+    pub done: bool,
+    pub cal_time: f32,
+    pub results: Vec<PacketCalibrationResult>,
+    #[serde(default)]
+    pub var_edges: Vec<usize>, // 该包基准执行时波动的 bitmap 下标
+    #[serde(default)]
+    pub baseline_edges: Vec<(usize, u8)>, // 记录边级差异时基准执行的稀疏 bitmap
+    #[serde(default)]
+    pub baseline_slots: Option<Vec<(usize, u64)>>, // 基准执行的非零 ijon 槽位，旧版断点中没有时不记录槽位变化
+    #[serde(default)]
+    pub probed: Vec<usize>, // 已探测的偏移（按探测顺序）
+    #[serde(default)]
+    pub truncated: bool, // 序列预算耗尽，未测完采样出的全部偏移
+    #[serde(default)]
+    pub mask: Option<Vec<usize>>, // 基准测量前取的波动下标快照，该包全部类编号都在此掩码下计算
+}
+
+impl PacketCheckpoint {
+    pub fn new(sequence_id: usize, packet_id: usize) -> Self {
+        Self {
+            sequence_id,
+            packet_id,
+            next_offset: 0,
+            done: false,
+            cal_time: 0.0,
+            results: vec![],
+            var_edges: vec![],
+            baseline_edges: vec![],
+            baseline_slots: None,
+            probed: vec![],
+            truncated: false,
+            mask: None,
+        }
+    }
+}
+
+fn checkpoint_dir(workdir: &str) -> String {
+    format!("{}/checkpoints", workdir)
+}
+
+fn packet_path(workdir: &str, sequence_id: usize, packet_id: usize) -> String {
+    format!("{}/sequence_{}_packet_{}.msgp", checkpoint_dir(workdir), sequence_id, packet_id)
+}
+
+/// 先写临时文件再 rename，避免进程在写入中途退出留下半个文件
+fn write_atomic<T: Serialize>(path: &str, value: &T) -> std::io::Result<()> {
+    let tmp = format!("{}.tmp", path);
+    {
+        let mut file = File::create(&tmp)?;
+        rmp_serde::encode::write_named(&mut file, value)
+            .map_err(std::io::Error::other)?;
+    }
+    fs::rename(&tmp, path)
+}
+
+fn read<T: for<'de> Deserialize<'de>>(path: &str) -> Option<T> {
+    let file = File::open(path).ok()?;
+    match rmp_serde::decode::from_read(file) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            eprintln!("[Analyzer] Ignoring broken checkpoint {}: {}", path, e);
+            None
+        }
+    }
+}
+
+pub fn save_packet(workdir: &str, checkpoint: &PacketCheckpoint) {
+    fs::create_dir_all(checkpoint_dir(workdir)).unwrap();
+    let path = packet_path(workdir, checkpoint.sequence_id, checkpoint.packet_id);
+    if let Err(e) = write_atomic(&path, checkpoint) {
+        eprintln!("[Analyzer] Failed to write checkpoint {}: {}", path, e);
+    }
+}
+
+pub fn load_packet(workdir: &str, sequence_id: usize, packet_id: usize) -> Option<PacketCheckpoint> {
+    read(&packet_path(workdir, sequence_id, packet_id))
+}
+
+/// 序列结果落盘后，其各包的断点不再需要
+pub fn remove_sequence(workdir: &str, sequence_id: usize, num_ops: usize) {
+    for packet_id in 0..num_ops {
+        let _ = fs::remove_file(packet_path(workdir, sequence_id, packet_id));
+    }
+}
+
+/// 当前屏蔽的全部波动下标，写在 workdir 根目录便于查看
+pub fn save_var_edges(workdir: &str, edges: &[usize]) {
+    let path = Path::new(workdir).join("var_edges.json");
+    let res = File::create(&path).and_then(|file| serde_json::to_writer(file, edges).map_err(std::io::Error::other));
+    if let Err(e) = res {
+        eprintln!("[Analyzer] Failed to write {:?}: {}", path, e);
+    }
+}
+
+/// 该序列的结果流已写完 Footer（或存在旧版的 JSON 结果），说明上次运行已完整测完
+pub fn sequence_done(workdir: &str, sequence_id: usize) -> bool {
+    result_stream::is_complete(&result_stream::stream_path(workdir, sequence_id))
+        || Path::new(workdir)
+            .join(format!("calibration_results_sequence_{}.json", sequence_id))
+            .exists()
+}
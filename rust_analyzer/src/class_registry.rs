@@ -0,0 +1,331 @@
+//! 进程内所有分析线程共享的等价类注册表：bitmap -> 稳定的类编号 + 代表样本。
+//! 注册表持久化在 workdir（或 `--classes` 指定的文件）中，跨线程、跨运行、跨目标的
+//! cf/cfc/vf/rc 编号可直接比较与合并。
+//!
+//! 波动下标（var_edges）在运行中只增不减。类不以某个时刻的掩码后哈希为键，而是在查找时
+//! 把样本与新 bitmap 按调用方给出的同一个掩码清零后比较：掩码增长后已有的编号仍然有效，
+//! 同一个包的基准与全部探测使用同一个掩码快照，类编号之间始终可比。
+
+use crate::hash;
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// 同一输入多次执行得到的 run_bitmap 中取值不一致的下标（AFL 的 var_bytes）
+pub fn variable_edges(runs: &[Vec<u8>]) -> Vec<usize> {
+    let first = match runs.first() {
+        Some(first) => first,
+        None => return vec![],
+    };
+    (0..first.len())
+        .filter(|&i| runs[1..].iter().any(|run| run[i] != first[i]))
+        .collect()
+}
+
+/// 归一化 hprintf 输出：数字串（含 0x 前缀的十六进制数）替换为 #，连续空白合并为一个空格，
+/// 使只有计数、地址、长度等取值不同的响应归入同一个类
+pub fn normalize_response(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_ascii_digit() {
+            let hex = c == '0' && matches!(chars.peek(), Some('x') | Some('X'));
+            if hex {
+                chars.next();
+            }
+            while chars.peek().map(|&d| if hex { d.is_ascii_hexdigit() } else { d.is_ascii_digit() }).unwrap_or(false) {
+                chars.next();
+            }
+            out.push('#');
+        } else if c.is_whitespace() {
+            while chars.peek().map(|d| d.is_whitespace()).unwrap_or(false) {
+                chars.next();
+            }
+            out.push(' ');
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_string()
+}
+
+/// 把 mask 中的下标清零后的 run_bitmap，mask 为空时直接借用
+pub fn mask_edges<'a>(run_bitmap: &'a [u8], mask: &[usize]) -> Cow<'a, [u8]> {
+    if mask.is_empty() {
+        return Cow::Borrowed(run_bitmap);
+    }
+    let mut masked = run_bitmap.to_vec();
+    for &i in mask.iter().filter(|&&i| i < run_bitmap.len()) {
+        masked[i] = 0;
+    }
+    Cow::Owned(masked)
+}
+
+/// 忽略 mask 中下标后的 run_bitmap 哈希，用于判断多次执行是否稳定
+pub fn masked_hash(run_bitmap: &[u8], mask: &[usize]) -> u64 {
+    let masked = mask_edges(run_bitmap, mask);
+    hash::hash64(&masked, masked.len())
+}
+
+/// run_bitmap 中 mask 下标清零、其余非 0 的值转换为 1 得到的 cov_bitmap
+fn cov_bitmap(run_bitmap: &[u8], mask: &[usize]) -> Vec<u8> {
+    mask_edges(run_bitmap, mask).iter().map(|&x| if x > 0 { 1 } else { 0 }).collect()
+}
+
+/// cov_bitmap 的哈希，只比较而不登记新类
+pub fn cov_hash(run_bitmap: &[u8], mask: &[usize]) -> u64 {
+    let cov_bitmap = cov_bitmap(run_bitmap, mask);
+    hash::hash64(&cov_bitmap, cov_bitmap.len())
+}
+
+/// bitmap 中的非零项 (下标, 取值)
+fn sparse(bitmap: &[u8]) -> Vec<(usize, u8)> {
+    bitmap.iter().enumerate().filter(|(_, &v)| v != 0).map(|(i, &v)| (i, v)).collect()
+}
+
+/// 稀疏样本在 mask（升序）下的哈希，mask 中的下标视为 0
+fn sample_hash(sample: &[(usize, u8)], mask: &[usize]) -> u64 {
+    let mut buf = Vec::with_capacity(sample.len() * 9);
+    for &(i, v) in sample.iter().filter(|(i, _)| mask.binary_search(i).is_err()) {
+        buf.extend_from_slice(&(i as u64).to_le_bytes());
+        buf.push(v);
+    }
+    hash::hash64(&buf, buf.len())
+}
+
+/// 一个等价类第一次出现时的 bitmap（已按登记时的掩码清零），稀疏存储为 (下标, 取值)
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ClassSample {
+    pub hash: u64, // 登记时样本的哈希
+    pub sample: Vec<(usize, u8)>,
+}
+
+/// 每张类表缓存查找索引的掩码个数，同时在测的包通常只用到少数几个掩码快照
+const MAX_MASK_INDEXES: usize = 8;
+
+/// 一种 bitmap 的类表，编号即 samples 中的下标
+#[derive(Serialize, Deserialize, Default)]
+struct ClassTable {
+    samples: Vec<ClassSample>,
+    #[serde(skip)]
+    indexes: Vec<(Vec<usize>, HashMap<u64, usize>)>, // 掩码 -> (掩码后样本哈希 -> 最小编号)，最近使用的在后
+}
+
+/// 索引可由样本重建，复制（落盘快照）时不带索引
+impl Clone for ClassTable {
+    fn clone(&self) -> Self {
+        Self { samples: self.samples.clone(), indexes: vec![] }
+    }
+}
+
+impl ClassTable {
+    /// mask 下的查找索引，没有缓存时由全部样本重建
+    fn index(&mut self, mask: &[usize]) -> &mut HashMap<u64, usize> {
+        match self.indexes.iter().position(|(m, _)| m[..] == *mask) {
+            Some(pos) => {
+                let entry = self.indexes.remove(pos);
+                self.indexes.push(entry);
+            }
+            None => {
+                let mut index = HashMap::new();
+                for (i, s) in self.samples.iter().enumerate() {
+                    index.entry(sample_hash(&s.sample, mask)).or_insert(i);
+                }
+                if self.indexes.len() >= MAX_MASK_INDEXES {
+                    self.indexes.remove(0);
+                }
+                self.indexes.push((mask.to_vec(), index));
+            }
+        }
+        &mut self.indexes.last_mut().unwrap().1
+    }
+
+    /// 按 mask 查找 bitmap 所属的类，没有时登记新类；返回 (编号, 是否新类)
+    fn handle(&mut self, bitmap: &[u8], mask: &[usize]) -> (usize, bool) {
+        let sample: Vec<(usize, u8)> = sparse(bitmap).into_iter().filter(|(i, _)| mask.binary_search(i).is_err()).collect();
+        let hash = sample_hash(&sample, &[]);
+        if let Some(&index) = self.index(mask).get(&hash) {
+            return (index, false);
+        }
+        let index = self.samples.len();
+        for (m, index_map) in self.indexes.iter_mut() {
+            index_map.entry(sample_hash(&sample, m)).or_insert(index);
+        }
+        self.samples.push(ClassSample { hash, sample });
+        (index, true)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct ClassRegistryData {
+    run_classes: ClassTable,  // cfc：带命中次数的 run_bitmap
+    cov_classes: ClassTable,  // cf：只看是否命中的 cov_bitmap
+    ijon_classes: ClassTable, // vf：ijon_map
+    #[serde(default)]
+    response_classes: ClassTable, // rc：归一化后的 hprintf 输出，样本为其字节
+    var_edges: BTreeSet<usize>, // 基准重复执行时取值会波动的 bitmap 下标，每个包测量前取一次快照作为掩码
+    #[serde(skip)]
+    dirty: bool, // 上次落盘后有新增内容
+}
+
+#[derive(Clone)]
+pub struct ClassRegistry {
+    path: String,
+    data: Arc<RwLock<ClassRegistryData>>,
+    save_lock: Arc<Mutex<()>>, // 保证各线程按快照的先后顺序写文件
+}
+
+impl ClassRegistry {
+    /// 从 path 加载已有的注册表，文件不存在或损坏时从空表开始
+    pub fn load_or_new(path: &str) -> Self {
+        let data = match File::open(path) {
+            Ok(file) => match rmp_serde::decode::from_read(file) {
+                Ok(data) => {
+                    println!("[Analyzer] Loaded equivalence classes from {}", path);
+                    data
+                }
+                Err(e) => {
+                    eprintln!("[Analyzer] Ignoring broken class registry {}: {}", path, e);
+                    ClassRegistryData::default()
+                }
+            },
+            Err(_) => ClassRegistryData::default(),
+        };
+        Self {
+            path: path.to_string(),
+            data: Arc::new(RwLock::new(data)),
+            save_lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// 有新增内容时写回文件（先写临时文件再 rename）。
+    /// 只在复制快照时持有锁，序列化与文件读写期间其他线程可以继续登记新类
+    pub fn save(&self) {
+        let _saving = self.save_lock.lock().unwrap();
+        let snapshot = {
+            let mut data = self.data.write().unwrap();
+            if !data.dirty {
+                return;
+            }
+            data.dirty = false;
+            data.clone()
+        };
+        let tmp = format!("{}.tmp", self.path);
+        let res = File::create(&tmp)
+            .and_then(|mut file| rmp_serde::encode::write_named(&mut file, &snapshot).map_err(std::io::Error::other))
+            .and_then(|_| fs::rename(&tmp, &self.path));
+        if let Err(e) = res {
+            self.data.write().unwrap().dirty = true;
+            eprintln!("[Analyzer] Failed to write class registry {}: {}", self.path, e);
+        }
+    }
+
+    /// 记录新发现的波动下标，返回其中此前未记录的个数
+    pub fn add_var_edges(&self, edges: &[usize]) -> usize {
+        let mut data = self.data.write().unwrap();
+        let added = edges.iter().filter(|&&e| data.var_edges.insert(e)).count();
+        data.dirty |= added > 0;
+        added
+    }
+
+    pub fn var_edges(&self) -> Vec<usize> {
+        self.data.read().unwrap().var_edges.iter().copied().collect()
+    }
+
+    /// 已登记的 (cf, cfc, vf, rc) 类数
+    pub fn counts(&self) -> (usize, usize, usize, usize) {
+        let data = self.data.read().unwrap();
+        (
+            data.cov_classes.samples.len(),
+            data.run_classes.samples.len(),
+            data.ijon_classes.samples.len(),
+            data.response_classes.samples.len(),
+        )
+    }
+
+    /// run_bitmap（带命中次数）在 mask 下的类编号
+    pub fn handle_run_bitmap(&self, run_bitmap: &[u8], mask: &[usize]) -> usize {
+        let mut data = self.data.write().unwrap();
+        let (index, new) = data.run_classes.handle(run_bitmap, mask);
+        data.dirty |= new;
+        index
+    }
+
+    /// cov_bitmap 在 mask 下的类编号
+    pub fn handle_cov_bitmap(&self, run_bitmap: &[u8], mask: &[usize]) -> usize {
+        let cov_bitmap = cov_bitmap(run_bitmap, mask);
+        let mut data = self.data.write().unwrap();
+        let (index, new) = data.cov_classes.handle(&cov_bitmap, mask);
+        data.dirty |= new;
+        index
+    }
+
+    /// ijon_map 的类编号
+    pub fn handle_ijon_map(&self, ijon_map: &[u8]) -> usize {
+        let mut data = self.data.write().unwrap();
+        let (index, new) = data.ijon_classes.handle(ijon_map, &[]);
+        data.dirty |= new;
+        index
+    }
+
+    /// hprintf 输出归一化后的类编号，没有输出的执行同样归入一个类
+    pub fn handle_response(&self, hprintf: &str) -> usize {
+        let normalized = normalize_response(hprintf);
+        let mut data = self.data.write().unwrap();
+        let (index, new) = data.response_classes.handle(normalized.as_bytes(), &[]);
+        data.dirty |= new;
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(name: &str) -> ClassRegistry {
+        let path = std::env::temp_dir().join(format!("class_registry_{}_{}.msgp", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        ClassRegistry::load_or_new(path.to_str().unwrap())
+    }
+
+    #[test]
+    fn test_normalize_response() {
+        assert_eq!(normalize_response("  bad length 12\n  at 0x7fff1A!\n"), "bad length # at #!");
+        // 同一次执行的多条 hprintf 以换行分隔，不会粘成一个词
+        assert_eq!(normalize_response("bad\nlength\n"), "bad length");
+        assert_ne!(normalize_response("bad\nlength\n"), normalize_response("badlength"));
+        assert_eq!(normalize_response(""), "");
+    }
+
+    #[test]
+    fn test_ids_survive_mask_growth() {
+        let classes = registry("mask");
+        let a = [1, 0, 2, 0];
+        let noisy = [1, 0, 2, 5];
+        let cfc = classes.handle_run_bitmap(&a, &[]);
+        assert_ne!(classes.handle_run_bitmap(&noisy, &[]), cfc);
+        // 下标 3 加入掩码后两者属于同一类，编号仍是最早登记的那个
+        assert_eq!(classes.handle_run_bitmap(&noisy, &[3]), cfc);
+        assert_eq!(classes.handle_run_bitmap(&a, &[3]), cfc);
+        // 旧掩码下的编号不受影响
+        assert_eq!(classes.handle_run_bitmap(&a, &[]), cfc);
+        assert_eq!(classes.handle_cov_bitmap(&noisy, &[3]), classes.handle_cov_bitmap(&[7, 0, 7, 0], &[]));
+        assert_eq!(classes.counts().1, 2);
+    }
+
+    #[test]
+    fn test_ids_stable_across_save_and_load() {
+        let classes = registry("reload");
+        let ids: Vec<usize> = [[0u8, 1, 0], [3, 1, 0], [0, 1, 4]].iter().map(|b| classes.handle_run_bitmap(b, &[])).collect();
+        let response = classes.handle_response("bad length 12");
+        classes.save();
+        let reloaded = ClassRegistry::load_or_new(&classes.path);
+        assert_eq!(reloaded.handle_run_bitmap(&[3, 1, 0], &[]), ids[1]);
+        assert_eq!(reloaded.handle_run_bitmap(&[0, 1, 9], &[2]), ids[0]);
+        assert_eq!(reloaded.handle_response("bad  length 7"), response);
+        let _ = fs::remove_file(&classes.path);
+    }
+}
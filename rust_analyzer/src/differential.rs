@@ -0,0 +1,236 @@
+//! 差分校准：同一个目标的两个构建（两个 sharedir，spec 相同）各启动一个 runner，
+//! 对同一批队列输入执行相同的基准与探测，报告两个构建的行为出现分歧的 (包, 偏移, 算子)：
+//! 只在一个构建中改变了 cf/vf 的字节，或两个构建的退出原因不同。
+//!
+//! 两个构建的类编号互不相通，敏感与否分别相对各自的基准判断。结果写在 `workdir/differential/`：
+//! 每个序列一个 `sequence_<id>.json`，所有分歧汇总在 `divergences.csv`。
+
+use crate::analyzer::{GetStructStorage, SegmentAnalyzer};
+use crate::fuzz_runner::{FuzzRunner, TestInfo};
+use crate::inference::BASELINE_OPERATOR;
+
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// 一次执行的等价类编号与退出原因
+#[derive(Serialize, Clone, Debug)]
+pub struct ProbeOutcome {
+    pub cf_index: usize,
+    pub vf_index: usize,
+    pub cfc_index: usize,
+    pub stable: bool,
+    pub exit_reason: String,
+}
+
+impl From<(TestInfo, usize, usize, usize, bool)> for ProbeOutcome {
+    fn from((info, cf_index, vf_index, cfc_index, stable): (TestInfo, usize, usize, usize, bool)) -> Self {
+        Self { cf_index, vf_index, cfc_index, stable, exit_reason: info.exitreason.name().to_string() }
+    }
+}
+
+impl ProbeOutcome {
+    /// 相对基准改变了控制流或取值反馈
+    fn sensitive(&self, base: &ProbeOutcome) -> bool {
+        self.cf_index != base.cf_index || self.vf_index != base.vf_index
+    }
+}
+
+/// 某个偏移上一个算子的探测，执行失败时 outcome 为 None
+pub struct OperatorProbe {
+    pub offset: usize,
+    pub operator: String,
+    pub outcome: Option<ProbeOutcome>,
+}
+
+/// 一个包在一个构建上的全部执行结果
+pub struct PacketProbes {
+    pub baseline: Option<ProbeOutcome>,
+    pub probes: Vec<OperatorProbe>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DivergenceKind {
+    OnlyA, // 只在构建 A 中改变了行为
+    OnlyB, // 只在构建 B 中改变了行为
+    Exit,  // 退出原因不同
+}
+
+impl DivergenceKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DivergenceKind::OnlyA => "ONLY_A",
+            DivergenceKind::OnlyB => "ONLY_B",
+            DivergenceKind::Exit => "EXIT",
+        }
+    }
+}
+
+/// 两个构建在 (包, 偏移, 算子) 上的分歧；基准的分歧记为算子 None、偏移 0
+#[derive(Serialize, Clone, Debug)]
+pub struct Divergence {
+    pub packet_id: usize,
+    pub offset: usize,
+    pub operator: String,
+    pub kind: DivergenceKind,
+    pub a: Option<ProbeOutcome>,
+    pub b: Option<ProbeOutcome>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SequenceDiff {
+    pub sequence_id: usize,
+    pub pkt_number: usize,
+    pub probes: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+fn exit_name(outcome: &Option<ProbeOutcome>) -> &str {
+    outcome.as_ref().map(|o| o.exit_reason.as_str()).unwrap_or("-")
+}
+
+/// 退出原因不同时的分歧
+fn exit_divergence(packet_id: usize, offset: usize, operator: &str, a: &Option<ProbeOutcome>, b: &Option<ProbeOutcome>) -> Option<Divergence> {
+    if exit_name(a) == exit_name(b) {
+        return None;
+    }
+    Some(Divergence { packet_id, offset, operator: operator.to_string(), kind: DivergenceKind::Exit, a: a.clone(), b: b.clone() })
+}
+
+/// 比较同一个包在两个构建上的执行结果。两侧探测按相同的偏移与算子顺序执行，逐个对应；
+/// 缺少基准、执行失败或任一侧探测/基准不稳定的探测只比较退出原因
+pub fn compare(packet_id: usize, a: &PacketProbes, b: &PacketProbes) -> Vec<Divergence> {
+    let mut divergences: Vec<Divergence> =
+        exit_divergence(packet_id, 0, BASELINE_OPERATOR, &a.baseline, &b.baseline).into_iter().collect();
+    for (pa, pb) in a.probes.iter().zip(b.probes.iter()) {
+        debug_assert!(pa.offset == pb.offset && pa.operator == pb.operator);
+        if let Some(d) = exit_divergence(packet_id, pa.offset, &pa.operator, &pa.outcome, &pb.outcome) {
+            divergences.push(d);
+            continue;
+        }
+        let sensitive = |base: &Option<ProbeOutcome>, outcome: &Option<ProbeOutcome>| match (base, outcome) {
+            (Some(base), Some(outcome)) if base.stable && outcome.stable => Some(outcome.sensitive(base)),
+            _ => None,
+        };
+        let kind = match (sensitive(&a.baseline, &pa.outcome), sensitive(&b.baseline, &pb.outcome)) {
+            (Some(true), Some(false)) => DivergenceKind::OnlyA,
+            (Some(false), Some(true)) => DivergenceKind::OnlyB,
+            _ => continue,
+        };
+        divergences.push(Divergence {
+            packet_id,
+            offset: pa.offset,
+            operator: pa.operator.clone(),
+            kind,
+            a: pa.outcome.clone(),
+            b: pb.outcome.clone(),
+        });
+    }
+    divergences
+}
+
+/// 持有两个构建的分析器，A 负责导入种子并决定探测的偏移
+pub struct DifferentialAnalyzer<Fuzz: FuzzRunner + GetStructStorage> {
+    a: SegmentAnalyzer<Fuzz>,
+    b: SegmentAnalyzer<Fuzz>,
+    out_dir: PathBuf,
+}
+
+impl<Fuzz: FuzzRunner + GetStructStorage> DifferentialAnalyzer<Fuzz> {
+    pub fn new(a: SegmentAnalyzer<Fuzz>, b: SegmentAnalyzer<Fuzz>, workdir: &str) -> Self {
+        Self { a, b, out_dir: Path::new(workdir).join("differential") }
+    }
+
+    /// 逐个序列、逐个包在两个构建上执行相同的探测并写出报告，返回分歧总数
+    pub fn run(&mut self) -> io::Result<usize> {
+        fs::create_dir_all(&self.out_dir)?;
+        let mut csv = File::create(self.out_dir.join("divergences.csv"))?;
+        writeln!(csv, "seq,pkt,offset,operator,kind,exit_a,exit_b")?;
+
+        let mut total = 0;
+        for (sequence_id, entry) in self.a.import_queue().into_iter().enumerate() {
+            let num_ops = self.a.num_packets(&entry);
+            let mut diff = SequenceDiff { sequence_id, pkt_number: num_ops, probes: 0, divergences: vec![] };
+            for packet_id in 0..num_ops {
+                let offsets = self.a.sample_offsets(&entry, sequence_id, packet_id);
+                println!(
+                    "[Differential] test case {} packet {}/{}: {} offsets",
+                    sequence_id,
+                    packet_id + 1,
+                    num_ops,
+                    offsets.len()
+                );
+                let (pa, pb) = match (
+                    self.a.probe_packet(&entry, packet_id, &offsets),
+                    self.b.probe_packet(&entry, packet_id, &offsets),
+                ) {
+                    (Some(pa), Some(pb)) => (pa, pb),
+                    _ => {
+                        eprintln!("[Differential] couldn't snapshot test case {} packet {}, skipping", sequence_id, packet_id);
+                        continue;
+                    }
+                };
+                diff.probes += pa.probes.len();
+                let divergences = compare(packet_id, &pa, &pb);
+                for d in divergences.iter() {
+                    writeln!(
+                        csv,
+                        "{},{},0x{:04x},{},{},{},{}",
+                        sequence_id,
+                        packet_id,
+                        d.offset,
+                        d.operator,
+                        d.kind.name(),
+                        exit_name(&d.a),
+                        exit_name(&d.b)
+                    )?;
+                }
+                diff.divergences.extend(divergences);
+            }
+            println!("[Differential] test case {}: {} divergences in {} probes", sequence_id, diff.divergences.len(), diff.probes);
+            total += diff.divergences.len();
+            let file = File::create(self.out_dir.join(format!("sequence_{}.json", sequence_id)))?;
+            serde_json::to_writer_pretty(file, &diff)?;
+        }
+        Ok(total)
+    }
+
+    pub fn shutdown(&mut self) {
+        self.a.shutdown();
+        self.b.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(cf_index: usize, stable: bool) -> Option<ProbeOutcome> {
+        Some(ProbeOutcome { cf_index, vf_index: 0, cfc_index: cf_index, stable, exit_reason: "Normal".to_string() })
+    }
+
+    fn probes(baseline: Option<ProbeOutcome>, outcomes: Vec<Option<ProbeOutcome>>) -> PacketProbes {
+        let probes = outcomes
+            .into_iter()
+            .enumerate()
+            .map(|(offset, outcome)| OperatorProbe { offset, operator: "LBF".to_string(), outcome })
+            .collect();
+        PacketProbes { baseline, probes }
+    }
+
+    #[test]
+    fn test_compare_skips_unstable() {
+        let a = probes(outcome(0, true), vec![outcome(1, true), outcome(1, false), outcome(0, true)]);
+        let b = probes(outcome(0, true), vec![outcome(0, true), outcome(0, true), outcome(2, false)]);
+        let divergences = compare(3, &a, &b);
+        assert_eq!(divergences.len(), 1);
+        assert_eq!((divergences[0].packet_id, divergences[0].offset), (3, 0));
+        assert_eq!(divergences[0].kind, DivergenceKind::OnlyA);
+
+        // 基准不稳定时整个包只比较退出原因
+        let a = probes(outcome(0, false), vec![outcome(1, true)]);
+        let b = probes(outcome(0, true), vec![outcome(0, true)]);
+        assert!(compare(0, &a, &b).is_empty());
+    }
+}
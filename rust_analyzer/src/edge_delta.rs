@@ -0,0 +1,84 @@
+//! 边级差异：记录某次探测相对包基准（None）执行新增、消失以及命中次数变化的 bitmap 下标，
+//! 用于建立字节到代码分支的依赖关系。
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug, PartialEq, Eq)]
+pub struct EdgeDelta {
+    pub added: Vec<usize>,       // 基准未命中、探测命中
+    pub removed: Vec<usize>,     // 基准命中、探测未命中
+    pub hit_changed: Vec<usize>, // 均命中但命中次数（bucket）不同
+}
+
+impl EdgeDelta {
+    /// baseline 为 `sparse` 得到的基准稀疏表示，bitmap 为探测执行后的 run_bitmap
+    pub fn between(baseline: &[(usize, u8)], bitmap: &[u8]) -> Self {
+        let mut delta = Self::default();
+        let mut base = baseline.iter().peekable();
+        for (i, &hits) in bitmap.iter().enumerate() {
+            // 基准中下标小于 i 的边在本次执行中未命中
+            while let Some(&&(b, _)) = base.peek() {
+                if b >= i {
+                    break;
+                }
+                delta.removed.push(b);
+                base.next();
+            }
+            let base_hits = match base.peek() {
+                Some(&&(b, h)) if b == i => {
+                    base.next();
+                    h
+                }
+                _ => 0,
+            };
+            if base_hits == hits {
+                continue;
+            }
+            if base_hits == 0 {
+                delta.added.push(i);
+            } else if hits == 0 {
+                delta.removed.push(i);
+            } else {
+                delta.hit_changed.push(i);
+            }
+        }
+        delta.removed.extend(base.map(|&(b, _)| b));
+        delta
+    }
+
+    /// 所有发生变化的下标
+    pub fn edges(&self) -> impl Iterator<Item = usize> + '_ {
+        self.added.iter().chain(self.removed.iter()).chain(self.hit_changed.iter()).copied()
+    }
+}
+
+/// run_bitmap 中非零项的 (下标, 命中次数)，按下标升序
+pub fn sparse(bitmap: &[u8]) -> Vec<(usize, u8)> {
+    bitmap
+        .iter()
+        .enumerate()
+        .filter(|(_, &hits)| hits != 0)
+        .map(|(i, &hits)| (i, hits))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_between() {
+        let baseline = sparse(&[0, 1, 0, 2, 4, 0, 1]);
+        assert_eq!(baseline, vec![(1, 1), (3, 2), (4, 4), (6, 1)]);
+        let delta = EdgeDelta::between(&baseline, &[1, 1, 0, 8, 0, 0, 1]);
+        assert_eq!(delta, EdgeDelta { added: vec![0], removed: vec![4], hit_changed: vec![3] });
+        assert_eq!(delta.edges().collect::<Vec<_>>(), vec![0, 4, 3]);
+
+        // 与基准相同时没有差异，基准为空时全部为新增
+        assert_eq!(EdgeDelta::between(&baseline, &[0, 1, 0, 2, 4, 0, 1]), EdgeDelta::default());
+        assert_eq!(EdgeDelta::between(&[], &[0, 3, 0, 1]).added, vec![1, 3]);
+        // 超出本次 bitmap 长度的基准边视为消失
+        let delta = EdgeDelta::between(&baseline, &[0, 1, 0, 2]);
+        assert_eq!(delta, EdgeDelta { added: vec![], removed: vec![4, 6], hit_changed: vec![] });
+    }
+}
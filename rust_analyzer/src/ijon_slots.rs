@@ -0,0 +1,86 @@
+//! IJON 状态变量归因：ijon max map 为 256 个小端 u64 槽位，每个槽位对应目标中一处
+//! IJON_MAX 标注的状态变量。记录某次探测相对包基准（None）发生变化的槽位及其新旧取值，
+//! 用于判断哪个包的哪些字节驱动了哪个状态变量。
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
+
+/// ijon max map 的槽位数（2048 字节）
+pub const IJON_SLOTS: usize = 256;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SlotChange {
+    pub slot: usize,
+    pub old: u64, // 基准执行的取值
+    pub new: u64, // 探测执行的取值
+}
+
+/// 按槽位解码 ijon max map，不足 8 字节的尾部忽略
+pub fn decode(map: &[u8]) -> Vec<u64> {
+    map.chunks_exact(8).take(IJON_SLOTS).map(|c| u64::from_le_bytes(c.try_into().unwrap())).collect()
+}
+
+/// 非零槽位的 (槽位, 取值)，按槽位升序
+pub fn sparse(map: &[u8]) -> Vec<(usize, u64)> {
+    decode(map).into_iter().enumerate().filter(|&(_, v)| v != 0).collect()
+}
+
+/// baseline 为 `sparse` 得到的基准取值，map 为探测执行后的 ijon max map
+pub fn changes(baseline: &[(usize, u64)], map: &[u8]) -> Vec<SlotChange> {
+    let mut base = baseline.iter().peekable();
+    let mut changes = vec![];
+    for (slot, new) in decode(map).into_iter().enumerate() {
+        while base.peek().map(|&&(s, _)| s < slot).unwrap_or(false) {
+            base.next();
+        }
+        let old = match base.peek() {
+            Some(&&(s, v)) if s == slot => v,
+            _ => 0,
+        };
+        if old != new {
+            changes.push(SlotChange { slot, old, new });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(slots: &[(usize, u64)]) -> Vec<u8> {
+        let mut map = vec![0u8; IJON_SLOTS * 8];
+        for &(slot, value) in slots.iter() {
+            map[slot * 8..slot * 8 + 8].copy_from_slice(&value.to_le_bytes());
+        }
+        map
+    }
+
+    #[test]
+    fn test_decode() {
+        let values = decode(&map(&[(0, 1), (255, u64::MAX)]));
+        assert_eq!(values.len(), IJON_SLOTS);
+        assert_eq!((values[0], values[1], values[255]), (1, 0, u64::MAX));
+        // 不足 8 字节的尾部忽略，超过 256 个槽位的部分忽略
+        assert_eq!(decode(&[1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0]), vec![1]);
+        assert_eq!(decode(&vec![0u8; IJON_SLOTS * 8 + 16]).len(), IJON_SLOTS);
+        assert_eq!(sparse(&map(&[(3, 7), (200, 0x1_0000_0000)])), vec![(3, 7), (200, 0x1_0000_0000)]);
+    }
+
+    #[test]
+    fn test_changes() {
+        let baseline = sparse(&map(&[(2, 5), (10, 1), (100, 9)]));
+        // 槽位 2 增大、槽位 10 不变、槽位 100 降为 0、槽位 50 新出现
+        let found = changes(&baseline, &map(&[(2, 6), (10, 1), (50, 3)]));
+        assert_eq!(
+            found,
+            vec![
+                SlotChange { slot: 2, old: 5, new: 6 },
+                SlotChange { slot: 50, old: 0, new: 3 },
+                SlotChange { slot: 100, old: 9, new: 0 },
+            ]
+        );
+        assert!(changes(&baseline, &map(&[(2, 5), (10, 1), (100, 9)])).is_empty());
+        assert_eq!(changes(&[], &map(&[(0, 1)])), vec![SlotChange { slot: 0, old: 0, new: 1 }]);
+    }
+}
@@ -0,0 +1,885 @@
+//! 字段推断：直接基于 `PacketCalibrationResult` 做字段划分与类型推断，
+//! 算法移植自 python_inference（pos_sensitivity / generate_continue_mask /
+//! segment_fields / classify_and_color_segments）。
+
+use crate::analyzer::{PacketCalibrationResult, SequenceCalibrationResults};
+use crate::result_stream;
+
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 基准（未变异）测量使用的算子名
+pub const BASELINE_OPERATOR: &str = "None";
+
+/// 序列中第 packet_id 个包的基准测量
+pub fn baseline(results: &SequenceCalibrationResults, packet_id: usize) -> Option<&PacketCalibrationResult> {
+    results
+        .packets_cali_result
+        .iter()
+        .find(|r| r.packet_id == packet_id && r.mutation_operator == BASELINE_OPERATOR)
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Control,
+    Delimiter,
+    Flow,
+    Data,
+    Unknown, // 采样时未探测、也无法由相邻偏移推断的字节
+}
+
+impl FieldType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FieldType::Control => "CONTROL",
+            FieldType::Delimiter => "DELIMITER",
+            FieldType::Flow => "FLOW",
+            FieldType::Data => "DATA",
+            FieldType::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// 推断出的一个字段，start/end 均为包内闭区间偏移
+#[derive(Serialize, Clone, Debug)]
+pub struct InferredField {
+    pub start: usize,
+    pub end: usize,
+    pub field_type: FieldType,
+}
+
+/// 多字节整数探测的结果：offset 处按 width 字节、给定字节序解释的整数改变了程序行为
+#[derive(Serialize, Clone, Debug)]
+pub struct IntFieldHint {
+    pub offset: usize,
+    pub width: usize,
+    pub big_endian: bool,
+    pub sensitive: usize, // 改变了 cf 或 vf 的探测数
+    pub probes: usize,
+    pub length: bool,     // 当前取值与负载长度吻合且长度 ±1 探测改变了控制流
+}
+
+/// 字节到代码的依赖：改变该 bitmap 下标的所有探测偏移
+#[derive(Serialize, Clone, Debug)]
+pub struct EdgeDependency {
+    pub edge: usize,
+    pub offsets: Vec<usize>,
+}
+
+/// 状态变量到字节的依赖：改变了该 ijon 槽位的所有探测偏移，以及探测中观察到的取值
+#[derive(Serialize, Clone, Debug)]
+pub struct SlotDependency {
+    pub slot: usize,
+    pub base: u64,        // 基准执行的取值
+    pub offsets: Vec<usize>,
+    pub values: Vec<u64>, // 至多 MAX_SLOT_VALUES 个不同取值
+}
+
+/// 只改变了响应类的探测：cf/cfc/vf 与基准一致而归一化后的 hprintf 输出不同，
+/// 通常是目标只在回复或报错（bad length、unknown command 等）中体现的字段
+#[derive(Serialize, Clone, Debug)]
+pub struct ResponseProbe {
+    pub offset: usize,
+    pub width: usize,
+    pub operator: String,
+    pub rc_base: usize, // 基准执行的响应类
+    pub rc_index: usize,
+}
+
+/// 每个槽位保留的不同取值个数
+const MAX_SLOT_VALUES: usize = 16;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct InferredPacket {
+    pub packet_id: usize,
+    pub data: Vec<u8>,
+    pub fields: Vec<InferredField>,
+    pub cf_mask: Vec<u8>, // 所有算子均连续敏感的偏移为1（analyze_segment_masks）
+    pub int_fields: Vec<IntFieldHint>,
+    pub edge_map: Vec<EdgeDependency>, // 仅在记录了边级差异时非空
+    pub slot_map: Vec<SlotDependency>, // 探测改变了 ijon 槽位时非空
+    pub response_only: Vec<ResponseProbe>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct InferredSequence {
+    pub sequence_id: usize,
+    pub packets: Vec<InferredPacket>,
+}
+
+/// 单个包在各算子下逐偏移的测量视图
+struct PacketProfile {
+    len: usize,
+    operators: Vec<String>,
+    cf_by_op: Vec<Vec<usize>>,
+    cf_sen: Vec<usize>,
+    cfc_sen: Vec<usize>,
+    vf_sen: Vec<usize>,
+    unstable: Vec<bool>,
+    measured: Vec<bool>, // 探测过或可由两侧探测推断的偏移
+    cf_base: usize,
+}
+
+impl PacketProfile {
+    /// 根据某个包的全部测量结果构建视图；没有基准测量时返回 None。
+    /// 缺失的 (算子, 偏移) 视为与基准一致，多字节算子不参与字段划分。
+    /// probed 为采样时实际探测的偏移（None 为全部探测）：未探测的偏移若两侧最近的探测结果
+    /// 在所有算子下都一致，则沿用该结果，否则标记为未测量。
+    fn new(data: &[u8], results: &[&PacketCalibrationResult], probed: Option<&[usize]>) -> Option<Self> {
+        let base = results.iter().find(|r| r.mutation_operator == BASELINE_OPERATOR)?;
+        let results: Vec<&PacketCalibrationResult> = results.iter().filter(|r| r.width == 1).copied().collect();
+
+        let mut len = data.len();
+        if len == 0 {
+            len = results
+                .iter()
+                .filter(|r| r.mutation_operator != BASELINE_OPERATOR)
+                .map(|r| r.offset + 1)
+                .max()
+                .unwrap_or(0);
+        }
+
+        let mut operators: Vec<String> = vec![];
+        let mut cf_by_op: Vec<Vec<usize>> = vec![];
+        let mut cfc_by_op: Vec<Vec<usize>> = vec![];
+        let mut vf_by_op: Vec<Vec<usize>> = vec![];
+        let mut unstable = vec![!base.stable; len];
+
+        for r in results.iter().filter(|r| r.mutation_operator != BASELINE_OPERATOR) {
+            if r.offset >= len {
+                continue;
+            }
+            let op = match operators.iter().position(|o| *o == r.mutation_operator) {
+                Some(op) => op,
+                None => {
+                    operators.push(r.mutation_operator.clone());
+                    cf_by_op.push(vec![base.cf_index; len]);
+                    cfc_by_op.push(vec![base.cfc_index; len]);
+                    vf_by_op.push(vec![base.vf_index; len]);
+                    operators.len() - 1
+                }
+            };
+            cf_by_op[op][r.offset] = r.cf_index;
+            cfc_by_op[op][r.offset] = r.cfc_index;
+            vf_by_op[op][r.offset] = r.vf_index;
+            unstable[r.offset] |= !r.stable;
+        }
+
+        let mut measured = vec![probed.is_none(); len];
+        for &offset in probed.unwrap_or(&[]).iter().filter(|&&o| o < len) {
+            measured[offset] = true;
+        }
+        let same = |a: usize, b: usize| {
+            [&cf_by_op, &cfc_by_op, &vf_by_op].iter().all(|by_op| by_op.iter().all(|seq| seq[a] == seq[b]))
+                && unstable[a] == unstable[b]
+        };
+        let probed_offsets: Vec<usize> = (0..len).filter(|&i| measured[i]).collect();
+        let gaps: Vec<(usize, usize)> = probed_offsets
+            .windows(2)
+            .filter(|w| w[1] > w[0] + 1 && same(w[0], w[1]))
+            .map(|w| (w[0], w[1]))
+            .collect();
+        for (left, right) in gaps {
+            for by_op in [&mut cf_by_op, &mut cfc_by_op, &mut vf_by_op] {
+                for seq in by_op.iter_mut() {
+                    let value = seq[left];
+                    seq[left + 1..right].iter_mut().for_each(|v| *v = value);
+                }
+            }
+            let value = unstable[left];
+            unstable[left + 1..right].iter_mut().for_each(|v| *v = value);
+            measured[left + 1..right].iter_mut().for_each(|v| *v = true);
+        }
+
+        let cf_sen = pos_sensitivity(base.cf_index, &cf_by_op, len);
+        let cfc_sen = pos_sensitivity(base.cfc_index, &cfc_by_op, len);
+        let vf_sen = pos_sensitivity(base.vf_index, &vf_by_op, len);
+
+        Some(Self {
+            len,
+            operators,
+            cf_by_op,
+            cf_sen,
+            cfc_sen,
+            vf_sen,
+            unstable,
+            measured,
+            cf_base: base.cf_index,
+        })
+    }
+
+    /// 所有算子都改变了行为时的敏感度取值（python 版本中的 4）
+    fn full(&self) -> usize {
+        self.operators.len()
+    }
+}
+
+/// 每个偏移上与基准不同的算子数量
+fn pos_sensitivity(base: usize, by_op: &[Vec<usize>], len: usize) -> Vec<usize> {
+    (0..len)
+        .map(|i| by_op.iter().filter(|seq| seq[i] != base).count())
+        .collect()
+}
+
+/// 反映字段在某个算子下的连续敏感度：差值为0的位置为0，
+/// 非0位置在差值变化时于1和2之间交替
+pub fn generate_continue_mask(diff: &[i64]) -> Vec<u8> {
+    let mut mask = Vec::with_capacity(diff.len());
+    let mut toggle = 1;
+    let mut current = diff.first().copied().unwrap_or(0);
+    for (i, &value) in diff.iter().enumerate() {
+        if value == 0 {
+            mask.push(0);
+        } else {
+            if i == 0 || value != current {
+                toggle = if toggle == 1 { 2 } else { 1 };
+            }
+            mask.push(toggle);
+        }
+        current = value;
+    }
+    mask
+}
+
+/// 候选分界点 k ∈ [start+2, end-2]：左右两侧各自连续一致，且 k 处与两侧均不同
+fn get_boundaries(seq: &[usize], start: usize, end: usize) -> Vec<usize> {
+    let mut boundaries = vec![];
+    if end < start + 4 {
+        return boundaries;
+    }
+    for k in start + 2..end - 1 {
+        if seq[k - 2] == seq[k - 1] && seq[k + 1] == seq[k + 2] && seq[k] != seq[k - 1] && seq[k] != seq[k + 1] {
+            boundaries.push(k);
+        }
+    }
+    boundaries
+}
+
+/// 按照 flag 的取值切换把 [start, end] 切分成若干段
+fn split_on_change<F: Fn(usize) -> bool>(start: usize, end: usize, flag: F, out: &mut Vec<(usize, usize)>) {
+    let mut seg_start = start;
+    let mut current = flag(start);
+    for i in start + 1..=end {
+        if flag(i) != current {
+            out.push((seg_start, i - 1));
+            seg_start = i;
+            current = flag(i);
+        }
+    }
+    out.push((seg_start, end));
+}
+
+fn segment_fields(p: &PacketProfile) -> Vec<(usize, usize)> {
+    let full = p.full();
+
+    // Step 1: 基于 cf_sen 是否满敏感做粗粒度划分
+    let mut coarse = vec![];
+    split_on_change(0, p.len - 1, |i| p.cf_sen[i] == full, &mut coarse);
+
+    // Step 2: 长度不小于5的段内，取多数算子（4个中的3个）认可的分界点单独成段
+    let mut detailed = vec![];
+    for &(start, end) in coarse.iter() {
+        if end - start + 1 < 5 {
+            detailed.push((start, end));
+            continue;
+        }
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        for seq in p.cf_by_op.iter() {
+            for b in get_boundaries(seq, start, end) {
+                *counts.entry(b).or_insert(0) += 1;
+            }
+        }
+        let mut selected: Vec<usize> = counts
+            .into_iter()
+            .filter(|&(_, count)| count * 4 >= full * 3)
+            .map(|(b, _)| b)
+            .collect();
+        selected.sort_unstable();
+
+        let mut current_start = start;
+        for b in selected {
+            if current_start < b {
+                detailed.push((current_start, b - 1));
+                detailed.push((b, b));
+                current_start = b + 1;
+            }
+        }
+        detailed.push((current_start, end));
+    }
+
+    // Step 3: 合并与不稳定偏移相邻的字段
+    let mut stable_segments = vec![];
+    let (mut cur_start, mut cur_end) = detailed[0];
+    for &(next_start, next_end) in detailed[1..].iter() {
+        if p.unstable[next_start] || p.unstable[cur_end] {
+            cur_end = next_end;
+        } else {
+            stable_segments.push((cur_start, cur_end));
+            cur_start = next_start;
+            cur_end = next_end;
+        }
+    }
+    stable_segments.push((cur_start, cur_end));
+
+    // Step 4: 按 vf_sen 是否为0再次划分
+    let mut fields = vec![];
+    for &(start, end) in stable_segments.iter() {
+        split_on_change(start, end, |i| p.vf_sen[i] == 0, &mut fields);
+    }
+    fields
+}
+
+fn classify(p: &PacketProfile, data: &[u8], start: usize, end: usize) -> FieldType {
+    let full = p.full();
+    let seg_len = end - start + 1;
+    let sum_cf: usize = p.cf_sen[start..=end].iter().sum();
+    let sum_cfc: usize = p.cfc_sen[start..=end].iter().sum();
+    let all_cf = full > 0 && sum_cf == full * seg_len;
+    let all_cfc = full > 0 && sum_cfc == full * seg_len;
+    let no_alnum = data
+        .get(start..=end)
+        .map(|s| !s.iter().any(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or(true);
+
+    if seg_len >= 2 && all_cf {
+        FieldType::Control
+    } else if seg_len <= 2 && all_cf && no_alnum {
+        FieldType::Delimiter
+    } else if 2 * sum_cf > seg_len && sum_cf < full * seg_len && all_cfc {
+        FieldType::Flow
+    } else {
+        FieldType::Data
+    }
+}
+
+/// offset 处的当前取值是否可能是长度：等于负载长度或其后剩余的字节数
+fn matches_length(data: &[u8], offset: usize, width: usize, big_endian: bool) -> bool {
+    let bytes = match data.get(offset..offset + width) {
+        Some(bytes) => bytes,
+        None => return false,
+    };
+    let mut value: u64 = 0;
+    for i in 0..width {
+        let b = if big_endian { bytes[i] } else { bytes[width - 1 - i] };
+        value = (value << 8) | b as u64;
+    }
+    value == data.len() as u64 || value == (data.len() - offset - width) as u64
+}
+
+/// 按 (偏移, 宽度, 字节序) 汇总多字节整数探测，只保留改变了行为的位置
+fn int_field_hints(data: &[u8], results: &[&PacketCalibrationResult]) -> Vec<IntFieldHint> {
+    let base = match results.iter().find(|r| r.mutation_operator == BASELINE_OPERATOR) {
+        Some(base) => base,
+        None => return vec![],
+    };
+    let mut groups: BTreeMap<(usize, usize, bool), IntFieldHint> = BTreeMap::new();
+    for r in results.iter().filter(|r| r.width > 1) {
+        let big_endian = r.big_endian.unwrap_or(false);
+        let hint = groups.entry((r.offset, r.width, big_endian)).or_insert(IntFieldHint {
+            offset: r.offset,
+            width: r.width,
+            big_endian,
+            sensitive: 0,
+            probes: 0,
+            length: false,
+        });
+        hint.probes += 1;
+        if r.cf_index != base.cf_index || r.vf_index != base.vf_index {
+            hint.sensitive += 1;
+        }
+        // 长度 ±1 的探测名以 _LENP1 / _LENM1 结尾
+        let len_probe = r.mutation_operator.ends_with("_LENP1") || r.mutation_operator.ends_with("_LENM1");
+        if len_probe && r.cf_index != base.cf_index && matches_length(data, r.offset, r.width, big_endian) {
+            hint.length = true;
+        }
+    }
+    groups.into_values().filter(|hint| hint.sensitive > 0).collect()
+}
+
+/// 汇总各探测的边级差异，得到每条边依赖的偏移（按探测的起始偏移计）
+fn edge_dependencies(results: &[&PacketCalibrationResult]) -> Vec<EdgeDependency> {
+    let mut map: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::new();
+    for r in results.iter() {
+        if let Some(delta) = &r.edge_delta {
+            for edge in delta.edges() {
+                map.entry(edge).or_default().insert(r.offset);
+            }
+        }
+    }
+    map.into_iter()
+        .map(|(edge, offsets)| EdgeDependency { edge, offsets: offsets.into_iter().collect() })
+        .collect()
+}
+
+/// 找出只改变了响应类的探测
+fn response_only_probes(results: &[&PacketCalibrationResult]) -> Vec<ResponseProbe> {
+    let base = match results.iter().find(|r| r.mutation_operator == BASELINE_OPERATOR) {
+        Some(base) => base,
+        None => return vec![],
+    };
+    results
+        .iter()
+        .filter(|r| r.mutation_operator != BASELINE_OPERATOR)
+        .filter(|r| r.cf_index == base.cf_index && r.cfc_index == base.cfc_index && r.vf_index == base.vf_index)
+        .filter(|r| r.rc_index != base.rc_index)
+        .map(|r| ResponseProbe {
+            offset: r.offset,
+            width: r.width,
+            operator: r.mutation_operator.clone(),
+            rc_base: base.rc_index,
+            rc_index: r.rc_index,
+        })
+        .collect()
+}
+
+/// 汇总各探测改变的 ijon 槽位，得到每个状态变量依赖的偏移
+fn slot_dependencies(results: &[&PacketCalibrationResult]) -> Vec<SlotDependency> {
+    let mut map: BTreeMap<usize, (u64, BTreeSet<usize>, BTreeSet<u64>)> = BTreeMap::new();
+    for r in results.iter() {
+        for change in r.slot_changes.iter() {
+            let (_, offsets, values) = map.entry(change.slot).or_insert((change.old, BTreeSet::new(), BTreeSet::new()));
+            offsets.insert(r.offset);
+            if values.len() < MAX_SLOT_VALUES {
+                values.insert(change.new);
+            }
+        }
+    }
+    map.into_iter()
+        .map(|(slot, (base, offsets, values))| SlotDependency {
+            slot,
+            base,
+            offsets: offsets.into_iter().collect(),
+            values: values.into_iter().collect(),
+        })
+        .collect()
+}
+
+/// 把升序偏移压缩成 0x0003-0x0005;0x0010 的形式
+fn format_offsets(offsets: &[usize]) -> String {
+    let mut parts = vec![];
+    let mut i = 0;
+    while i < offsets.len() {
+        let start = offsets[i];
+        while i + 1 < offsets.len() && offsets[i + 1] == offsets[i] + 1 {
+            i += 1;
+        }
+        if offsets[i] == start {
+            parts.push(format!("0x{:04x}", start));
+        } else {
+            parts.push(format!("0x{:04x}-0x{:04x}", start, offsets[i]));
+        }
+        i += 1;
+    }
+    parts.join(";")
+}
+
+/// 对单个包做推断，results 只需包含该包的测量结果，probed 为采样探测过的偏移（None 为全部）。
+/// 校准过程中某个包一测完即可调用，不需要等整个序列结束。
+pub fn infer_packet(
+    packet_id: usize,
+    data: &[u8],
+    results: &[&PacketCalibrationResult],
+    probed: Option<&[usize]>,
+) -> Option<InferredPacket> {
+    let profile = PacketProfile::new(data, results, probed)?;
+    let int_fields = int_field_hints(data, results);
+    let edge_map = edge_dependencies(results);
+    let slot_map = slot_dependencies(results);
+    let response_only = response_only_probes(results);
+    if profile.len == 0 {
+        return Some(InferredPacket {
+            packet_id,
+            data: data.to_vec(),
+            fields: vec![],
+            cf_mask: vec![],
+            int_fields,
+            edge_map,
+            slot_map,
+            response_only,
+        });
+    }
+
+    let masks: Vec<Vec<u8>> = profile
+        .cf_by_op
+        .iter()
+        .map(|seq| {
+            let diff: Vec<i64> = seq.iter().map(|&cf| cf as i64 - profile.cf_base as i64).collect();
+            generate_continue_mask(&diff)
+        })
+        .collect();
+    let cf_mask = (0..profile.len)
+        .map(|i| if profile.measured[i] && !masks.is_empty() && masks.iter().all(|m| m[i] != 0) { 1 } else { 0 })
+        .collect();
+
+    // 字段再按是否测量过切分，未测量的部分不做分类
+    let mut segments = vec![];
+    for (start, end) in segment_fields(&profile) {
+        split_on_change(start, end, |i| profile.measured[i], &mut segments);
+    }
+    let fields = segments
+        .into_iter()
+        .map(|(start, end)| {
+            let field_type = if profile.measured[start] { classify(&profile, data, start, end) } else { FieldType::Unknown };
+            InferredField { start, end, field_type }
+        })
+        .collect();
+
+    Some(InferredPacket { packet_id, data: data.to_vec(), fields, cf_mask, int_fields, edge_map, slot_map, response_only })
+}
+
+/// 把序列的 raw_data 拆成各包负载：每个包为 [u16 LE 长度][负载]，与 python_inference 的 parse_raw_data 一致
+pub fn split_raw_data(raw: &[u8]) -> Vec<Vec<u8>> {
+    let mut packets = vec![];
+    let mut index = 0;
+    while index + 2 <= raw.len() {
+        let len = u16::from_le_bytes([raw[index], raw[index + 1]]) as usize;
+        index += 2;
+        if index + len > raw.len() {
+            break;
+        }
+        packets.push(raw[index..index + len].to_vec());
+        index += len;
+    }
+    packets
+}
+
+/// 对已保存的序列结果离线推断，包负载取自 raw_data；没有 raw_data 时负载为空
+pub fn infer_sequence(results: &SequenceCalibrationResults) -> InferredSequence {
+    let raw = results.raw_data.as_deref().and_then(result_stream::decode_hex).unwrap_or_default();
+    let payloads = split_raw_data(&raw);
+    let packets = (0..results.pkt_number)
+        .filter_map(|packet_id| {
+            let packet_results: Vec<&PacketCalibrationResult> =
+                results.packets_cali_result.iter().filter(|r| r.packet_id == packet_id).collect();
+            let payload = payloads.get(packet_id).map(|p| &p[..]).unwrap_or(&[]);
+            let probed = results.probed_offsets.iter().find(|p| p.packet_id == packet_id).map(|p| &p.offsets[..]);
+            infer_packet(packet_id, payload, &packet_results, probed)
+        })
+        .collect();
+    InferredSequence { sequence_id: results.sequence_id, packets }
+}
+
+impl InferredPacket {
+    /// 单行描述字段布局，用于校准过程中的实时输出
+    pub fn layout(&self) -> String {
+        let mut layout = self.fields
+            .iter()
+            .map(|f| format!("{}[{}..{}]", f.field_type.name(), f.start, f.end))
+            .collect::<Vec<_>>()
+            .join(" ");
+        for hint in self.int_fields.iter().filter(|h| h.length) {
+            layout += &format!(" LEN:{}@{}", hint.type_name(), hint.offset);
+        }
+        for dep in self.slot_map.iter() {
+            layout += &format!(" SLOT{}@{}", dep.slot, format_offsets(&dep.offsets));
+        }
+        if !self.response_only.is_empty() {
+            let offsets: BTreeSet<usize> = self.response_only.iter().map(|p| p.offset).collect();
+            layout += &format!(" RESP@{}", format_offsets(&offsets.into_iter().collect::<Vec<_>>()));
+        }
+        layout
+    }
+
+    /// 覆盖 offsets 中任一偏移的字段，形如 CONTROL[0..3]
+    pub fn fields_at(&self, offsets: &[usize]) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter(|f| offsets.iter().any(|&o| f.start <= o && o <= f.end))
+            .map(|f| format!("{}[{}..{}]", f.field_type.name(), f.start, f.end))
+            .collect()
+    }
+}
+
+impl IntFieldHint {
+    /// 形如 U16BE 的整数类型名
+    pub fn type_name(&self) -> String {
+        format!("U{}{}", self.width * 8, if self.big_endian { "BE" } else { "LE" })
+    }
+}
+
+impl InferredSequence {
+    /// 与 python_inference 输出一致的 CSV 行，首行为表头
+    pub fn to_csv_rows(&self) -> Vec<String> {
+        let mut rows = vec!["pkt,start,end,type".to_string()];
+        for packet in self.packets.iter() {
+            for field in packet.fields.iter() {
+                rows.push(format!(
+                    "{},0x{:04x},0x{:04x},{}",
+                    packet.packet_id,
+                    field.start,
+                    field.end,
+                    field.field_type.name()
+                ));
+            }
+        }
+        rows
+    }
+
+    pub fn write_csv(&self, file_name: &str) -> std::io::Result<()> {
+        let mut file = File::create(file_name)?;
+        for row in self.to_csv_rows() {
+            writeln!(file, "{}", row)?;
+        }
+        Ok(())
+    }
+
+    /// 在 dir 下写出字段 CSV，以及非空时的整数字段与边依赖 CSV，返回写出的文件
+    pub fn write_reports(&self, dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let id = self.sequence_id;
+        let csv_path = dir.join(format!("result_calibration_results_sequence_{}.csv", id));
+        self.write_csv(csv_path.to_str().unwrap())?;
+        let mut written = vec![csv_path];
+        if self.packets.iter().any(|p| !p.int_fields.is_empty()) {
+            let csv_path = dir.join(format!("result_int_fields_sequence_{}.csv", id));
+            self.write_int_csv(csv_path.to_str().unwrap())?;
+            written.push(csv_path);
+        }
+        if self.packets.iter().any(|p| !p.edge_map.is_empty()) {
+            let csv_path = dir.join(format!("result_edge_map_sequence_{}.csv", id));
+            self.write_edge_csv(csv_path.to_str().unwrap())?;
+            written.push(csv_path);
+        }
+        if self.packets.iter().any(|p| !p.slot_map.is_empty()) {
+            let csv_path = dir.join(format!("result_ijon_slots_sequence_{}.csv", id));
+            self.write_slot_csv(csv_path.to_str().unwrap())?;
+            written.push(csv_path);
+        }
+        if self.packets.iter().any(|p| !p.response_only.is_empty()) {
+            let csv_path = dir.join(format!("result_responses_sequence_{}.csv", id));
+            self.write_response_csv(csv_path.to_str().unwrap())?;
+            written.push(csv_path);
+        }
+        Ok(written)
+    }
+
+    /// 多字节整数探测的汇总，每个改变了行为的 (包, 偏移, 宽度, 字节序) 一行
+    pub fn write_int_csv(&self, file_name: &str) -> std::io::Result<()> {
+        let mut file = File::create(file_name)?;
+        writeln!(file, "pkt,offset,type,sensitive,probes,length")?;
+        for packet in self.packets.iter() {
+            for hint in packet.int_fields.iter() {
+                writeln!(
+                    file,
+                    "{},0x{:04x},{},{},{},{}",
+                    packet.packet_id,
+                    hint.offset,
+                    hint.type_name(),
+                    hint.sensitive,
+                    hint.probes,
+                    hint.length as u8
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 只改变了响应类的探测，每个探测一行，并给出偏移所在的推断字段
+    pub fn write_response_csv(&self, file_name: &str) -> std::io::Result<()> {
+        let mut file = File::create(file_name)?;
+        writeln!(file, "pkt,offset,operator,rc_base,rc,fields")?;
+        for packet in self.packets.iter() {
+            for probe in packet.response_only.iter() {
+                writeln!(
+                    file,
+                    "{},0x{:04x},{},{},{},{}",
+                    packet.packet_id,
+                    probe.offset,
+                    probe.operator,
+                    probe.rc_base,
+                    probe.rc_index,
+                    packet.fields_at(&[probe.offset]).join(";")
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 字节到状态变量的依赖表，每个 (包, 槽位) 一行，并给出这些偏移所在的推断字段
+    pub fn write_slot_csv(&self, file_name: &str) -> std::io::Result<()> {
+        let mut file = File::create(file_name)?;
+        writeln!(file, "pkt,slot,base,offsets,fields,values")?;
+        for packet in self.packets.iter() {
+            for dep in packet.slot_map.iter() {
+                let values: Vec<String> = dep.values.iter().map(|v| format!("0x{:x}", v)).collect();
+                writeln!(
+                    file,
+                    "{},{},0x{:x},{},{},{}",
+                    packet.packet_id,
+                    dep.slot,
+                    dep.base,
+                    format_offsets(&dep.offsets),
+                    packet.fields_at(&dep.offsets).join(";"),
+                    values.join(";")
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// 字节到代码的依赖表，每个 (包, 边) 一行
+    pub fn write_edge_csv(&self, file_name: &str) -> std::io::Result<()> {
+        let mut file = File::create(file_name)?;
+        writeln!(file, "pkt,edge,offsets")?;
+        for packet in self.packets.iter() {
+            for dep in packet.edge_map.iter() {
+                writeln!(file, "{},{},{}", packet.packet_id, dep.edge, format_offsets(&dep.offsets))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPS: [&str; 4] = ["LBF", "FBF", "ADD", "SUB"];
+
+    fn result(packet_id: usize, offset: usize, op: &str, (cf, cfc, vf): (usize, usize, usize), stable: bool) -> PacketCalibrationResult {
+        PacketCalibrationResult {
+            packet_id,
+            offset,
+            stable,
+            mutation_operator: op.to_string(),
+            cf_index: cf,
+            vf_index: vf,
+            cfc_index: cfc,
+            rc_index: 0,
+            width: 1,
+            big_endian: None,
+            edge_delta: None,
+            slot_changes: vec![],
+        }
+    }
+
+    /// 基准的各类编号均为 0；class(op, offset) 给出 (cf, cfc, vf)，unstable 中的偏移在 LBF 下不稳定
+    fn packet<F: Fn(&str, usize) -> (usize, usize, usize)>(
+        packet_id: usize,
+        len: usize,
+        class: F,
+        unstable: &[usize],
+    ) -> Vec<PacketCalibrationResult> {
+        let mut results = vec![result(packet_id, 0, BASELINE_OPERATOR, (0, 0, 0), true)];
+        for offset in 0..len {
+            for op in OPS.iter() {
+                let stable = !(*op == "LBF" && unstable.contains(&offset));
+                results.push(result(packet_id, offset, op, class(op, offset), stable));
+            }
+        }
+        results
+    }
+
+    fn sequence(payloads: &[&[u8]], results: Vec<PacketCalibrationResult>) -> SequenceCalibrationResults {
+        let mut raw = vec![];
+        for p in payloads.iter() {
+            raw.extend_from_slice(&(p.len() as u16).to_le_bytes());
+            raw.extend_from_slice(p);
+        }
+        SequenceCalibrationResults {
+            sequence_id: 0,
+            cal_time: 0.0,
+            pkt_number: payloads.len(),
+            raw_data: Some(raw.iter().map(|b| format!("{:02x}", b)).collect()),
+            packets_cali_result: results,
+            var_edges: vec![],
+            probed_offsets: vec![],
+        }
+    }
+
+    fn layout(packet: &InferredPacket) -> Vec<(usize, usize, &'static str)> {
+        packet.fields.iter().map(|f| (f.start, f.end, f.field_type.name())).collect()
+    }
+
+    #[test]
+    fn test_generate_continue_mask() {
+        // 与 python_inference 的实际输出一致（其文档中的例子 1/2 写反了）
+        assert_eq!(generate_continue_mask(&[0, 0, 1, 1, 1, 0, 0, -1, -1]), vec![0, 0, 2, 2, 2, 0, 0, 1, 1]);
+        assert_eq!(generate_continue_mask(&[3, 3, 4, 0, 4]), vec![2, 2, 1, 0, 2]);
+        assert!(generate_continue_mask(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_get_boundaries() {
+        let seq = [5, 5, 6, 7, 7];
+        assert_eq!(get_boundaries(&seq, 0, 4), vec![2]);
+        // 区间不足 5 个偏移
+        assert!(get_boundaries(&seq, 0, 3).is_empty());
+        assert!(get_boundaries(&[5, 5, 5, 7, 7], 0, 4).is_empty());
+    }
+
+    #[test]
+    fn test_split_raw_data() {
+        let raw = [2, 0, b'a', b'b', 0, 0, 1, 0, b'c', 5, 0, b'x'];
+        // 最后一个包声明的长度超出数据，丢弃
+        assert_eq!(split_raw_data(&raw), vec![b"ab".to_vec(), vec![], b"c".to_vec()]);
+        assert!(split_raw_data(&[1]).is_empty());
+    }
+
+    /// 期望结果为 python_inference（main.py -i）对同一份结果 JSON 的输出
+    #[test]
+    fn test_matches_python_inference() {
+        let mut results = packet(
+            0,
+            15,
+            |op, o| {
+                let cf = match o {
+                    0..=3 => 5,
+                    4 => 6,
+                    5..=8 => 7,
+                    _ if o >= 10 && (op == "ADD" || op == "SUB") => 8,
+                    _ => 0,
+                };
+                let cfc = if o >= 10 { 9 } else { cf };
+                let vf = if o >= 10 && op == "LBF" { 1 } else { 0 };
+                (cf, cfc, vf)
+            },
+            &[],
+        );
+        let boundary = |o: usize| match o {
+            0..=1 => 5,
+            2 => 6,
+            _ => 7,
+        };
+        results.extend(packet(1, 5, |_, o| (boundary(o), boundary(o), 0), &[2]));
+        // 只有两个算子认可的分界点不单独成段
+        results.extend(packet(2, 5, |op, o| if op == "LBF" || op == "FBF" { (boundary(o), 5, 0) } else { (5, 5, 0) }, &[]));
+        let seq = sequence(&[b"AUTH:KEY1 hello", b"ab;cd", b"xy-zw"], results);
+
+        let inferred = infer_sequence(&seq);
+        assert_eq!(inferred.packets.len(), 3);
+        assert_eq!(
+            layout(&inferred.packets[0]),
+            vec![(0, 3, "CONTROL"), (4, 4, "DELIMITER"), (5, 8, "CONTROL"), (9, 9, "DATA"), (10, 14, "FLOW")]
+        );
+        // 偏移 2 不稳定，与两侧字段合并
+        assert_eq!(layout(&inferred.packets[1]), vec![(0, 4, "CONTROL")]);
+        assert_eq!(layout(&inferred.packets[2]), vec![(0, 4, "CONTROL")]);
+    }
+
+    #[test]
+    fn test_int_field_hints() {
+        let data = [0x00, 0x04, b'a', b'b', b'c', b'd'];
+        let base = result(0, 0, BASELINE_OPERATOR, (0, 0, 0), true);
+        let int = |offset: usize, op: &str, cf: usize, big_endian: bool| PacketCalibrationResult {
+            width: 2,
+            big_endian: Some(big_endian),
+            ..result(0, offset, op, (cf, 0, 0), true)
+        };
+        let results = [
+            base,
+            int(0, "U16BE_LENP1", 1, true),
+            int(0, "U16BE_LENM1", 2, true),
+            int(0, "U16LE_LENP1", 1, false),
+            int(2, "U16BE_LENP1", 0, true),
+        ];
+        let refs: Vec<&PacketCalibrationResult> = results.iter().collect();
+        let hints = int_field_hints(&data, &refs);
+        // 偏移 2 的探测没有改变行为；小端解释的 0x0400 不是长度
+        assert_eq!(hints.len(), 2);
+        assert_eq!((hints[0].offset, hints[0].big_endian, hints[0].sensitive, hints[0].probes, hints[0].length), (0, false, 1, 1, false));
+        assert_eq!((hints[1].offset, hints[1].big_endian, hints[1].sensitive, hints[1].probes, hints[1].length), (0, true, 2, 2, true));
+    }
+}
@@ -0,0 +1,188 @@
+//! 运行中的机器可读统计：每个分析线程一份 `workdir/analyzer_stats_<tid>`，
+//! 汇总写入 `workdir/analyzer_stats`，均为 key=value 文本，由监控线程定期整体重写。
+
+use crate::class_registry::ClassRegistry;
+use crate::fuzz_runner::ExitReason;
+use crate::queue::Queue;
+use crate::scheduler::CalibrationScheduler;
+use crate::triage;
+
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// 一个分析线程当前的位置与累计计数
+#[derive(Default, Clone)]
+pub struct ThreadStats {
+    pub sequence_id: Option<usize>,
+    pub packet_id: usize,
+    pub num_packets: usize,
+    pub offset: usize,
+    pub packet_len: usize,
+    pub packet_probes_left: u64, // 当前包剩余的探测数
+    pub probes_done: u64,
+    pub unstable_probes: u64,
+    pub execs: u64,
+    pub crashes: u64,
+    pub timeouts: u64,
+}
+
+#[derive(Default)]
+struct LiveStatsData {
+    threads: Vec<ThreadStats>,
+    probes_planned: u64, // 已调度的包的探测总数
+    probes_skipped: u64, // 续跑时断点中已完成的探测数
+}
+
+#[derive(Clone)]
+pub struct LiveStats {
+    workdir: String,
+    quiet: bool,
+    start_time: Instant,
+    data: Arc<RwLock<LiveStatsData>>,
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn ratio(a: u64, b: u64) -> f64 {
+    if b == 0 { 0.0 } else { a as f64 / b as f64 }
+}
+
+fn kv<V: Display>(out: &mut String, key: &str, value: V) {
+    out.push_str(&format!("{}={}\n", key, value));
+}
+
+/// 先写临时文件再 rename，读取方不会看到写了一半的文件
+fn write_atomic(path: &str, content: &str) -> io::Result<()> {
+    let tmp = format!("{}.tmp", path);
+    fs::write(&tmp, content)?;
+    fs::rename(&tmp, path)
+}
+
+impl LiveStats {
+    pub fn new(workdir: &str, threads: usize, quiet: bool) -> Self {
+        let data = LiveStatsData { threads: vec![ThreadStats::default(); threads], ..Default::default() };
+        Self { workdir: workdir.to_string(), quiet, start_time: Instant::now(), data: Arc::new(RwLock::new(data)) }
+    }
+
+    /// 是否关闭交互式输出（进度行与监控线程的屏幕输出）
+    pub fn quiet(&self) -> bool {
+        self.quiet
+    }
+
+    pub fn update<F: FnOnce(&mut ThreadStats)>(&self, thread_id: usize, f: F) {
+        let mut data = self.data.write().unwrap();
+        if let Some(stats) = data.threads.get_mut(thread_id) {
+            f(stats);
+        }
+    }
+
+    pub fn plan_probes(&self, probes: u64) {
+        self.data.write().unwrap().probes_planned += probes;
+    }
+
+    pub fn skip_probes(&self, probes: u64) {
+        self.data.write().unwrap().probes_skipped += probes;
+    }
+
+    /// 线程累计的执行次数，校准时据此计算序列预算的消耗
+    pub fn execs(&self, thread_id: usize) -> u64 {
+        self.data.read().unwrap().threads.get(thread_id).map(|s| s.execs).unwrap_or(0)
+    }
+
+    /// 记录一次执行及其退出原因
+    pub fn record_exec(&self, thread_id: usize, reason: &ExitReason) {
+        self.update(thread_id, |stats| {
+            stats.execs += 1;
+            match reason {
+                ExitReason::Timeout => stats.timeouts += 1,
+                // 与 crashes/ 下的分桶一致：超时以外的非正常退出都计为崩溃
+                _ if triage::is_triaged(reason) => stats.crashes += 1,
+                _ => {}
+            }
+        });
+    }
+
+    /// 所有线程的累计计数之和
+    pub fn totals(&self) -> ThreadStats {
+        let data = self.data.read().unwrap();
+        let mut total = ThreadStats::default();
+        for stats in data.threads.iter() {
+            total.probes_done += stats.probes_done;
+            total.unstable_probes += stats.unstable_probes;
+            total.execs += stats.execs;
+            total.crashes += stats.crashes;
+            total.timeouts += stats.timeouts;
+        }
+        total
+    }
+
+    pub fn runtime_as_secs(&self) -> f64 {
+        self.start_time.elapsed().as_secs_f64().max(1e-3)
+    }
+
+    fn thread_report(thread_id: usize, stats: &ThreadStats, runtime: f64) -> String {
+        let mut out = String::new();
+        kv(&mut out, "thread_id", thread_id);
+        kv(&mut out, "last_update", unix_time());
+        kv(&mut out, "sequence", stats.sequence_id.map(|id| id.to_string()).unwrap_or_default());
+        kv(&mut out, "packet", format!("{}/{}", stats.packet_id + 1, stats.num_packets));
+        kv(&mut out, "offset", format!("{}/{}", stats.offset, stats.packet_len));
+        kv(&mut out, "probes_done", stats.probes_done);
+        kv(&mut out, "packet_probes_remaining", stats.packet_probes_left);
+        kv(&mut out, "unstable_probes", stats.unstable_probes);
+        kv(&mut out, "unstable_ratio", format!("{:.4}", ratio(stats.unstable_probes, stats.probes_done)));
+        kv(&mut out, "execs_done", stats.execs);
+        kv(&mut out, "execs_per_sec", format!("{:.2}", stats.execs as f64 / runtime));
+        kv(&mut out, "crashes", stats.crashes);
+        kv(&mut out, "timeouts", stats.timeouts);
+        out
+    }
+
+    /// 重写每个线程的统计文件与汇总文件
+    pub fn write(&self, queue: &Queue, scheduler: &CalibrationScheduler, classes: &ClassRegistry) -> io::Result<()> {
+        let runtime = self.runtime_as_secs();
+        let total = self.totals();
+        let data = self.data.read().unwrap();
+        for (i, stats) in data.threads.iter().enumerate() {
+            write_atomic(&format!("{}/analyzer_stats_{}", self.workdir, i), &Self::thread_report(i, stats, runtime))?;
+        }
+        let remaining = data.probes_planned.saturating_sub(data.probes_skipped + total.probes_done);
+        let probes_per_sec = total.probes_done as f64 / runtime;
+        let (cf, cfc, vf, rc) = classes.counts();
+
+        let mut out = String::new();
+        kv(&mut out, "start_time", unix_time().saturating_sub(runtime as u64));
+        kv(&mut out, "last_update", unix_time());
+        kv(&mut out, "run_time", runtime as u64);
+        kv(&mut out, "threads", data.threads.len());
+        kv(&mut out, "sequences_active", data.threads.iter().filter(|t| t.sequence_id.is_some()).count());
+        kv(&mut out, "jobs_pending", scheduler.num_pending_jobs());
+        kv(&mut out, "probes_done", total.probes_done + data.probes_skipped);
+        kv(&mut out, "probes_remaining", remaining);
+        // 还没有完成任何探测时无法估计
+        let eta = match remaining {
+            0 => "0".to_string(),
+            _ if probes_per_sec > 0.0 => ((remaining as f64 / probes_per_sec) as u64).to_string(),
+            _ => String::new(),
+        };
+        kv(&mut out, "eta_secs", eta);
+        kv(&mut out, "unstable_probes", total.unstable_probes);
+        kv(&mut out, "unstable_ratio", format!("{:.4}", ratio(total.unstable_probes, total.probes_done)));
+        kv(&mut out, "execs_done", total.execs);
+        kv(&mut out, "execs_per_sec", format!("{:.2}", total.execs as f64 / runtime));
+        kv(&mut out, "crashes", total.crashes);
+        kv(&mut out, "timeouts", total.timeouts);
+        kv(&mut out, "crash_buckets", queue.crashes().len());
+        kv(&mut out, "queue_len", queue.len());
+        kv(&mut out, "cf_classes", cf);
+        kv(&mut out, "cfc_classes", cfc);
+        kv(&mut out, "vf_classes", vf);
+        kv(&mut out, "rc_classes", rc);
+        write_atomic(&format!("{}/analyzer_stats", self.workdir), &out)
+    }
+}
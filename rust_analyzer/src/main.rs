@@ -12,15 +12,18 @@ extern crate glob;
 
 
 use analyzer::SegmentAnalyzer;
+use differential::DifferentialAnalyzer;
 
 use structured_fuzzer::graph_mutator::spec_loader;
+use structured_fuzzer::primitive_mutator::calibration::CalibrationRegistry;
 
 use std::process;
 use std::time::Duration;
 
-use clap::{value_t, App, Arg};
+use clap::{value_t, App, AppSettings, Arg, ArgMatches, SubCommand};
 
 use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::thread;
 
 use fuzz_runner::nyx::qemu_process_new_from_kernel;
@@ -30,7 +33,7 @@ use fuzz_runner::nyx::qemu_process::QemuProcess;
 
 use std::fs;
 
-use config::{Config, FuzzRunnerConfig};
+use config::{Config, FuzzRunnerConfig, FuzzerConfig};
 
 
 
@@ -41,98 +44,517 @@ mod analyzer;
 mod bitmap;
 mod romu;
 mod queue;
+mod queue_store;
 mod hash;
-mod localhashmap;
+mod class_registry;
+mod inference;
+mod scheduler;
+mod checkpoint;
+mod edge_delta;
+mod ijon_slots;
+mod result_stream;
+mod triage;
+mod replay;
+mod offline;
+mod spec_export;
+mod pcap;
+mod seed_import;
+mod live_stats;
+mod sampling;
+mod differential;
+mod catalog;
+mod state_machine;
 use rand::thread_rng;
 use crate::rand::Rng;
 use crate::romu::*;
 use crate::queue::Queue;
+use crate::sampling::Sampling;
+use crate::scheduler::{CalibrationScheduler, SequenceBudget};
+use crate::class_registry::ClassRegistry;
+use crate::live_stats::LiveStats;
 use colored::*;
 
+// 退出码：脚本据此判断结果
+const EXIT_OK: i32 = 0;
+const EXIT_ERROR: i32 = 1;   // 参数、配置或读写错误
+const EXIT_FINDING: i32 = 2; // replay 中出现非正常退出（crash/timeout/asan...）、fuzz 发现了 crash 或 diff 发现了分歧
+
+fn sharedir_arg() -> Arg<'static, 'static> {
+    // 目标打包目录
+    Arg::with_name("sharedir")
+        .short("s")
+        .long("sharedir")
+        .value_name("SHAREDIR_PATH")
+        .takes_value(true)
+        .required(true)
+        .help("path to the sharedir")
+}
+
+fn quiet_arg() -> Arg<'static, 'static> {
+    Arg::with_name("quiet")
+        .short("q")
+        .long("quiet")
+        .takes_value(false)
+        .help("no progress output on the console (progress is still written to <workdir>/analyzer_stats)")
+}
+
+fn packet_node_arg() -> Arg<'static, 'static> {
+    Arg::with_name("packet_node")
+        .long("packet-node")
+        .value_name("NODE")
+        .takes_value(true)
+        .help("node type carrying one message when importing raw or replayable seeds (default: the first node with variable-length data)")
+}
+
+fn cpu_arg() -> Arg<'static, 'static> {
+    Arg::with_name("cpu_start")
+        .short("c")
+        .long("cpu")
+        .value_name("CPU_START")
+        .takes_value(true)
+        .help("overrides the config value for the first CPU to pin threads to")
+}
+
+fn workdir_arg() -> Arg<'static, 'static> {
+    Arg::with_name("workdir")
+        .short("w")
+        .long("workdir")
+        .value_name("WORKDIR")
+        .takes_value(true)
+        .help("workdir")
+}
+
+fn results_arg() -> Arg<'static, 'static> {
+    Arg::with_name("results")
+        .value_name("RESULTS")
+        .multiple(true)
+        .required(true)
+        .help("calibration result files (.msgs or .json) or folders containing them")
+}
+
+fn output_arg() -> Arg<'static, 'static> {
+    Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .value_name("OUTPUT_FOLDER")
+        .takes_value(true)
+        .help("folder to write to (default: next to each input)")
+}
+
 fn main() {
     
     let matches = App::new("nyx")
         .about("Fuzz EVERYTHING!")
-        .arg(// 目标打包目录
-            Arg::with_name("sharedir")
-                .short("s")
-                .long("sharedir")
-                .value_name("SHAREDIR_PATH")
-                .takes_value(true)
-                .help("path to the sharedir"),
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("calibrate")
+                .about("calibrate every seed of the sharedir packet by packet")
+                .arg(sharedir_arg())
+                .arg(cpu_arg())
+                .arg(workdir_arg())
+                .arg(packet_node_arg())
+                .arg(quiet_arg())
+                .arg(//使用python打包转译好的payload
+                    Arg::with_name("dump_payload_folder")
+                        .short("t")
+                        .long("dump_payload_folder")
+                        .value_name("DUMP_PAYLOAD_PATH")
+                        .takes_value(true)
+                        .help("dump payload files to folder"),
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .takes_value(false)
+                        .help("resume an interrupted calibration from the checkpoints and the queue saved in the workdir"),
+                )
+                .arg(
+                    Arg::with_name("operators")
+                        .long("operators")
+                        .value_name("OP1,OP2,...")
+                        .takes_value(true)
+                        .help("calibration operators or groups (byte, u16, u32, u64, len) to probe with (overrides the config value, default: byte)"),
+                )
+                .arg(
+                    Arg::with_name("edge_deltas")
+                        .long("edge-deltas")
+                        .takes_value(false)
+                        .help("record per-probe bitmap edges added/removed/changed relative to the packet baseline"),
+                )
+                .arg(
+                    Arg::with_name("minimize")
+                        .long("minimize")
+                        .takes_value(false)
+                        .help("shrink every seed to the smallest node sequence with the same coverage class and exit reason before calibrating it"),
+                )
+                .arg(
+                    Arg::with_name("sampling")
+                        .long("sampling")
+                        .value_name("STRATEGY")
+                        .takes_value(true)
+                        .help("offsets to probe in each packet: all, first:K, stride:N, random:N or adaptive:N, e.g. first:256,adaptive:8 (overrides the config value, default: all)"),
+                )
+                .arg(
+                    Arg::with_name("seq_time")
+                        .long("seq-time")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .help("time budget per sequence, shared by its remaining packets; packets over budget are marked truncated"),
+                )
+                .arg(
+                    Arg::with_name("seq_execs")
+                        .long("seq-execs")
+                        .value_name("N")
+                        .takes_value(true)
+                        .help("execution budget per sequence, shared by its remaining packets"),
+                )
+                .arg(
+                    Arg::with_name("classes")
+                        .long("classes")
+                        .value_name("CLASS_REGISTRY")
+                        .takes_value(true)
+                        .help("equivalence class registry to load and update (default: <workdir>/class_registry.msgp)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("fuzz")
+                .about("coverage-guided fuzzing of the sharedir seeds with the spec mutator (exit code 2 if crashes were found)")
+                .arg(sharedir_arg())
+                .arg(cpu_arg())
+                .arg(workdir_arg())
+                .arg(packet_node_arg())
+                .arg(quiet_arg())
+                .arg(
+                    Arg::with_name("resume")
+                        .long("resume")
+                        .takes_value(false)
+                        .help("continue with the queue saved in the workdir instead of re-importing the seeds"),
+                )
+                .arg(
+                    Arg::with_name("time")
+                        .long("time")
+                        .value_name("SECONDS")
+                        .takes_value(true)
+                        .help("stop after SECONDS (default: run until interrupted)"),
+                ),
         )
-        .arg(
-            Arg::with_name("cpu_start")
-                .short("c")
-                .long("cpu")
-                .value_name("CPU_START")
-                .takes_value(true)
-                .help("overrides the config value for the first CPU to pin threads to"),
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("run the same seeds and calibration probes against two builds and report where their behaviour diverges (exit code 2 on any divergence)")
+                .arg(sharedir_arg())
+                .arg(
+                    Arg::with_name("against")
+                        .short("b")
+                        .long("against")
+                        .value_name("SHAREDIR_PATH")
+                        .takes_value(true)
+                        .required(true)
+                        .help("sharedir of the second build (its spec must match the first one)"),
+                )
+                .arg(cpu_arg())
+                .arg(workdir_arg())
+                .arg(packet_node_arg())
+                .arg(quiet_arg())
+                .arg(
+                    Arg::with_name("operators")
+                        .long("operators")
+                        .value_name("OP1,OP2,...")
+                        .takes_value(true)
+                        .help("calibration operators or groups to probe with in both builds (overrides the config value of the first sharedir)"),
+                )
+                .arg(
+                    Arg::with_name("sampling")
+                        .long("sampling")
+                        .value_name("STRATEGY")
+                        .takes_value(true)
+                        .help("offsets to probe in each packet: all, first:K, stride:N or random:N (overrides the config value of the first sharedir)"),
+                ),
         )
-        .arg(
-            Arg::with_name("target_file")
-                .short("f")
-                .long("target_file")
-                .value_name("TARGET")
-                .takes_value(true)
-                .help("specifies one target file"),
+        .subcommand(
+            SubCommand::with_name("replay")
+                .about("execute .bin inputs and report how the target exited (exit code 2 on any abnormal exit)")
+                .arg(sharedir_arg())
+                .arg(cpu_arg())
+                .arg(//要复现的测试用例或其所在目录
+                    Arg::with_name("target")
+                        .value_name("TARGET")
+                        .required(true)
+                        .help("a .bin file or a folder of .bin files"),
+                )
+                .arg(
+                    Arg::with_name("repeat")
+                        .long("repeat")
+                        .value_name("N")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("execute every input N times and report whether the runs are stable"),
+                )
+                .arg(
+                    Arg::with_name("per_packet")
+                        .long("per-packet")
+                        .takes_value(false)
+                        .help("also execute every node prefix and show how coverage grows as each node is added"),
+                )
+                .arg(
+                    Arg::with_name("quiet")
+                        .short("q")
+                        .long("quiet")
+                        .takes_value(false)
+                        .help("only report abnormal exits and the summary"),
+                ),
         )
-        .arg(//要复现的测试用例的目录
-            Arg::with_name("target_path")
-                .short("d")
-                .long("target_path")
-                .value_name("TARGET_PATH")
-                .takes_value(true)
-                .help("specifies path to a target folder"),
+        .subcommand(
+            SubCommand::with_name("inspect")
+                .about("decode .bin inputs with the spec")
+                .arg(
+                    Arg::with_name("sharedir")
+                        .short("s")
+                        .long("sharedir")
+                        .value_name("SHAREDIR_PATH")
+                        .takes_value(true)
+                        .required_unless("spec")
+                        .help("path to the sharedir (uses <SHAREDIR>/spec.msgp)"),
+                )
+                .arg(
+                    Arg::with_name("spec")
+                        .long("spec")
+                        .value_name("SPEC")
+                        .takes_value(true)
+                        .help("spec.msgp to decode with"),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(offline::INSPECT_FORMATS)
+                        .default_value("script"),
+                )
+                .arg(
+                    Arg::with_name("target")
+                        .value_name("TARGET")
+                        .multiple(true)
+                        .required(true)
+                        .help(".bin files or folders of .bin files"),
+                ),
         )
-        .arg(//使用python打包转译好的payload
-            Arg::with_name("dump_payload_folder")
-                .short("t")
-                .long("dump_payload_folder")
-                .value_name("DUMP_PAYLOAD_PATH")
-                .takes_value(true)
-                .help("dump payload files to folder"),
+        .subcommand(
+            SubCommand::with_name("infer")
+                .about("segment packets into fields from saved calibration results and write the CSV reports")
+                .arg(results_arg())
+                .arg(output_arg())
+                .arg(
+                    Arg::with_name("quiet")
+                        .short("q")
+                        .long("quiet")
+                        .takes_value(false)
+                        .help("don't print the per-packet layouts"),
+                ),
         )
-        .arg(//使用python打包转译好的payload
-            Arg::with_name("workdir")
-                .short("w")
-                .long("workdir")
-                .value_name("WORKDIR")
-                .takes_value(true)
-                .help("workdir"),
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("export saved calibration results: inferred formats (JSON), python_inference JSON, result streams, a message type catalog, the protocol state machine (JSON and DOT) or a refined spec")
+                .arg(results_arg())
+                .arg(output_arg())
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .value_name("FORMAT")
+                        .takes_value(true)
+                        .possible_values(offline::EXPORT_FORMATS)
+                        .default_value("inferred"),
+                )
+                .arg(
+                    Arg::with_name("spec")
+                        .short("s")
+                        .long("spec")
+                        .value_name("SPEC")
+                        .takes_value(true)
+                        .required_if("format", "spec")
+                        .help("original spec.msgp to refine (--format spec)"),
+                )
+                .arg(
+                    Arg::with_name("packet_node")
+                        .long("packet-node")
+                        .value_name("NODE")
+                        .takes_value(true)
+                        .help("node carrying whole packets (default: the first node with a vec atom)"),
+                )
+                .arg(
+                    Arg::with_name("prefix_bytes")
+                        .long("prefix-bytes")
+                        .value_name("N")
+                        .takes_value(true)
+                        .help("leading bytes that must match for packets to share a message type (--format catalog/states, default: 4)"),
+                )
+                .arg(
+                    Arg::with_name("seeds")
+                        .long("seeds")
+                        .value_name("SEED_FOLDER")
+                        .takes_value(true)
+                        .help(".bin seeds encoded with the original spec to convert to the refined one"),
+                ),
         )
-        .arg(
-            Arg::with_name("quiet")
-                .short("q")
-                .long("quiet")
-                .takes_value(false)
-                .help("quite mode - don't output aux buffer results"),
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("extract client messages from pcap/pcapng captures and write one .bin seed per conversation")
+                .arg(
+                    Arg::with_name("sharedir")
+                        .short("s")
+                        .long("sharedir")
+                        .value_name("SHAREDIR_PATH")
+                        .takes_value(true)
+                        .required_unless("spec")
+                        .help("path to the sharedir (uses <SHAREDIR>/spec.msgp, writes to <SHAREDIR>/seeds)"),
+                )
+                .arg(
+                    Arg::with_name("spec")
+                        .long("spec")
+                        .value_name("SPEC")
+                        .takes_value(true)
+                        .requires("output")
+                        .help("spec.msgp to encode the seeds with"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("OUTPUT_FOLDER")
+                        .takes_value(true)
+                        .help("folder to write the seeds to (default: <SHAREDIR>/seeds)"),
+                )
+                .arg(
+                    Arg::with_name("port")
+                        .short("p")
+                        .long("port")
+                        .value_name("PORT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("server port; packets sent to it are client messages"),
+                )
+                .arg(
+                    Arg::with_name("proto")
+                        .long("proto")
+                        .value_name("PROTO")
+                        .takes_value(true)
+                        .possible_values(&["tcp", "udp"])
+                        .default_value("tcp"),
+                )
+                .arg(
+                    Arg::with_name("node")
+                        .long("node")
+                        .value_name("NODE")
+                        .takes_value(true)
+                        .help("node type carrying a message (default: the first node with variable-length data)"),
+                )
+                .arg(
+                    Arg::with_name("captures")
+                        .value_name("CAPTURE")
+                        .multiple(true)
+                        .required(true)
+                        .help("pcap or pcapng files"),
+                ),
         )
-        .after_help("Example: cargo run --release -- -s <SHAREDIR>  -t <OUTPUT_FOLDER>\n")
+        .after_help("Example: cargo run --release -- calibrate -s <SHAREDIR>  -w <WORKDIR>\n         cargo run --release -- fuzz -s <SHAREDIR> -w <WORKDIR> --time 3600\n")
         .get_matches();
 
     //println!("{:?}", matches);
 
-    let sharedir = matches
-        .value_of("sharedir")
-        .expect("need to specify sharedir (-s)")
-        .to_string();
-    
-    // if !matches.value_of("target_file").is_some() && !matches.value_of("target_path").is_some() {
-    //     panic!("Neither a target_file nor a target_path has been specififed!");
-    // }
+    let code = match matches.subcommand() {
+        ("calibrate", Some(m)) => analyze(m, Job::Calibrate),
+        ("fuzz", Some(m)) => match value_t!(m, "time", u64) {
+            Ok(secs) => analyze(m, Job::Fuzz(Some(Duration::from_secs(secs)))),
+            Err(e) if e.kind == clap::ErrorKind::ArgumentNotFound => analyze(m, Job::Fuzz(None)),
+            Err(e) => e.exit(),
+        },
+        ("diff", Some(m)) => differential(m),
+        ("replay", Some(m)) => replay_inputs(m),
+        ("inspect", Some(m)) => inspect(m),
+        ("infer", Some(m)) => infer(m),
+        ("export", Some(m)) => export(m),
+        ("import", Some(m)) => import(m),
+        _ => EXIT_ERROR,
+    };
+    process::exit(code);
+}
 
+/// 读取 sharedir 中的配置并应用 --cpu/--workdir，未指定 workdir 时使用 /tmp/{name}_workdir_{cpu}/
+fn load_config(matches: &ArgMatches, name: &str) -> (String, FuzzerConfig, FuzzRunnerConfig) {
+    let sharedir = matches.value_of("sharedir").unwrap().to_string();
     let cfg: Config = Config::new_from_sharedir(&sharedir);
 
-
     let mut config = cfg.fuzz;
-    let config_runner = cfg.runner;
-
     if let Ok(start_cpu_id) = value_t!(matches, "cpu_start", usize) {
         config.cpu_pin_start_at = start_cpu_id;
     }
+    if let Some(path) = matches.value_of("workdir") {
+        config.workdir_path = path.to_string();
+    }else{
+        config.workdir_path = format!("/tmp/{}_workdir_{}/", name, config.cpu_pin_start_at);
+    }
+    (sharedir, config, cfg.runner)
+}
+
+fn load_spec(path: &str) -> Option<structured_fuzzer::graph_mutator::spec::GraphSpec> {
+    match File::open(path) {
+        Ok(specfile) => Some(spec_loader::load_spec_from_read(specfile)),
+        Err(e) => {
+            eprintln!("[!] couldn't open spec {}: {}", path, e);
+            None
+        }
+    }
+}
 
-    
+/// 根据具体的运行模式新建 runner
+fn spawn_runner(sharedir: String, runner: &FuzzRunnerConfig, cfg: &FuzzerConfig) -> QemuProcess {
+    let mut runner = match runner {
+        FuzzRunnerConfig::QemuKernel(run_cfg) => qemu_process_new_from_kernel(sharedir, run_cfg, cfg),
+        FuzzRunnerConfig::QemuSnapshot(run_cfg) => qemu_process_new_from_snapshot(sharedir, run_cfg, cfg),
+    };
+    runner.set_timeout(cfg.time_limit); // 根据config设置超时
+    runner
+}
+
+fn paths(matches: &ArgMatches, name: &str) -> Vec<PathBuf> {
+    matches.values_of(name).map(|v| v.map(PathBuf::from).collect()).unwrap_or_default()
+}
+
+/// 应用 --operators/--sampling 并检查算子与采样策略，有误时返回 false
+fn calibration_args(matches: &ArgMatches, config: &mut FuzzerConfig) -> bool {
+    if let Some(ops) = matches.value_of("operators") {
+        config.calibration_operators = ops.split(',').map(|op| op.trim().to_string()).filter(|op| !op.is_empty()).collect();
+    }
+    match CalibrationRegistry::with_defaults().select(&config.calibration_operators) {
+        Ok(ops) => println!("operators:{:?}", ops.iter().map(|op| op.name()).collect::<Vec<_>>()),
+        Err(e) => {
+            eprintln!("[!] {}", e);
+            return false;
+        }
+    }
+    if let Some(sampling) = matches.value_of("sampling") {
+        config.calibration_sampling = sampling.to_string();
+    }
+    if let Err(e) = Sampling::parse(&config.calibration_sampling) {
+        eprintln!("[!] {}", e);
+        return false;
+    }
+    true
+}
+
+/// 分析线程的工作：逐包校准，或在给定时长内（None 为不限时）做模糊测试
+#[derive(Clone, Copy)]
+enum Job {
+    Calibrate,
+    Fuzz(Option<Duration>),
+}
+
+/// calibrate 与 fuzz 子命令：准备 workdir 与共享状态，每个线程启动一个 qemu 实例执行 job
+fn analyze(matches: &ArgMatches, job: Job) -> i32 {
+    let name = match job {
+        Job::Calibrate => "calibrate",
+        Job::Fuzz(_) => "fuzz",
+    };
+    let (sharedir, mut config, config_runner) = load_config(matches, name);
 
     //println!("DUMP: {}", matches.value_of("dump_payload_folder").is_some());
     config.dump_python_code_for_inputs = Some(matches.value_of("dump_payload_folder").is_some());
@@ -141,21 +563,37 @@ fn main() {
         fs::create_dir_all(matches.value_of("dump_payload_folder").unwrap()).unwrap();
     }
 
-    
-    if let Some(path) = matches.value_of("workdir") {
-        config.workdir_path = path.to_string();
-    }else{
-        config.workdir_path = format!("/tmp/calibrate_workdir_{}/", config.cpu_pin_start_at);
+    if matches.is_present("edge_deltas") {
+        config.record_edge_deltas = true;
+    }
+    if matches.is_present("minimize") {
+        config.minimize_seeds = true;
+    }
+    if !calibration_args(matches, &mut config) {
+        return EXIT_ERROR;
+    }
+    if matches.is_present("seq_time") {
+        config.calibration_seq_time = Some(Duration::from_secs(value_t!(matches, "seq_time", u64).unwrap_or_else(|e| e.exit())));
+    }
+    if matches.is_present("seq_execs") {
+        config.calibration_seq_execs = Some(value_t!(matches, "seq_execs", u64).unwrap_or_else(|e| e.exit()));
     }
-    // let sdir = sharedir.clone();
-
-    let specfile = File::open(&config.spec_path).expect(&format!(
-        "couldn't open spec (File not found: {}",
-        config.spec_path
-    ));
 
-    let spec = spec_loader::load_spec_from_read(specfile);
-    let queue = Queue::new(&config);
+    let spec = match load_spec(&config.spec_path) {
+        Some(spec) => spec,
+        None => return EXIT_ERROR,
+    };
+    // 续跑时不能清空workdir，只清理上次运行遗留的运行时文件
+    let resume = matches.is_present("resume")
+        && Path::new(&config.workdir_path).join("seeds").exists();
+    if matches.is_present("resume") && !resume {
+        println!("[!] nothing to resume in {}, starting a fresh {}", config.workdir_path, name);
+    }
+    let budget = SequenceBudget {
+        secs: config.calibration_seq_time.map(|d| d.as_secs_f32()),
+        execs: config.calibration_seq_execs,
+    };
+    let scheduler = CalibrationScheduler::new(resume, budget);
     let timeout = config.time_limit;
     println!("timeout:{:?}",timeout);
 
@@ -163,9 +601,32 @@ fn main() {
     let core_ids = core_affinity::get_core_ids().unwrap();
     let seed = value_t!(matches, "cpu_start", u64).unwrap_or(thread_rng().gen());
     let mut rng = RomuPrng::new_from_u64(seed);
-    QemuProcess::prepare_workdir(&config.workdir_path, config.seed_path.clone());
+    if resume {
+        QemuProcess::prepare_workdir_for_resume(&config.workdir_path);
+    } else {
+        // 种子按格式转换为 .bin 后写入 workdir/seeds，而不是原样复制
+        QemuProcess::prepare_workdir(&config.workdir_path, None);
+        if let Some(seed_path) = config.seed_path.as_ref() {
+            let seed_dir = Path::new(&config.workdir_path).join("seeds");
+            match seed_import::import_seeds(Path::new(seed_path), &seed_dir, &spec, matches.value_of("packet_node")) {
+                Ok(n) => println!("[!] imported {} seeds from {}", n, seed_path),
+                Err(e) => {
+                    eprintln!("[!] couldn't import seeds from {}: {}", seed_path, e);
+                    return EXIT_ERROR;
+                }
+            }
+        }
+    }
+    // 队列中的 crash 分桶会读取 workdir 中已有的 crashes/index.json，需在准备 workdir 之后创建
+    // 续跑时恢复保存的队列，无需重新导入、执行种子
+    let queue = if resume { Queue::load_or_new(&config, &spec) } else { Queue::new(&config) };
+    // 等价类注册表在所有线程间共享，workdir 准备好之后再加载
+    let classes = match matches.value_of("classes") {
+        Some(path) => ClassRegistry::load_or_new(path),
+        None => ClassRegistry::load_or_new(&format!("{}/class_registry.msgp", config.workdir_path)),
+    };
 
-    
+    let stats = LiveStats::new(&config.workdir_path, config.threads, matches.is_present("quiet"));
 
     for i in 0..config.threads {
         let mut cfg = config.clone();
@@ -173,74 +634,274 @@ fn main() {
 
         let spec1 = spec.clone();
         let queue1 = queue.clone(); //每次新建一个queue的拷贝
+        let scheduler1 = scheduler.clone();
+        let classes1 = classes.clone();
+        let stats1 = stats.clone();
         let core_id = core_ids[(i + cfg.cpu_pin_start_at) % core_ids.len()].clone();
         let thread_seed = rng.next_u64();
         let sdir = sharedir.clone();
+        let runner_cfg = config_runner.clone();
+
+        thread_handles.push(thread::spawn(move ||{
+            println!("[!] fuzzer: spawning qemu instance #{}", i);  // 打印信息
+            core_affinity::set_for_current(core_id);
+            let runner = spawn_runner(sdir, &runner_cfg, &cfg);
+            let mut analyzer = SegmentAnalyzer::new(runner, cfg, spec1,queue1,scheduler1,classes1,stats1,thread_seed);
+            match job {
+                Job::Calibrate => analyzer.run(),
+                Job::Fuzz(budget) => analyzer.fuzz(budget),
+            }
+            analyzer.shutdown();
+            println!("[!] analyzer #{}: FINISH!", i);
+        }));
+        if let FuzzRunnerConfig::QemuSnapshot(_) = config_runner {
+            std::thread::sleep(Duration::from_millis(100));  // 线程休眠一段时间
+        }
+    }
 
-        match config_runner.clone() {
-
-            FuzzRunnerConfig::QemuKernel(run_cfg) => {
-                thread_handles.push(thread::spawn(move ||{
-                    
-                    core_affinity::set_for_current(core_id);  
-                    let mut runner = qemu_process_new_from_kernel(sdir, &run_cfg, &cfg);
-                    runner.set_timeout(cfg.time_limit); // 设置超时
-                    //runner.aux.config.page_dump_mode = 1;
-                    //runner.aux.config.changed = 1;
-                    let mut analyzer = SegmentAnalyzer::new(runner, cfg, spec1,queue1,thread_seed);
-                    analyzer.run();
-                    analyzer.shutdown();
-                    println!("[!] analyzer: FINISH!");
-                    process::exit(0);
-                // execute(&mut runner, &matches, quite_mode, &config_fuzzer.workdir_path,spec);
-                }))
+    // 监控线程不参与 join：所有分析线程结束（任务队列清空或模糊测试到时）后进程退出
+    // 统计文件每 5 秒重写一次，屏幕输出每分钟一次
+    let (monitor, monitor_scheduler, monitor_classes, monitor_stats) = (queue.clone(), scheduler.clone(), classes.clone(), stats.clone());
+    thread::spawn(move || {
+        for tick in 0u64.. {
+            if let Err(e) = monitor_stats.write(&monitor, &monitor_scheduler, &monitor_classes) {
+                eprintln!("[!] couldn't write analyzer_stats: {}", e);
             }
-            
-            FuzzRunnerConfig::QemuSnapshot(run_cfg) => {
-                thread_handles.push(thread::spawn(move ||{
-                println!("[!] fuzzer: spawning qemu instance #{}", i);  // 打印信息
-                core_affinity::set_for_current(core_id);   
-                let mut runner = qemu_process_new_from_snapshot(sdir, &run_cfg, &cfg);
-                runner.set_timeout(cfg.time_limit); // 根据config设置超时
-                let mut analyzer = SegmentAnalyzer::new(runner, cfg, spec1,queue1,thread_seed);
-                // execute(&mut runner, &matches, quite_mode, &config_fuzzer.workdir_path,spec);
-                analyzer.run();
-                println!("[!] analyzer: FINISH!");
-                process::exit(0);
-                }));
-                std::thread::sleep(Duration::from_millis(100));  // 线程休眠一段时间
+            let total = monitor_stats.totals();
+            if tick % 12 == 0 && total.execs > 0 && !monitor_stats.quiet() {
+                let runtime = monitor_stats.runtime_as_secs();
+                println!("[!] {}", format!("Execs/sec: {:.2}, Time:{:.0}s, total_execs:{}, probes:{}, queue:{}, crash buckets:{}", total.execs as f64 / runtime, runtime, total.execs, total.probes_done, monitor.len(), monitor.crashes().len()).yellow().bold());
             }
+            std::thread::sleep(Duration::from_secs(5));
+        }
+    });
+    let mut code = EXIT_OK;
+    for t in thread_handles.into_iter() {
+        if t.join().is_err() {
+            code = EXIT_ERROR;
+        }
+    }
+    if let Err(e) = stats.write(&queue, &scheduler, &classes) {
+        eprintln!("[!] couldn't write analyzer_stats: {}", e);
+    }
+    if let (Job::Fuzz(_), EXIT_OK) = (job, code) {
+        if queue.crashes().len() > 0 {
+            code = EXIT_FINDING;
         }
     }
-    //  根据具体的运行模式新建runer，开始测试
+    code
+}
 
-    thread_handles.push(thread::spawn(move || {
-        // let mut num_bits_last = 0;
-    
-    loop {
-            let total_execs = queue.get_total_execs();
-
-            
-            if total_execs > 0 {
-                // let num_bits = queue.num_bits();
-
-                println!("[!] {}", format!("Execs/sec: {}, Time:{}s, total_execs:{}", total_execs as f32 / queue.get_runtime_as_secs_f32(),queue.get_runtime_as_secs_f32(),total_execs).yellow().bold()); 
-                // std::fs::write(
-                //     &format!(
-                //     "{}/tree.dot",
-                //     config.workdir_path,
-                //     ),
-                //     queue.print_snap_tree()
-                // ).unwrap();
-    
+/// diff 子命令：两个构建各启动一个 runner，workdir 下分别为 <name>_a 与 <name>_b；
+/// 种子由第一个构建导入，两个构建使用相同的算子与采样策略
+fn differential(matches: &ArgMatches) -> i32 {
+    let (sharedir_a, mut config_a, runner_a) = load_config(matches, "diff");
+    let sharedir_b = matches.value_of("against").unwrap().to_string();
+    let cfg_b = Config::new_from_sharedir(&sharedir_b);
+    let (mut config_b, runner_b) = (cfg_b.fuzz, cfg_b.runner);
+    if !calibration_args(matches, &mut config_a) {
+        return EXIT_ERROR;
+    }
+    let (spec_a, spec_b) = match (load_spec(&config_a.spec_path), load_spec(&config_b.spec_path)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return EXIT_ERROR,
+    };
+    if spec_a.checksum != spec_b.checksum {
+        eprintln!(
+            "[!] {} and {} use different specs ({:x} != {:x})",
+            sharedir_a, sharedir_b, spec_a.checksum, spec_b.checksum
+        );
+        return EXIT_ERROR;
+    }
+
+    let workdir = config_a.workdir_path.clone();
+    let name = Path::new(&workdir).file_name().and_then(|n| n.to_str()).unwrap_or("diff").to_string();
+    config_a.threads = 1;
+    config_a.thread_id = 0;
+    config_a.workdir_path = format!("{}/{}_a", workdir, name);
+    config_b.threads = 1;
+    config_b.thread_id = 0;
+    config_b.workdir_path = format!("{}/{}_b", workdir, name);
+    config_b.cpu_pin_start_at = config_a.cpu_pin_start_at;
+    config_b.calibration_operators = config_a.calibration_operators.clone();
+    config_b.calibration_sampling = config_a.calibration_sampling.clone();
+
+    QemuProcess::prepare_workdir(&config_a.workdir_path, None);
+    QemuProcess::prepare_workdir(&config_b.workdir_path, None);
+    if let Some(seed_path) = config_a.seed_path.as_ref() {
+        let seed_dir = Path::new(&config_a.workdir_path).join("seeds");
+        match seed_import::import_seeds(Path::new(seed_path), &seed_dir, &spec_a, matches.value_of("packet_node")) {
+            Ok(n) => println!("[!] imported {} seeds from {}", n, seed_path),
+            Err(e) => {
+                eprintln!("[!] couldn't import seeds from {}: {}", seed_path, e);
+                return EXIT_ERROR;
+            }
+        }
+    }
+
+    // 两个构建的变异使用相同的随机数种子
+    let seed = value_t!(matches, "cpu_start", u64).unwrap_or(thread_rng().gen());
+    let quiet = matches.is_present("quiet");
+    let build = |sharedir: String, runner: &FuzzRunnerConfig, cfg: FuzzerConfig, spec| {
+        let fuzzer = spawn_runner(sharedir, runner, &cfg);
+        let queue = Queue::new(&cfg);
+        let scheduler = CalibrationScheduler::new(false, SequenceBudget::default());
+        let classes = ClassRegistry::load_or_new(&format!("{}/class_registry.msgp", cfg.workdir_path));
+        let stats = LiveStats::new(&cfg.workdir_path, 1, quiet);
+        SegmentAnalyzer::new(fuzzer, cfg, spec, queue, scheduler, classes, stats, seed)
+    };
+    let a = build(sharedir_a, &runner_a, config_a, spec_a);
+    let b = build(sharedir_b, &runner_b, config_b, spec_b);
+    let mut diff = DifferentialAnalyzer::new(a, b, &workdir);
+    let res = diff.run();
+    diff.shutdown();
+    match res {
+        Ok(0) => EXIT_OK,
+        Ok(n) => {
+            println!("[!] {} divergences, see {}/differential/divergences.csv", n, workdir);
+            EXIT_FINDING
+        }
+        Err(e) => {
+            eprintln!("[!] differential calibration failed: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn replay_inputs(matches: &ArgMatches) -> i32 {
+    let (sharedir, mut config, config_runner) = load_config(matches, "replay");
+    let spec = match load_spec(&config.spec_path) {
+        Some(spec) => spec,
+        None => return EXIT_ERROR,
+    };
+    let target = matches.value_of("target").unwrap();
+    let files = match replay::collect_bins(Path::new(target)) {
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("[!] couldn't read {}: {}", target, e);
+            return EXIT_ERROR;
+        }
+    };
+
+    // 复现只需要一个实例，也不导入种子；prepare_workdir 会清空 workdir，
+    // 用进程私有的临时目录，不碰任何已有的 workdir（要复现的输入往往就在其中）
+    config.threads = 1;
+    config.thread_id = 0;
+    config.workdir_path = std::env::temp_dir()
+        .join(format!("replay_workdir_{}", process::id()))
+        .to_string_lossy()
+        .into_owned();
+    QemuProcess::prepare_workdir(&config.workdir_path, None);
+    let mut runner = spawn_runner(sharedir, &config_runner, &config);
+    let opts = replay::ReplayOptions {
+        repeat: value_t!(matches, "repeat", usize).unwrap_or(1).max(1),
+        per_packet: matches.is_present("per_packet"),
+        quiet: matches.is_present("quiet"),
+    };
+    let res = replay::replay(&mut runner, &spec, &files, &opts);
+    runner.shutdown();
+    let _ = fs::remove_dir_all(&config.workdir_path);
+    match res {
+        Ok((findings, _)) if findings > 0 => EXIT_FINDING,
+        Ok((_, skipped)) if skipped > 0 => EXIT_ERROR,
+        Ok(_) => EXIT_OK,
+        Err(e) => {
+            eprintln!("[!] replay failed: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
+
+fn inspect(matches: &ArgMatches) -> i32 {
+    let spec_path = match matches.value_of("spec") {
+        Some(path) => path.to_string(),
+        None => format!("{}/spec.msgp", matches.value_of("sharedir").unwrap()),
+    };
+    let spec = match load_spec(&spec_path) {
+        Some(spec) => spec,
+        None => return EXIT_ERROR,
+    };
+    let mut files = vec![];
+    for target in paths(matches, "target") {
+        match replay::collect_bins(&target) {
+            Ok(found) => files.extend(found),
+            Err(e) => {
+                eprintln!("[!] couldn't read {:?}: {}", target, e);
+                return EXIT_ERROR;
             }
-            std::thread::sleep(Duration::from_millis(1000*60));
         }
-    }));
-    for t in thread_handles.into_iter() {
-        t.join().unwrap();
     }
+    let failed = offline::inspect(&spec, &files, matches.value_of("format").unwrap());
+    if failed > 0 { EXIT_ERROR } else { EXIT_OK }
+}
 
+fn infer(matches: &ArgMatches) -> i32 {
+    let out_dir = matches.value_of("output").map(Path::new);
+    match offline::infer(&paths(matches, "results"), out_dir, matches.is_present("quiet")) {
+        Ok(0) => EXIT_OK,
+        Ok(_) => EXIT_ERROR,
+        Err(e) => {
+            eprintln!("[!] infer failed: {}", e);
+            EXIT_ERROR
+        }
+    }
 }
 
+fn export(matches: &ArgMatches) -> i32 {
+    let out_dir = matches.value_of("output").map(Path::new);
+    let format = matches.value_of("format").unwrap();
+    let prefix_len = if matches.is_present("prefix_bytes") {
+        value_t!(matches, "prefix_bytes", usize).unwrap_or_else(|e| e.exit())
+    } else {
+        catalog::DEFAULT_PREFIX_LEN
+    };
+    let res = if format == "catalog" {
+        offline::export_catalog(&paths(matches, "results"), prefix_len, out_dir.unwrap_or_else(|| Path::new(".")))
+    } else if format == "states" {
+        offline::export_states(&paths(matches, "results"), prefix_len, out_dir.unwrap_or_else(|| Path::new(".")))
+    } else if format == "spec" {
+        offline::export_spec(
+            &paths(matches, "results"),
+            Path::new(matches.value_of("spec").unwrap()),
+            matches.value_of("packet_node"),
+            matches.value_of("seeds").map(Path::new),
+            out_dir.unwrap_or_else(|| Path::new(".")),
+        )
+    } else {
+        offline::export(&paths(matches, "results"), format, out_dir)
+    };
+    match res {
+        Ok(0) => EXIT_OK,
+        Ok(_) => EXIT_ERROR,
+        Err(e) => {
+            eprintln!("[!] export failed: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
 
+fn import(matches: &ArgMatches) -> i32 {
+    let sharedir = matches.value_of("sharedir");
+    let spec_path = match matches.value_of("spec") {
+        Some(path) => path.to_string(),
+        None => format!("{}/spec.msgp", sharedir.unwrap()),
+    };
+    let spec = match load_spec(&spec_path) {
+        Some(spec) => spec,
+        None => return EXIT_ERROR,
+    };
+    let out_dir = match matches.value_of("output") {
+        Some(path) => PathBuf::from(path),
+        None => Path::new(sharedir.unwrap()).join("seeds"),
+    };
+    let port = value_t!(matches, "port", u16).unwrap_or_else(|e| e.exit());
+    let transport = pcap::Transport::from_name(matches.value_of("proto").unwrap()).unwrap();
+    match offline::import_captures(&spec, &paths(matches, "captures"), transport, port, matches.value_of("node"), &out_dir) {
+        Ok(0) => EXIT_OK,
+        Ok(_) => EXIT_ERROR,
+        Err(e) => {
+            eprintln!("[!] import failed: {}", e);
+            EXIT_ERROR
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! 不需要启动目标的离线子命令：inspect（按 spec 解码 .bin）、
+//! infer（对已保存的校准结果做字段推断）、export（导出推断结果、转换结果格式、生成消息类型目录、状态机或细化的 spec）、
+//! import（从抓包中提取会话并生成种子）。
+
+use crate::analyzer::SequenceCalibrationResults;
+use crate::catalog;
+use crate::inference::{self, InferredSequence};
+use crate::pcap::{self, Transport};
+use crate::replay;
+use crate::result_stream::{self, STREAM_EXTENSION};
+use crate::seed_import;
+use crate::spec_export;
+use crate::state_machine;
+use crate::structured_fuzzer::graph_mutator::spec::GraphSpec;
+use crate::structured_fuzzer::graph_mutator::spec_loader;
+use crate::structured_fuzzer::GraphStorage;
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const INSPECT_FORMATS: &[&str] = &["script", "dot", "payloads"];
+pub const EXPORT_FORMATS: &[&str] = &["inferred", "json", "msgs", "catalog", "states", "spec"];
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 输出目录：显式给出时使用该目录，否则写在输入文件旁边
+fn output_dir(input: &Path, out_dir: Option<&Path>) -> PathBuf {
+    match out_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => input.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| PathBuf::from(".")),
+    }
+}
+
+/// 按 spec 解码并打印 .bin 测试用例，返回无法解码的文件数
+pub fn inspect(spec: &GraphSpec, files: &[PathBuf], format: &str) -> usize {
+    let mut failed = 0;
+    for file in files.iter() {
+        let graph = match replay::load_bin(file, spec) {
+            Ok(graph) => graph,
+            Err(e) => {
+                eprintln!("[Inspect] {:?}: {}", file, e);
+                failed += 1;
+                continue;
+            }
+        };
+        println!("# {:?}: {} nodes, {} ops, {} data bytes", file, graph.node_len(spec), graph.op_len(), graph.data_len());
+        match format {
+            "dot" => println!("{}", graph.to_dot(spec)),
+            "payloads" => {
+                for (i, node) in graph.node_iter(spec).enumerate() {
+                    let name = spec.get_node(node.id).map(|n| n.name.as_str()).unwrap_or("?");
+                    println!("{} {} {}", i, name, hex(node.data));
+                }
+            }
+            _ => println!("{}", graph.to_script(spec)),
+        }
+    }
+    failed
+}
+
+fn load_results(file: &Path) -> Option<crate::analyzer::SequenceCalibrationResults> {
+    match result_stream::load(file) {
+        Ok(results) => Some(results),
+        Err(e) => {
+            eprintln!("[Infer] failed to read {:?}: {}", file, e);
+            None
+        }
+    }
+}
+
+/// 对保存的校准结果（.msgs 或 .json）重新做字段推断并写出 CSV，返回失败的文件数
+pub fn infer(inputs: &[PathBuf], out_dir: Option<&Path>, quiet: bool) -> io::Result<usize> {
+    let mut failed = 0;
+    for file in result_stream::collect(inputs)? {
+        let results = match load_results(&file) {
+            Some(results) => results,
+            None => {
+                failed += 1;
+                continue;
+            }
+        };
+        let inferred = inference::infer_sequence(&results);
+        if !quiet {
+            for packet in inferred.packets.iter() {
+                println!("[Infer] sequence {} packet {}: {}", inferred.sequence_id, packet.packet_id, packet.layout());
+            }
+        }
+        match inferred.write_reports(&output_dir(&file, out_dir)) {
+            Ok(written) => println!("[Infer] {:?} -> {:?}", file, written),
+            Err(e) => {
+                eprintln!("[Infer] failed to write reports for {:?}: {}", file, e);
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+fn write_inferred(inferred: &InferredSequence, path: &Path) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(serde_json::to_string_pretty(inferred)?.as_bytes())
+}
+
+/// 导出保存的校准结果，返回失败的文件数。
+/// inferred：推断出的字段/整数/边依赖（JSON）；json：python_inference 可读的结果 JSON；msgs：结果流
+pub fn export(inputs: &[PathBuf], format: &str, out_dir: Option<&Path>) -> io::Result<usize> {
+    let mut failed = 0;
+    for file in result_stream::collect(inputs)? {
+        let results = match load_results(&file) {
+            Some(results) => results,
+            None => {
+                failed += 1;
+                continue;
+            }
+        };
+        let dir = output_dir(&file, out_dir);
+        let id = results.sequence_id;
+        let (out, res) = match format {
+            "json" => {
+                let out = dir.join(format!("calibration_results_sequence_{}.json", id));
+                let res = result_stream::write_json(&results, &out);
+                (out, res)
+            }
+            "msgs" => {
+                let out = dir.join(format!("calibration_results_sequence_{}.{}", id, STREAM_EXTENSION));
+                let res = result_stream::write_stream(results, &out);
+                (out, res)
+            }
+            _ => {
+                let out = dir.join(format!("inferred_sequence_{}.json", id));
+                let res = write_inferred(&inference::infer_sequence(&results), &out);
+                (out, res)
+            }
+        };
+        match res {
+            Ok(()) => println!("[Export] {:?} -> {:?}", file, out),
+            Err(e) => {
+                eprintln!("[Export] failed to write {:?}: {}", out, e);
+                failed += 1;
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// 读取所有结果文件，返回 (文件, 结果) 与无法读取的文件数
+fn load_all(inputs: &[PathBuf]) -> io::Result<(Vec<(PathBuf, SequenceCalibrationResults)>, usize)> {
+    let mut failed = 0;
+    let mut sequences = vec![];
+    for file in result_stream::collect(inputs)? {
+        match load_results(&file) {
+            Some(results) => sequences.push((file, results)),
+            None => failed += 1,
+        }
+    }
+    Ok((sequences, failed))
+}
+
+/// 把所有结果文件中的包聚类为消息类型，目录写入 out_dir。返回无法读取的文件数
+pub fn export_catalog(inputs: &[PathBuf], prefix_len: usize, out_dir: &Path) -> io::Result<usize> {
+    let (sequences, failed) = load_all(inputs)?;
+    let catalog = catalog::build(&sequences, prefix_len);
+    for ty in catalog.types.iter() {
+        println!(
+            "[Export] {}: {} packets, prefix {}, cf {}, len {}-{} {}",
+            ty.name, ty.members, ty.prefix, ty.baseline_cf, ty.min_len, ty.max_len, ty.layout()
+        );
+    }
+    fs::create_dir_all(out_dir)?;
+    let written = catalog.write(out_dir)?;
+    println!("[Export] {} packets in {} message types -> {:?}", catalog.packets, catalog.types.len(), written);
+    Ok(failed)
+}
+
+/// 由所有结果文件中各前缀的基准类合并出协议状态机，边按消息类型目录标注，写入 out_dir。
+/// 返回无法读取的文件数
+pub fn export_states(inputs: &[PathBuf], prefix_len: usize, out_dir: &Path) -> io::Result<usize> {
+    let (sequences, failed) = load_all(inputs)?;
+    let catalog = catalog::build(&sequences, prefix_len);
+    let machine = state_machine::build(&sequences, &catalog);
+    for node in machine.nodes.iter() {
+        // 初始状态没有 (cf, vf) 类，不单独列出
+        let (cf, vf) = match (node.cf_index, node.vf_index) {
+            (Some(cf), Some(vf)) => (cf, vf),
+            _ => continue,
+        };
+        println!(
+            "[Export] state {} (cf {}, vf {}): {} visits from {} sequences, reached by [{}]",
+            node.id,
+            cf,
+            vf,
+            node.visits,
+            node.sequences,
+            node.shortest_path.join(" ")
+        );
+    }
+    fs::create_dir_all(out_dir)?;
+    let written = machine.write(out_dir)?;
+    println!("[Export] {} states, {} transitions -> {:?}", machine.nodes.len(), machine.edges.len(), written);
+    Ok(failed)
+}
+
+/// 由所有结果文件推断出的包格式生成细化的 spec.msgp 写入 out_dir，
+/// 给出 seeds 时把其中按原 spec 编码的 .bin 转换到 out_dir/seeds/。返回失败的文件数
+pub fn export_spec(
+    inputs: &[PathBuf],
+    orig_spec: &Path,
+    packet_node: Option<&str>,
+    seeds: Option<&Path>,
+    out_dir: &Path,
+) -> io::Result<usize> {
+    let mut failed = 0;
+    let mut sequences = vec![];
+    for file in result_stream::collect(inputs)? {
+        match load_results(&file) {
+            Some(results) => sequences.push(inference::infer_sequence(&results)),
+            None => failed += 1,
+        }
+    }
+
+    let orig = spec_loader::load_spec_loader_from_read(File::open(orig_spec)?);
+    let node = spec_export::find_packet_node(&orig, packet_node).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::NotFound, format!("no packet node {:?} in {:?}", packet_node, orig_spec))
+    })?;
+    let types = spec_export::message_types(&sequences);
+    for ty in types.iter() {
+        let fields: Vec<String> = ty.header.iter().map(|f| format!("{}:{}{:?}", f.name, f.width, f.options)).collect();
+        println!(
+            "[Export] {}: {} packets, header {} bytes{} [{}]",
+            ty.name,
+            ty.members,
+            ty.header_len,
+            if ty.has_payload { " + payload" } else { "" },
+            fields.join(" ")
+        );
+    }
+    let refined = spec_export::refine(&orig, node, types)?;
+
+    fs::create_dir_all(out_dir)?;
+    let out = out_dir.join("spec.msgp");
+    refined.write_to(&mut File::create(&out)?)?;
+    println!("[Export] {} message types -> {:?} (checksum {:x})", refined.types.len(), out, refined.loader.checksum);
+
+    if let Some(seeds) = seeds {
+        let orig_spec = orig.to_graph_spec();
+        let new_spec = refined.graph_spec()?;
+        let seed_dir = out_dir.join("seeds");
+        fs::create_dir_all(&seed_dir)?;
+        for file in replay::collect_bins(seeds)? {
+            let graph = match replay::load_bin(&file, &orig_spec) {
+                Ok(graph) => graph,
+                Err(e) => {
+                    eprintln!("[Export] skipping {:?}: {}", file, e);
+                    failed += 1;
+                    continue;
+                }
+            };
+            let (converted, packets) = refined.convert(&graph, &orig_spec);
+            let out = seed_dir.join(file.file_name().unwrap());
+            converted.write_to_file(out.to_str().unwrap(), &new_spec);
+            println!("[Export] {:?} -> {:?} ({}/{} packets typed)", file, out, packets, graph.node_len(&orig_spec));
+        }
+    }
+    Ok(failed)
+}
+
+/// 从 pcap/pcapng 抓包中提取服务端端口为 port 的会话，每个会话写成 out_dir 下的一个 .bin 种子，
+/// 返回无法读取或编码的抓包/会话数
+pub fn import_captures(
+    spec: &GraphSpec,
+    captures: &[PathBuf],
+    transport: Transport,
+    port: u16,
+    packet_node: Option<&str>,
+    out_dir: &Path,
+) -> io::Result<usize> {
+    let node = seed_import::find_packet_node(spec, packet_node)?;
+    fs::create_dir_all(out_dir)?;
+    let mut failed = 0;
+    let mut written = 0;
+    for capture in captures.iter() {
+        let conversations = match fs::read(capture).and_then(|data| pcap::conversations(&data, transport, port)) {
+            Ok(conversations) => conversations,
+            Err(e) => {
+                eprintln!("[Import] skipping {:?}: {}", capture, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let stem = capture.file_stem().and_then(|s| s.to_str()).unwrap_or("capture");
+        for (i, conversation) in conversations.iter().enumerate() {
+            match seed_import::messages_to_graph(spec, node, &conversation.messages) {
+                Ok(graph) => {
+                    let out = out_dir.join(format!("{}_{}.bin", stem, i));
+                    graph.write_to_file(out.to_str().unwrap(), spec);
+                    println!("[Import] {} ({} messages) -> {:?}", conversation, conversation.messages.len(), out);
+                    written += 1;
+                }
+                Err(e) => {
+                    eprintln!("[Import] {:?} {}: {}", capture, conversation, e);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    println!("[Import] wrote {} seeds to {:?}", written, out_dir);
+    Ok(failed)
+}
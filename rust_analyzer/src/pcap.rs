@@ -0,0 +1,545 @@
+//! 读取 pcap/pcapng 抓包，按会话提取客户端发往服务端的消息：
+//! TCP 按序列号重组客户端方向的字节流，服务端每回复一次（带负载的报文）就切分出一条消息；
+//! UDP 的每个客户端数据报即一条消息。
+
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+impl Transport {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tcp" => Some(Transport::Tcp),
+            "udp" => Some(Transport::Udp),
+            _ => None,
+        }
+    }
+}
+
+pub type Endpoint = (IpAddr, u16);
+
+/// 一个 TCP/UDP 报文
+struct Segment {
+    src: Endpoint,
+    dst: Endpoint,
+    transport: Transport,
+    seq: u32,
+    syn: bool,
+    payload: Vec<u8>,
+}
+
+/// 一个会话中客户端依次发出的消息
+pub struct Conversation {
+    pub client: Endpoint,
+    pub server: Endpoint,
+    pub messages: Vec<Vec<u8>>,
+}
+
+impl fmt::Display for Conversation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{} -> {}:{}", self.client.0, self.client.1, self.server.0, self.server.1)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// 按抓包文件的字节序读取整数
+#[derive(Clone, Copy)]
+struct Endian(bool); // true 为大端
+
+impl Endian {
+    fn u16(self, b: &[u8]) -> u16 {
+        let b = b[..2].try_into().unwrap();
+        if self.0 { u16::from_be_bytes(b) } else { u16::from_le_bytes(b) }
+    }
+
+    fn u32(self, b: &[u8]) -> u32 {
+        let b = b[..4].try_into().unwrap();
+        if self.0 { u32::from_be_bytes(b) } else { u32::from_le_bytes(b) }
+    }
+}
+
+fn be16(b: &[u8]) -> u16 {
+    u16::from_be_bytes([b[0], b[1]])
+}
+
+fn be32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+/// 抓包中的一帧：(链路层类型, 帧数据)
+type Frame<'a> = (u32, &'a [u8]);
+
+fn pcap_frames(data: &[u8]) -> io::Result<Vec<Frame<'_>>> {
+    if data.len() < 24 {
+        return Err(invalid("truncated pcap header"));
+    }
+    let endian = match data[..4] {
+        [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => Endian(false),
+        [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => Endian(true),
+        _ => return Err(invalid("not a pcap file")),
+    };
+    let linktype = endian.u32(&data[20..]);
+    let mut frames = vec![];
+    let mut pos = 24;
+    while pos + 16 <= data.len() {
+        let caplen = endian.u32(&data[pos + 8..]) as usize;
+        let start = pos + 16;
+        if start + caplen > data.len() {
+            break; // 截断的最后一帧
+        }
+        frames.push((linktype, &data[start..start + caplen]));
+        pos = start + caplen;
+    }
+    Ok(frames)
+}
+
+fn pcapng_frames(data: &[u8]) -> io::Result<Vec<Frame<'_>>> {
+    let mut frames = vec![];
+    let mut endian = Endian(false);
+    let mut linktypes: Vec<u32> = vec![];
+    let mut pos = 0;
+    while pos + 12 <= data.len() {
+        // 块类型 0x0A0D0D0A 是回文，字节序由其后的 byte-order magic 决定
+        if data[pos..pos + 4] == [0x0a, 0x0d, 0x0d, 0x0a] {
+            endian = match data[pos + 8..pos + 12] {
+                [0x4d, 0x3c, 0x2b, 0x1a] => Endian(false),
+                [0x1a, 0x2b, 0x3c, 0x4d] => Endian(true),
+                _ => return Err(invalid("bad pcapng byte-order magic")),
+            };
+            linktypes.clear(); // 新的 section 重新编号接口
+        }
+        let block_type = endian.u32(&data[pos..]);
+        let block_len = endian.u32(&data[pos + 4..]) as usize;
+        if block_len < 12 || pos + block_len > data.len() {
+            break;
+        }
+        let body = &data[pos + 8..pos + block_len - 4];
+        match block_type {
+            // Interface Description Block
+            1 if body.len() >= 2 => linktypes.push(endian.u16(body) as u32),
+            // Enhanced Packet Block
+            6 if body.len() >= 20 => {
+                let iface = endian.u32(body) as usize;
+                let caplen = endian.u32(&body[12..]) as usize;
+                if let (Some(&linktype), Some(frame)) = (linktypes.get(iface), body.get(20..20 + caplen)) {
+                    frames.push((linktype, frame));
+                }
+            }
+            // Simple Packet Block（总是第一个接口）
+            3 if body.len() >= 4 => {
+                let len = std::cmp::min(endian.u32(body) as usize, body.len() - 4);
+                if let Some(&linktype) = linktypes.first() {
+                    frames.push((linktype, &body[4..4 + len]));
+                }
+            }
+            // 已废弃的 Packet Block
+            2 if body.len() >= 20 => {
+                let iface = endian.u16(body) as usize;
+                let caplen = endian.u32(&body[12..]) as usize;
+                if let (Some(&linktype), Some(frame)) = (linktypes.get(iface), body.get(20..20 + caplen)) {
+                    frames.push((linktype, frame));
+                }
+            }
+            _ => {}
+        }
+        pos += block_len;
+    }
+    Ok(frames)
+}
+
+/// 去掉链路层头部，返回 IP 包
+fn ip_packet(linktype: u32, frame: &[u8]) -> Option<&[u8]> {
+    let (ethertype, offset) = match linktype {
+        // Ethernet，跳过 VLAN 标签
+        1 => {
+            let mut offset = 12;
+            let mut ethertype = be16(frame.get(offset..offset + 2)?);
+            while ethertype == 0x8100 || ethertype == 0x88a8 {
+                offset += 4;
+                ethertype = be16(frame.get(offset..offset + 2)?);
+            }
+            (ethertype, offset + 2)
+        }
+        // Linux cooked capture v1/v2
+        113 => (be16(frame.get(14..16)?), 16),
+        276 => (be16(frame.get(0..2)?), 20),
+        // BSD loopback：4 字节的地址族（主机字节序）
+        0 => {
+            let family = u32::from_le_bytes(frame.get(0..4)?.try_into().ok()?);
+            let family = if family > 0xffff { family.swap_bytes() } else { family };
+            (if family == 2 { 0x0800 } else { 0x86dd }, 4)
+        }
+        // 裸 IP
+        12 | 14 | 101 | 228 | 229 => (if frame.first()? >> 4 == 6 { 0x86dd } else { 0x0800 }, 0),
+        _ => return None,
+    };
+    match ethertype {
+        0x0800 | 0x86dd => frame.get(offset..),
+        _ => None,
+    }
+}
+
+/// 解析 IP 头，返回 (源地址, 目的地址, 上层协议号, 上层数据)；分片的包被忽略
+fn ip_payload(packet: &[u8]) -> Option<(IpAddr, IpAddr, u8, &[u8])> {
+    match packet.first()? >> 4 {
+        4 => {
+            let ihl = ((packet[0] & 0x0f) as usize) * 4;
+            let total = be16(packet.get(2..4)?) as usize;
+            let frag = be16(packet.get(6..8)?);
+            if frag & 0x3fff != 0 {
+                return None;
+            }
+            let src: [u8; 4] = packet.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = packet.get(16..20)?.try_into().ok()?;
+            let end = std::cmp::min(total, packet.len());
+            Some((Ipv4Addr::from(src).into(), Ipv4Addr::from(dst).into(), packet[9], packet.get(ihl..end)?))
+        }
+        6 => {
+            let len = be16(packet.get(4..6)?) as usize;
+            let src: [u8; 16] = packet.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = packet.get(24..40)?.try_into().ok()?;
+            let end = std::cmp::min(40 + len, packet.len());
+            let mut next = packet[6];
+            let mut offset = 40;
+            // hop-by-hop / routing / destination options 扩展头
+            while next == 0 || next == 43 || next == 60 {
+                let header = packet.get(offset..offset + 2)?;
+                next = header[0];
+                offset += (header[1] as usize + 1) * 8;
+            }
+            if next == 44 {
+                return None;
+            }
+            Some((Ipv6Addr::from(src).into(), Ipv6Addr::from(dst).into(), next, packet.get(offset..end)?))
+        }
+        _ => None,
+    }
+}
+
+fn segment(linktype: u32, frame: &[u8]) -> Option<Segment> {
+    let (src, dst, proto, data) = ip_payload(ip_packet(linktype, frame)?)?;
+    match proto {
+        6 => {
+            let offset = ((data.get(12)? >> 4) as usize) * 4;
+            Some(Segment {
+                src: (src, be16(data.get(0..2)?)),
+                dst: (dst, be16(data.get(2..4)?)),
+                transport: Transport::Tcp,
+                seq: be32(data.get(4..8)?),
+                syn: data.get(13)? & 0x02 != 0,
+                payload: data.get(offset..)?.to_vec(),
+            })
+        }
+        17 => {
+            let len = be16(data.get(4..6)?) as usize;
+            let end = std::cmp::min(std::cmp::max(len, 8), data.len());
+            Some(Segment {
+                src: (src, be16(data.get(0..2)?)),
+                dst: (dst, be16(data.get(2..4)?)),
+                transport: Transport::Udp,
+                seq: 0,
+                syn: false,
+                payload: data.get(8..end)?.to_vec(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// 读取抓包中的所有 TCP/UDP 报文（按抓包顺序）
+fn read_segments(data: &[u8]) -> io::Result<Vec<Segment>> {
+    let frames = if data.starts_with(&[0x0a, 0x0d, 0x0d, 0x0a]) { pcapng_frames(data)? } else { pcap_frames(data)? };
+    Ok(frames.into_iter().filter_map(|(linktype, frame)| segment(linktype, frame)).collect())
+}
+
+/// 重组中的一个会话
+struct Stream {
+    conversation: Conversation,
+    isn: Option<u32>,                   // 客户端方向的初始序列号
+    next: u64,                          // 下一个期望的相对序列号
+    pending: BTreeMap<u64, Vec<u8>>,    // 乱序到达的数据
+    current: Vec<u8>,                   // 尚未被服务端回复切分的消息
+}
+
+impl Stream {
+    fn new(client: Endpoint, server: Endpoint) -> Self {
+        Self {
+            conversation: Conversation { client, server, messages: vec![] },
+            isn: None,
+            next: 0,
+            pending: BTreeMap::new(),
+            current: vec![],
+        }
+    }
+
+    fn has_data(&self) -> bool {
+        !self.conversation.messages.is_empty() || !self.current.is_empty() || !self.pending.is_empty()
+    }
+
+    fn client_data(&mut self, seq: u32, payload: Vec<u8>) {
+        let isn = *self.isn.get_or_insert(seq);
+        let rel = seq.wrapping_sub(isn) as u64;
+        if rel + payload.len() as u64 <= self.next {
+            return; // 重传
+        }
+        self.pending.entry(rel).or_insert(payload);
+        while let Some((&rel, _)) = self.pending.iter().next() {
+            if rel > self.next {
+                break;
+            }
+            let data = self.pending.remove(&rel).unwrap();
+            let skip = (self.next - rel) as usize;
+            if skip < data.len() {
+                self.current.extend_from_slice(&data[skip..]);
+                self.next = rel + data.len() as u64;
+            }
+        }
+    }
+
+    fn cut(&mut self) {
+        if !self.current.is_empty() {
+            let message = std::mem::take(&mut self.current);
+            self.conversation.messages.push(message);
+        }
+    }
+
+    fn finish(mut self) -> Conversation {
+        // 缺失数据之后的部分仍按顺序拼接
+        for (_, data) in std::mem::take(&mut self.pending) {
+            self.current.extend_from_slice(&data);
+        }
+        self.cut();
+        self.conversation
+    }
+}
+
+/// 提取服务端端口为 port 的所有会话（按会话开始的顺序），没有客户端消息的会话被忽略
+pub fn conversations(data: &[u8], transport: Transport, port: u16) -> io::Result<Vec<Conversation>> {
+    let mut streams: Vec<Stream> = vec![];
+    let mut open: HashMap<(Endpoint, Endpoint), usize> = HashMap::new();
+    for seg in read_segments(data)?.into_iter().filter(|s| s.transport == transport) {
+        let (client, server, from_client) = if seg.dst.1 == port {
+            (seg.src, seg.dst, true)
+        } else if seg.src.1 == port {
+            (seg.dst, seg.src, false)
+        } else {
+            continue;
+        };
+        let key = (client, server);
+        // 同一四元组上新的连接（客户端重新 SYN）开始新的会话
+        let reopen = from_client && seg.syn && open.get(&key).map(|&i| streams[i].has_data()).unwrap_or(false);
+        if reopen || !open.contains_key(&key) {
+            open.insert(key, streams.len());
+            streams.push(Stream::new(client, server));
+        }
+        let stream = &mut streams[open[&key]];
+        match (transport, from_client) {
+            (Transport::Udp, true) => {
+                if !seg.payload.is_empty() {
+                    stream.conversation.messages.push(seg.payload);
+                }
+            }
+            (Transport::Tcp, true) => {
+                if seg.syn {
+                    stream.isn = Some(seg.seq.wrapping_add(1));
+                    stream.next = 0;
+                } else if !seg.payload.is_empty() {
+                    stream.client_data(seg.seq, seg.payload);
+                }
+            }
+            (_, false) => {
+                if !seg.payload.is_empty() {
+                    stream.cut();
+                }
+            }
+        }
+    }
+    Ok(streams
+        .into_iter()
+        .map(Stream::finish)
+        .filter(|c| !c.messages.is_empty())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 1];
+    const SERVER: [u8; 4] = [10, 0, 0, 2];
+    const SYN: u8 = 0x02;
+    const ACK: u8 = 0x10;
+
+    fn ipv4(src: [u8; 4], dst: [u8; 4], proto: u8, l4: &[u8]) -> Vec<u8> {
+        let mut p = vec![0x45, 0];
+        p.extend_from_slice(&((20 + l4.len()) as u16).to_be_bytes());
+        p.extend_from_slice(&[0, 0, 0x40, 0, 64, proto, 0, 0]);
+        p.extend_from_slice(&src);
+        p.extend_from_slice(&dst);
+        p.extend_from_slice(l4);
+        p
+    }
+
+    fn ipv6(src: [u8; 16], dst: [u8; 16], proto: u8, l4: &[u8]) -> Vec<u8> {
+        let mut p = vec![0x60, 0, 0, 0];
+        p.extend_from_slice(&(l4.len() as u16).to_be_bytes());
+        p.extend_from_slice(&[proto, 64]);
+        p.extend_from_slice(&src);
+        p.extend_from_slice(&dst);
+        p.extend_from_slice(l4);
+        p
+    }
+
+    fn tcp(sport: u16, dport: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut s = vec![];
+        s.extend_from_slice(&sport.to_be_bytes());
+        s.extend_from_slice(&dport.to_be_bytes());
+        s.extend_from_slice(&seq.to_be_bytes());
+        s.extend_from_slice(&[0, 0, 0, 0, 0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        s.extend_from_slice(payload);
+        s
+    }
+
+    fn udp(sport: u16, dport: u16, payload: &[u8]) -> Vec<u8> {
+        let mut d = vec![];
+        d.extend_from_slice(&sport.to_be_bytes());
+        d.extend_from_slice(&dport.to_be_bytes());
+        d.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+        d.extend_from_slice(&[0, 0]);
+        d.extend_from_slice(payload);
+        d
+    }
+
+    /// Ethernet 帧，vlan 时带一个 802.1Q 标签
+    fn ether(ethertype: u16, ip: &[u8], vlan: bool) -> Vec<u8> {
+        let mut f = vec![0; 12];
+        if vlan {
+            f.extend_from_slice(&[0x81, 0x00, 0x00, 0x07]);
+        }
+        f.extend_from_slice(&ethertype.to_be_bytes());
+        f.extend_from_slice(ip);
+        f
+    }
+
+    fn tcp_frame(from_client: bool, seq: u32, flags: u8, payload: &[u8], vlan: bool) -> Vec<u8> {
+        let (src, dst, sport, dport) = if from_client { (CLIENT, SERVER, 40000, 8080) } else { (SERVER, CLIENT, 8080, 40000) };
+        ether(0x0800, &ipv4(src, dst, 6, &tcp(sport, dport, seq, flags, payload)), vlan)
+    }
+
+    /// 小端 pcap，链路层为 Ethernet
+    fn pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = vec![0xd4, 0xc3, 0xb2, 0xa1, 2, 0, 4, 0];
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&65535u32.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        for frame in frames.iter() {
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            data.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    fn be_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let len = (12 + body.len()) as u32;
+        let mut b = block_type.to_be_bytes().to_vec();
+        b.extend_from_slice(&len.to_be_bytes());
+        b.extend_from_slice(body);
+        b.extend_from_slice(&len.to_be_bytes());
+        b
+    }
+
+    /// 大端 pcapng：SHB、一个 Ethernet 接口，每帧一个 EPB
+    fn pcapng_be(frames: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = be_block(0x0a0d_0d0a, &[0x1a, 0x2b, 0x3c, 0x4d, 0, 1, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        data.extend(be_block(1, &[0, 1, 0, 0, 0, 0, 0xff, 0xff]));
+        for frame in frames.iter() {
+            let mut body = vec![0; 12];
+            body.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            body.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            body.extend_from_slice(frame);
+            body.resize(body.len().div_ceil(4) * 4, 0);
+            data.extend(be_block(6, &body));
+        }
+        data
+    }
+
+    fn messages(conversation: &Conversation) -> Vec<&[u8]> {
+        conversation.messages.iter().map(|m| m.as_slice()).collect()
+    }
+
+    #[test]
+    fn test_tcp_reassembly() {
+        let frames = vec![
+            tcp_frame(true, 100, SYN, b"", false),
+            tcp_frame(false, 900, SYN | ACK, b"", false),
+            tcp_frame(true, 101, ACK, b"HELO", false),
+            tcp_frame(false, 901, ACK, b"OK", false),
+            // 乱序：CD 先于 AB 到达，AB 随后被重传一次
+            tcp_frame(true, 107, ACK, b"CD", true),
+            tcp_frame(true, 105, ACK, b"AB", false),
+            tcp_frame(true, 105, ACK, b"AB", false),
+            tcp_frame(false, 903, ACK, b"OK", false),
+            // 同一四元组上重新 SYN，开始新的会话
+            tcp_frame(true, 500, SYN, b"", false),
+            tcp_frame(true, 501, ACK, b"BYE", false),
+        ];
+        let found = conversations(&pcap(&frames), Transport::Tcp, 8080).unwrap();
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].to_string(), "10.0.0.1:40000 -> 10.0.0.2:8080");
+        assert_eq!(messages(&found[0]), vec![&b"HELO"[..], b"ABCD"]);
+        assert_eq!(messages(&found[1]), vec![&b"BYE"[..]]);
+        assert!(conversations(&pcap(&frames), Transport::Udp, 8080).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_udp_conversation() {
+        let frames = vec![
+            ether(0x0800, &ipv4(CLIENT, SERVER, 17, &udp(5000, 53, b"q1")), false),
+            ether(0x0800, &ipv4(SERVER, CLIENT, 17, &udp(53, 5000, b"r1")), false),
+            ether(0x0800, &ipv4(CLIENT, SERVER, 17, &udp(5000, 123, b"other")), false),
+            ether(0x0800, &ipv4(CLIENT, SERVER, 17, &udp(5000, 53, b"")), false),
+            ether(0x0800, &ipv4(CLIENT, SERVER, 17, &udp(5000, 53, b"q2")), false),
+        ];
+        let found = conversations(&pcap(&frames), Transport::Udp, 53).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].client, (IpAddr::from(CLIENT), 5000));
+        assert_eq!(messages(&found[0]), vec![&b"q1"[..], b"q2"]);
+    }
+
+    #[test]
+    fn test_big_endian_pcapng() {
+        let mut client = [0u8; 16];
+        client[15] = 1;
+        let mut server = [0u8; 16];
+        server[15] = 2;
+        // 奇数长度的帧，检查 EPB 的填充
+        let frames = vec![
+            ether(0x86dd, &ipv6(client, server, 17, &udp(6000, 53, b"ping!")), false),
+            ether(0x86dd, &ipv6(server, client, 17, &udp(53, 6000, b"pong")), false),
+            ether(0x86dd, &ipv6(client, server, 17, &udp(6000, 53, b"x")), false),
+        ];
+        let found = conversations(&pcapng_be(&frames), Transport::Udp, 53).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].to_string(), "::1:6000 -> ::2:53");
+        assert_eq!(messages(&found[0]), vec![&b"ping!"[..], b"x"]);
+    }
+
+    #[test]
+    fn test_not_a_capture() {
+        assert!(conversations(&[0u8; 32], Transport::Tcp, 80).is_err());
+        assert!(conversations(&[0u8; 4], Transport::Tcp, 80).is_err());
+    }
+}
@@ -5,6 +5,8 @@ use crate::config::FuzzerConfig;
 use crate::fuzz_runner::ExitReason;
 // use crate::structured_fuzzer::custom_dict::CustomDict;
 use crate::input::{Input, InputID};
+use crate::queue_store::{self, InputRecord, QueueState, QUEUE_FORMAT_VERSION};
+use crate::triage::CrashTriage;
 use crate::structured_fuzzer::graph_mutator::graph_storage::{GraphStorage, VecGraph};
 use crate::structured_fuzzer::mutator::InputQueue;
 use crate::structured_fuzzer::random::distributions::Distributions;
@@ -13,6 +15,7 @@ use crate::structured_fuzzer::GraphSpec;
 // use crate::snap_tree::SnapTree;
 use std::collections::HashMap;
 
+use std::io;
 use std::sync::Arc;
 use std::sync::RwLock;
 
@@ -20,6 +23,11 @@ use std::sync::RwLock;
 pub struct QueueStats {
     num_inputs: usize,
     favqueue: Vec<usize>,
+    total_execs: u64,
+    runtime: f32,               // 秒
+    execs_per_sec: f32,
+    bitmap_bits: usize,         // normal bitmap 中命中的项数
+    crash_buckets: usize,
 }
 
 pub struct QueueData {
@@ -42,6 +50,7 @@ pub struct Queue {
     start_time: std::time::Instant,
     total_execs: Arc<RwLock<u64>>,
     data: Arc<RwLock<QueueData>>,           //队列智能指针
+    crashes: CrashTriage,                   //crash 分桶
 }
 
 impl<'a> InputQueue for Queue {
@@ -79,13 +88,103 @@ impl Queue {
                 bitmaps: BitmapHandler::new(config.bitmap_size),
                 next_input_id: 0,
             })),
+            crashes: CrashTriage::new(&config.workdir_path),
         };
     }
 
-    // pub fn update_total_execs(&self, update: u64){
-    //     let mut w = self.total_execs.write().unwrap();
-    //     *w += update; 
-    // }
+    /// 把整个队列（输入、最短输入表、最爱队列与全局bitmap）写入 workdir/queue_state.msgp
+    pub fn save(&self, spec: &GraphSpec) -> io::Result<()> {
+        let total_execs = self.get_total_execs();
+        let runtime = self.get_runtime_as_secs_f32();
+        let state = {
+            let data = self.data.read().unwrap();
+            QueueState {
+                version: QUEUE_FORMAT_VERSION,
+                spec_checksum: spec.checksum,
+                total_execs,
+                runtime,
+                next_input_id: data.next_input_id,
+                inputs: data.inputs.iter().map(|input| InputRecord::new(&input.read().unwrap())).collect(),
+                input_to_iters_no_finds: data.input_to_iters_no_finds.clone(),
+                input_selected_times: data.input_selected_times.clone(),
+                bitmap_index_to_min_example: data
+                    .bitmap_index_to_min_example
+                    .iter()
+                    .map(|(index, id)| (*index, id.as_usize()))
+                    .collect(),
+                bitmap_bits: data.bitmap_bits.clone(),
+                favqueue: data.favqueue.iter().map(|id| id.as_usize()).collect(),
+                bitmaps: data.bitmaps.state(),
+            }
+        };
+        queue_store::write(&queue_store::state_path(&self.workdir), &state)
+    }
+
+    /// 从 workdir/queue_state.msgp 恢复队列，版本或 spec 不一致、文件损坏时返回错误
+    pub fn load(config: &FuzzerConfig, spec: &GraphSpec) -> io::Result<Self> {
+        let state = queue_store::read(&queue_store::state_path(&config.workdir_path), spec.checksum)?;
+        let num_inputs = state.inputs.len();
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("inconsistent queue state: {}", what));
+        if state.input_to_iters_no_finds.len() != num_inputs || state.input_selected_times.len() != num_inputs {
+            return Err(invalid("per-input counters"));
+        }
+        if state.bitmap_index_to_min_example.iter().any(|(_, id)| *id >= num_inputs) || state.favqueue.iter().any(|id| *id >= num_inputs) {
+            return Err(invalid("input ids"));
+        }
+        let mut inputs = vec![];
+        for (id, record) in state.inputs.into_iter().enumerate() {
+            inputs.push(Arc::new(RwLock::new(record.into_input(id, state.bitmaps.size)?)));
+        }
+        let runtime = std::time::Duration::from_secs_f32(state.runtime);
+        let now = std::time::Instant::now();
+        Ok(Self {
+            workdir: config.workdir_path.clone(),
+            start_time: now.checked_sub(runtime).unwrap_or(now),
+            total_execs: Arc::new(RwLock::new(state.total_execs)),
+            data: Arc::new(RwLock::new(QueueData {
+                bitmap_index_to_min_example: state
+                    .bitmap_index_to_min_example
+                    .into_iter()
+                    .map(|(index, id)| (index, InputID::new(id)))
+                    .collect(),
+                inputs,
+                favqueue: state.favqueue.into_iter().map(InputID::new).collect(),
+                input_to_iters_no_finds: state.input_to_iters_no_finds,
+                input_selected_times: state.input_selected_times,
+                bitmap_bits: state.bitmap_bits,
+                bitmaps: BitmapHandler::from_state(&state.bitmaps),
+                next_input_id: state.next_input_id,
+            })),
+            crashes: CrashTriage::new(&config.workdir_path),
+        })
+    }
+
+    /// 续跑时使用：能读取已保存的队列时恢复之，否则新建空队列（之后重新导入种子）
+    pub fn load_or_new(config: &FuzzerConfig, spec: &GraphSpec) -> Self {
+        let path = queue_store::state_path(&config.workdir_path);
+        if !std::path::Path::new(&path).exists() {
+            return Self::new(config);
+        }
+        match Self::load(config, spec) {
+            Ok(queue) => {
+                println!("[!] restored {} inputs from {}", queue.len(), path);
+                queue
+            }
+            Err(e) => {
+                eprintln!("[!] ignoring queue state {}: {}", path, e);
+                Self::new(config)
+            }
+        }
+    }
+
+    pub fn update_total_execs(&self, update: u64){
+        let mut w = self.total_execs.write().unwrap();
+        *w += update; 
+    }
+
+    pub fn crashes(&self) -> &CrashTriage {
+        &self.crashes
+    }
 
     pub fn get_total_execs(&self) -> u64 {
         *self.total_execs.read().unwrap()
@@ -100,6 +199,8 @@ impl Queue {
         use std::fs::OpenOptions;
         use std::io::prelude::*;
         //读取数据
+        let total_execs = self.get_total_execs();
+        let runtime = self.get_runtime_as_secs_f32();
         let dat = self.data.read().unwrap();
         let ser = QueueStats {
             num_inputs: dat.inputs.len(),
@@ -108,6 +209,11 @@ impl Queue {
                 .iter()
                 .map(|id| id.as_usize())
                 .collect::<Vec<_>>(),
+            total_execs,
+            runtime,
+            execs_per_sec: total_execs as f32 / runtime,
+            bitmap_bits: dat.bitmaps.normal_bitmap().bits().iter().filter(|b| **b > 0).count(),
+            crash_buckets: self.crashes.len(),
         };
         //写入队列统计信息到queue_stats.msgp
         let mut file = File::create(format!("{}/queue_stats.msgp", &self.workdir)).unwrap();
@@ -349,6 +455,37 @@ impl Queue {
         }
     }
 
+    /// 选出下一个要变异的输入：优先从最爱队列中选（最爱队列为空时从全部输入中选），
+    /// 随机抽取两个候选，取连续无发现轮数较少者，相同时取被选中次数较少者
+    pub fn select_for_fuzzing(&self, dist: &Distributions) -> Option<Arc<RwLock<Input>>> {
+        let mut data = self.data.write().unwrap();
+        let candidates: Vec<usize> = if data.favqueue.is_empty() || dist.gen_range(0, 10) == 0 {
+            (0..data.inputs.len()).collect()
+        } else {
+            data.favqueue.iter().map(|id| id.as_usize()).collect()
+        };
+        if candidates.is_empty() {
+            return None;
+        }
+        let a = candidates[dist.gen_range(0, candidates.len())];
+        let b = candidates[dist.gen_range(0, candidates.len())];
+        let key = |i: usize| (data.input_to_iters_no_finds[i], data.input_selected_times[i]);
+        let id = if key(b) < key(a) { b } else { a };
+        data.input_selected_times[id] += 1;
+        Some(data.inputs[id].clone())
+    }
+
+    /// 记录对 id 的一轮变异：有新发现时清零连续无发现轮数，否则累加本轮执行次数
+    pub fn report_fuzz_round(&self, id: InputID, iters: usize, found: usize) {
+        let mut data = self.data.write().unwrap();
+        let no_finds = &mut data.input_to_iters_no_finds[id.as_usize()];
+        if found > 0 {
+            *no_finds = 0;
+        } else {
+            *no_finds += iters;
+        }
+    }
+
     //直接根据指定的id拿input
     pub fn schedule(&self, id:usize) -> Arc<RwLock<Input>> {
         self.data.read().unwrap().inputs[id].clone()
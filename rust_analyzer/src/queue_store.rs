@@ -0,0 +1,334 @@
+//! 队列的落盘格式：`workdir/queue_state.msgp` 保存所有输入（graph、bitmap、存储原因等）、
+//! 每个 bitmap 下标的最短输入、最爱队列以及全局 bitmap，重启后据此恢复队列而无需重新导入、执行种子。
+//! 格式带版本号，读取时版本或 spec checksum 不一致的文件会被拒绝。
+
+use crate::bitmap::{Bitmap, BitmapHandlerState, StorageReason};
+use crate::fuzz_runner::ExitReason;
+use crate::input::{Input, InputID};
+use crate::structured_fuzzer::graph_mutator::graph_storage::{GraphStorage, VecGraph};
+use crate::structured_fuzzer::mutator::{GenerateTail, MutationStrategy};
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io;
+use std::time::Duration;
+
+/// 格式变化时递增
+pub const QUEUE_FORMAT_VERSION: u32 = 1;
+
+pub fn state_path(workdir: &str) -> String {
+    format!("{}/queue_state.msgp", workdir)
+}
+
+/// ExitReason 的可序列化形式
+#[derive(Serialize, Deserialize)]
+pub enum ExitRecord {
+    Normal(i32),
+    Timeout,
+    Signaled(i32),
+    Crash(Vec<u8>),
+    Asan,
+    Stopped(i32),
+    FuzzerError,
+    InvalidWriteToPayload(Vec<u8>),
+}
+
+impl From<&ExitReason> for ExitRecord {
+    fn from(reason: &ExitReason) -> Self {
+        match reason {
+            ExitReason::Normal(code) => ExitRecord::Normal(*code),
+            ExitReason::Timeout => ExitRecord::Timeout,
+            ExitReason::Signaled(sig) => ExitRecord::Signaled(*sig),
+            ExitReason::Crash(desc) => ExitRecord::Crash(desc.clone()),
+            ExitReason::Asan => ExitRecord::Asan,
+            ExitReason::Stopped(sig) => ExitRecord::Stopped(*sig),
+            ExitReason::FuzzerError => ExitRecord::FuzzerError,
+            ExitReason::InvalidWriteToPayload(desc) => ExitRecord::InvalidWriteToPayload(desc.clone()),
+        }
+    }
+}
+
+impl From<ExitRecord> for ExitReason {
+    fn from(record: ExitRecord) -> Self {
+        match record {
+            ExitRecord::Normal(code) => ExitReason::Normal(code),
+            ExitRecord::Timeout => ExitReason::Timeout,
+            ExitRecord::Signaled(sig) => ExitReason::Signaled(sig),
+            ExitRecord::Crash(desc) => ExitReason::Crash(desc),
+            ExitRecord::Asan => ExitReason::Asan,
+            ExitRecord::Stopped(sig) => ExitReason::Stopped(sig),
+            ExitRecord::FuzzerError => ExitReason::FuzzerError,
+            ExitRecord::InvalidWriteToPayload(desc) => ExitReason::InvalidWriteToPayload(desc),
+        }
+    }
+}
+
+/// MutationStrategy 按名字保存，GenerateTail 额外保存其参数
+#[derive(Serialize, Deserialize)]
+pub struct StrategyRecord {
+    pub name: String,
+    pub generate_tail: Option<(usize, usize)>, // (drop_last, generate)
+}
+
+impl From<&MutationStrategy> for StrategyRecord {
+    fn from(strategy: &MutationStrategy) -> Self {
+        let generate_tail = match strategy {
+            MutationStrategy::GenerateTail(args) => Some((args.drop_last, args.generate)),
+            _ => None,
+        };
+        Self { name: strategy.name().to_string(), generate_tail }
+    }
+}
+
+impl StrategyRecord {
+    pub fn to_strategy(&self) -> io::Result<MutationStrategy> {
+        let strategy = match self.name.as_str() {
+            "generate_tail" => {
+                let (drop_last, generate) = self.generate_tail.unwrap_or((0, 0));
+                MutationStrategy::GenerateTail(GenerateTail { drop_last, generate })
+            }
+            "splice_random" => MutationStrategy::SpliceRandom,
+            "splice" => MutationStrategy::Splice,
+            "data_only" => MutationStrategy::DataOnly,
+            "generate" => MutationStrategy::Generate,
+            "repeat" => MutationStrategy::Repeat,
+            "minimize" => MutationStrategy::Minimize,
+            "minimize_split" => MutationStrategy::MinimizeSplit,
+            "import" => MutationStrategy::Import,
+            "seed_import" => MutationStrategy::SeedImport,
+            other => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown mutation strategy {}", other))),
+        };
+        Ok(strategy)
+    }
+}
+
+/// 队列中的一个输入
+#[derive(Serialize, Deserialize)]
+pub struct InputRecord {
+    pub ops: Vec<u16>,
+    pub data: Vec<u8>,
+    pub bitmap: Vec<(usize, u8)>, // 稀疏表示
+    pub exit_reason: ExitRecord,
+    pub ops_used: usize,
+    pub time: Duration,
+    pub storage_reasons: Vec<StorageReason>,
+    pub found_by: StrategyRecord,
+    pub parent_snapshot_position: usize,
+    pub parent_id: Option<usize>,
+}
+
+impl InputRecord {
+    pub fn new(input: &Input) -> Self {
+        Self {
+            ops: input.data.ops_as_slice().to_vec(),
+            data: input.data.data_as_slice().to_vec(),
+            bitmap: input.bitmap.sparse(),
+            exit_reason: ExitRecord::from(&input.exit_reason),
+            ops_used: input.ops_used,
+            time: input.time,
+            storage_reasons: input.storage_reasons.clone(),
+            found_by: StrategyRecord::from(&input.found_by),
+            parent_snapshot_position: input.parent_snapshot_position,
+            parent_id: if input.parent_id == InputID::invalid() { None } else { Some(input.parent_id.as_usize()) },
+        }
+    }
+
+    /// 恢复为编号 id 的输入
+    pub fn into_input(self, id: usize, bitmap_size: usize) -> io::Result<Input> {
+        let found_by = self.found_by.to_strategy()?;
+        let mut input = Input::new(
+            VecGraph::new(self.ops, self.data),
+            found_by,
+            self.storage_reasons,
+            Bitmap::from_sparse(bitmap_size, &self.bitmap),
+            self.exit_reason.into(),
+            self.ops_used,
+            self.time,
+        );
+        input.id = InputID::new(id);
+        input.parent_snapshot_position = self.parent_snapshot_position;
+        input.parent_id = self.parent_id.map(InputID::new).unwrap_or_else(InputID::invalid);
+        Ok(input)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct QueueState {
+    pub version: u32,
+    pub spec_checksum: u64,
+    pub total_execs: u64,
+    pub runtime: f32, // 已累计的运行时间（秒）
+    pub next_input_id: usize,
+    pub inputs: Vec<InputRecord>,
+    pub input_to_iters_no_finds: Vec<usize>,
+    pub input_selected_times: Vec<usize>,
+    pub bitmap_index_to_min_example: Vec<(usize, usize)>,
+    pub bitmap_bits: Vec<usize>,
+    pub favqueue: Vec<usize>,
+    pub bitmaps: BitmapHandlerState,
+}
+
+/// 先写临时文件再 rename，避免中途退出留下半个文件
+pub fn write(path: &str, state: &QueueState) -> io::Result<()> {
+    let tmp = format!("{}.tmp", path);
+    {
+        let mut file = File::create(&tmp)?;
+        rmp_serde::encode::write_named(&mut file, state).map_err(io::Error::other)?;
+    }
+    fs::rename(&tmp, path)
+}
+
+/// 读取并检查版本与 spec checksum
+pub fn read(path: &str, spec_checksum: u64) -> io::Result<QueueState> {
+    let file = File::open(path)?;
+    let state: QueueState = rmp_serde::decode::from_read(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if state.version != QUEUE_FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("queue format version {} (expected {})", state.version, QUEUE_FORMAT_VERSION),
+        ));
+    }
+    if state.spec_checksum != spec_checksum {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("spec checksum mismatch ({:x} != {:x})", state.spec_checksum, spec_checksum),
+        ));
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FuzzerConfig, SnapshotPlacement};
+    use crate::queue::Queue;
+    use crate::structured_fuzzer::graph_mutator::spec::GraphSpec;
+
+    const BITMAP_SIZE: usize = 64;
+
+    fn spec(checksum: u64) -> GraphSpec {
+        let mut spec = GraphSpec::new();
+        spec.node_type("op", None, vec![], vec![], vec![]);
+        spec.checksum = checksum;
+        spec
+    }
+
+    fn config(name: &str) -> FuzzerConfig {
+        let workdir = std::env::temp_dir().join(format!("queue_store_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&workdir);
+        fs::create_dir_all(&workdir).unwrap();
+        FuzzerConfig {
+            spec_path: String::new(),
+            workdir_path: workdir.to_str().unwrap().to_string(),
+            bitmap_size: BITMAP_SIZE,
+            mem_limit: 0,
+            time_limit: Duration::from_millis(100),
+            target_binary: None,
+            threads: 1,
+            thread_id: 0,
+            cpu_pin_start_at: 0,
+            seed_path: None,
+            dict: vec![],
+            snapshot_placement: SnapshotPlacement::None,
+            dump_python_code_for_inputs: None,
+            exit_after_first_crash: false,
+            calibration_operators: vec![],
+            record_edge_deltas: false,
+            minimize_seeds: false,
+            calibration_sampling: "all".to_string(),
+            calibration_seq_time: None,
+            calibration_seq_execs: None,
+        }
+    }
+
+    /// 执行 edges 对应的 bitmap，新覆盖时加入队列
+    fn add(queue: &mut Queue, spec: &GraphSpec, nodes: usize, edges: &[usize], reason: ExitReason, strategy: MutationStrategy) {
+        let mut bitmap = vec![0u8; BITMAP_SIZE];
+        for &e in edges.iter() {
+            bitmap[e] = 1;
+        }
+        let reasons = queue.check_new_bytes(&bitmap, &reason, strategy).unwrap();
+        let data = VecGraph::new(vec![0; nodes], vec![]);
+        let input = Input::new(data, strategy, reasons, Bitmap::new_from_buffer(&bitmap), reason, nodes, Duration::from_millis(3));
+        queue.add(input, spec).unwrap();
+    }
+
+    fn filled_queue(config: &FuzzerConfig, spec: &GraphSpec) -> Queue {
+        let mut queue = Queue::new(config);
+        add(&mut queue, spec, 3, &[1, 2], ExitReason::Normal(0), MutationStrategy::SeedImport);
+        let tail = MutationStrategy::GenerateTail(GenerateTail { drop_last: 1, generate: 2 });
+        add(&mut queue, spec, 2, &[1, 5], ExitReason::Crash(b"boom".to_vec()), tail);
+        queue.update_total_execs(42);
+        queue
+    }
+
+    #[test]
+    fn test_queue_round_trip() {
+        let (config, spec) = (config("round_trip"), spec(0x51));
+        let queue = filled_queue(&config, &spec);
+        queue.save(&spec).unwrap();
+        assert!(!std::path::Path::new(&format!("{}.tmp", state_path(&config.workdir_path))).exists());
+
+        let loaded = Queue::load(&config, &spec).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.get_total_execs(), 42);
+        for id in 0..2 {
+            let (a, b) = (queue.schedule(id), loaded.schedule(id));
+            let (a, b) = (a.read().unwrap(), b.read().unwrap());
+            assert_eq!(b.id, InputID::new(id));
+            assert_eq!(a.data.ops_as_slice(), b.data.ops_as_slice());
+            assert_eq!(a.bitmap.sparse(), b.bitmap.sparse());
+            assert_eq!(a.exit_reason, b.exit_reason);
+            assert_eq!(a.found_by, b.found_by);
+            assert_eq!(a.storage_reasons, b.storage_reasons);
+            assert_eq!((a.ops_used, a.time), (b.ops_used, b.time));
+        }
+        // 全局 bitmap 也被恢复：已见过的覆盖不再是新覆盖
+        let mut loaded = loaded;
+        let mut bitmap = vec![0u8; BITMAP_SIZE];
+        bitmap[5] = 1;
+        assert!(loaded.check_new_bytes(&bitmap, &ExitReason::Crash(vec![]), MutationStrategy::Splice).is_none());
+        let _ = fs::remove_dir_all(&config.workdir_path);
+    }
+
+    #[test]
+    fn test_version_mismatch() {
+        let (config, spec) = (config("version"), spec(0x51));
+        filled_queue(&config, &spec).save(&spec).unwrap();
+        let path = state_path(&config.workdir_path);
+        let mut state = read(&path, spec.checksum).unwrap();
+        state.version = QUEUE_FORMAT_VERSION + 1;
+        write(&path, &state).unwrap();
+
+        let err = read(&path, spec.checksum).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("version"));
+        assert!(Queue::load(&config, &spec).is_err());
+        // 续跑时忽略无法使用的队列，重新开始
+        assert_eq!(Queue::load_or_new(&config, &spec).len(), 0);
+        let _ = fs::remove_dir_all(&config.workdir_path);
+    }
+
+    #[test]
+    fn test_spec_checksum_mismatch() {
+        let (config, spec) = (config("checksum"), spec(0x51));
+        filled_queue(&config, &spec).save(&spec).unwrap();
+        let other = self::spec(0x52);
+        let err = read(&state_path(&config.workdir_path), other.checksum).err().unwrap();
+        assert!(err.to_string().contains("checksum"));
+        assert!(Queue::load(&config, &other).is_err());
+        assert_eq!(Queue::load_or_new(&config, &other).len(), 0);
+        assert_eq!(Queue::load_or_new(&config, &spec).len(), 2);
+        let _ = fs::remove_dir_all(&config.workdir_path);
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_state() {
+        let (config, spec) = (config("missing"), spec(0x51));
+        assert_eq!(Queue::load_or_new(&config, &spec).len(), 0);
+        fs::write(state_path(&config.workdir_path), b"not msgpack").unwrap();
+        assert!(Queue::load(&config, &spec).is_err());
+        assert_eq!(Queue::load_or_new(&config, &spec).len(), 0);
+        let _ = fs::remove_dir_all(&config.workdir_path);
+    }
+}
@@ -0,0 +1,243 @@
+//! replay 子命令：在一个 qemu 实例中依次执行 .bin 测试用例，报告退出原因、
+//! 覆盖率与 ijon 哈希、aux buffer 的 misc 以及 hprintf 输出，可重复执行检查稳定性，
+//! 也可逐个节点执行观察覆盖率的增长。
+
+use crate::analyzer::GetStructStorage;
+use crate::class_registry;
+use crate::fuzz_runner::{ExitReason, FuzzRunner, QemuProcess};
+use crate::hash;
+use crate::seed_import;
+use crate::structured_fuzzer::graph_mutator::graph_storage::VecGraph;
+use crate::structured_fuzzer::graph_mutator::spec::GraphSpec;
+use crate::structured_fuzzer::GraphStorage;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// .bin 文件头：checksum、num_ops、num_data、op_offset、data_offset 各 8 字节
+pub const BIN_HEADER_LEN: usize = 40;
+
+/// 读取 .bin 测试用例。先检查文件头是否自洽以及与 spec 的 checksum，
+/// 避免 `VecGraph::new_from_bin_file` 在输入被截断或不匹配时直接 panic
+pub fn load_bin(path: &Path, spec: &GraphSpec) -> io::Result<VecGraph> {
+    let checksum = seed_import::bin_checksum(&fs::read(path)?)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated or malformed .bin file"))?;
+    if checksum != spec.checksum {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("spec checksum mismatch ({:x} != {:x})", checksum, spec.checksum),
+        ));
+    }
+    Ok(VecGraph::new_from_bin_file(path.to_str().unwrap(), spec))
+}
+
+/// 目标为文件时原样返回，为目录时返回其中所有 .bin 文件（按文件名排序）
+pub fn collect_bins(target: &Path) -> io::Result<Vec<PathBuf>> {
+    if !target.is_dir() {
+        return Ok(vec![target.to_path_buf()]);
+    }
+    let mut files = vec![];
+    for entry in fs::read_dir(target)? {
+        let file = entry?.path();
+        if file.extension().map(|e| e == "bin").unwrap_or(false) {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 一次执行是否值得调用方关注（非正常退出）
+pub fn is_finding(reason: &ExitReason) -> bool {
+    !reason.is_normal()
+}
+
+pub struct ReplayOptions {
+    pub repeat: usize,     // 每个用例执行的次数，>1 时报告稳定性
+    pub per_packet: bool,  // 逐个追加节点执行，观察覆盖率的增长
+    pub quiet: bool,       // 只报告非正常退出与汇总
+}
+
+/// 单次执行的完整报告
+pub struct ExecReport {
+    pub reason: ExitReason,
+    pub ops_used: usize,
+    pub edges: usize,       // run_bitmap 中非零项个数
+    pub bitmap_hash: u64,
+    pub ijon_hash: u64,
+    pub misc: String,       // aux buffer 的 misc 区（crash 时为崩溃描述）
+    pub hprintf: String,    // 本次执行期间目标通过 hprintf 输出的内容
+    pub run_bitmap: Vec<u8>,
+}
+
+/// 执行 graph 并收集报告
+pub fn execute(runner: &mut QemuProcess, graph: &VecGraph, spec: &GraphSpec) -> io::Result<ExecReport> {
+    {
+        let mut storage = runner.get_struct_storage(spec.checksum);
+        storage.copy_from(graph);
+    }
+    let info = runner.run_test().map_err(|e| io::Error::other(e.to_string()))?;
+    let run_bitmap = runner.bitmap_buffer().to_vec();
+    let ijon = runner.ijon_max_buffer();
+    Ok(ExecReport {
+        reason: info.exitreason,
+        ops_used: info.ops_used as usize,
+        edges: run_bitmap.iter().filter(|&&b| b != 0).count(),
+        bitmap_hash: hash::hash64(&run_bitmap, run_bitmap.len()),
+        ijon_hash: hash::hash64(ijon, ijon.len()),
+        misc: runner.aux.misc.as_string(),
+        hprintf: info.hprintf.trim_end().to_string(),
+        run_bitmap,
+    })
+}
+
+pub fn reason_name(reason: &ExitReason) -> String {
+    match reason {
+        ExitReason::FuzzerError => "fuzzer_error".to_string(),
+        ExitReason::Normal(code) => format!("normal({})", code),
+        ExitReason::Signaled(sig) => format!("signal({})", sig),
+        other => other.name().to_string(),
+    }
+}
+
+impl ExecReport {
+    fn print(&self, node_len: usize) {
+        println!("  exit reason : {}", reason_name(&self.reason));
+        println!("  ops used    : {}/{}", self.ops_used, node_len);
+        println!("  edges       : {} (hash {:016x})", self.edges, self.bitmap_hash);
+        println!("  ijon hash   : {:016x}", self.ijon_hash);
+        if !self.misc.is_empty() {
+            println!("  misc        : {}", self.misc);
+        }
+        if !self.hprintf.is_empty() {
+            println!("  hprintf     :");
+            for line in self.hprintf.lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "{} ops={} edges={} bitmap={:016x} ijon={:016x}",
+            reason_name(&self.reason),
+            self.ops_used,
+            self.edges,
+            self.bitmap_hash,
+            self.ijon_hash
+        )
+    }
+}
+
+/// 重复执行 repeat 次，打印每次的摘要以及不同 bitmap/ijon 哈希的个数和波动的下标，返回非正常退出的次数
+fn replay_repeated(
+    runner: &mut QemuProcess,
+    graph: &VecGraph,
+    spec: &GraphSpec,
+    first: ExecReport,
+    repeat: usize,
+) -> io::Result<usize> {
+    let mut reports = vec![first];
+    for _ in 1..repeat {
+        reports.push(execute(runner, graph, spec)?);
+    }
+    for (i, report) in reports.iter().enumerate() {
+        println!("  run {:>3}: {}", i, report.summary());
+    }
+    let bitmap_hashes: BTreeSet<u64> = reports.iter().map(|r| r.bitmap_hash).collect();
+    let ijon_hashes: BTreeSet<u64> = reports.iter().map(|r| r.ijon_hash).collect();
+    let runs: Vec<Vec<u8>> = reports.iter().map(|r| r.run_bitmap.clone()).collect();
+    let var_edges = class_registry::variable_edges(&runs);
+    println!(
+        "  stability   : {} distinct bitmaps, {} distinct ijon maps, {} variable edges{}",
+        bitmap_hashes.len(),
+        ijon_hashes.len(),
+        var_edges.len(),
+        if bitmap_hashes.len() == 1 && ijon_hashes.len() == 1 { " (stable)" } else { " (UNSTABLE)" }
+    );
+    if !var_edges.is_empty() {
+        println!("  var edges   : {:?}", var_edges);
+    }
+    Ok(reports.iter().filter(|r| is_finding(&r.reason)).count())
+}
+
+/// 依次执行只包含前 1..=n 个节点的前缀，打印每增加一个节点后覆盖率的变化
+fn replay_per_packet(runner: &mut QemuProcess, graph: &VecGraph, spec: &GraphSpec) -> io::Result<usize> {
+    let names: Vec<String> = graph
+        .node_iter(spec)
+        .map(|node| spec.get_node(node.id).map(|n| n.name.clone()).unwrap_or_else(|_| "?".to_string()))
+        .collect();
+    let mut findings = 0;
+    let mut covered: BTreeSet<usize> = BTreeSet::new();
+    println!("  {:>4} {:<20} {:>7} {:>5} {:<16} exit", "node", "type", "edges", "new", "bitmap");
+    for (i, name) in names.iter().enumerate() {
+        let mut prefix = VecGraph::empty();
+        prefix.copy_from_cutoff(graph, i + 1, spec);
+        let report = execute(runner, &prefix, spec)?;
+        let hit: Vec<usize> = report.run_bitmap.iter().enumerate().filter(|(_, &b)| b != 0).map(|(e, _)| e).collect();
+        let new = hit.iter().filter(|e| !covered.contains(e)).count();
+        covered.extend(hit);
+        println!(
+            "  {:>4} {:<20} {:>7} {:>5} {:016x} {}",
+            i,
+            name,
+            report.edges,
+            new,
+            report.bitmap_hash,
+            reason_name(&report.reason)
+        );
+        if is_finding(&report.reason) {
+            findings += 1;
+        }
+    }
+    Ok(findings)
+}
+
+/// 依次执行 files，返回 (出现非正常退出的用例数, 无法读取而跳过的文件数)；runner 执行失败时返回错误
+pub fn replay(
+    runner: &mut QemuProcess,
+    spec: &GraphSpec,
+    files: &[PathBuf],
+    opts: &ReplayOptions,
+) -> io::Result<(usize, usize)> {
+    let mut findings = 0;
+    let mut skipped = 0;
+    for file in files.iter() {
+        let graph = match load_bin(file, spec) {
+            Ok(graph) => graph,
+            Err(e) => {
+                eprintln!("[Replay] skipping {:?}: {}", file, e);
+                skipped += 1;
+                continue;
+            }
+        };
+        let node_len = graph.node_len(spec);
+        let report = execute(runner, &graph, spec)?;
+        let abnormal = is_finding(&report.reason);
+        // 显式要求的重复执行与逐节点执行总是输出
+        let verbose = !opts.quiet || abnormal || opts.repeat > 1 || opts.per_packet;
+        if verbose {
+            println!("[Replay] {:?}", file);
+            report.print(node_len);
+        }
+        let mut abnormal_runs = if abnormal { 1 } else { 0 };
+        if opts.repeat > 1 {
+            abnormal_runs = replay_repeated(runner, &graph, spec, report, opts.repeat)?;
+        }
+        if opts.per_packet {
+            abnormal_runs += replay_per_packet(runner, &graph, spec)?;
+        }
+        if abnormal_runs > 0 {
+            findings += 1;
+        }
+    }
+    println!(
+        "[Replay] {} inputs, {} with abnormal exits, {} skipped",
+        files.len() - skipped,
+        findings,
+        skipped
+    );
+    Ok((findings, skipped))
+}
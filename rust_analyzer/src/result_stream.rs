@@ -0,0 +1,392 @@
+//! 流式校准结果：每个序列一个 `calibration_results_sequence_{id}.msgs` 文件，
+//! 由若干条 [u32 LE 长度][MessagePack 记录] 组成。序列登记时写 Header，
+//! 每个包测完追加一条 Packet，整个序列完成后追加 Footer。
+//! 与 python_inference 使用的 JSON 格式可以互相转换。
+
+use crate::analyzer::{PacketCalibrationResult, PacketProbedOffsets, PacketVarEdges, SequenceCalibrationResults};
+use crate::inference;
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const STREAM_EXTENSION: &str = "msgs";
+
+#[derive(Serialize, Deserialize)]
+pub enum ResultRecord {
+    Header {
+        sequence_id: usize,
+        pkt_number: usize,
+        raw_data: Option<Vec<u8>>,
+    },
+    Packet {
+        packet_id: usize,
+        cal_time: f32,
+        results: Vec<PacketCalibrationResult>,
+        var_edges: Vec<usize>,
+        #[serde(default)]
+        probed: Option<Vec<usize>>, // 旧版结果流中没有，视为全部探测
+        #[serde(default)]
+        truncated: bool,
+    },
+    Footer {
+        cal_time: f32,
+    },
+}
+
+pub fn stream_path(workdir: &str, sequence_id: usize) -> PathBuf {
+    Path::new(workdir).join(format!("calibration_results_sequence_{}.{}", sequence_id, STREAM_EXTENSION))
+}
+
+fn encode(record: &ResultRecord) -> io::Result<Vec<u8>> {
+    let mut body = vec![];
+    rmp_serde::encode::write_named(&mut body, record).map_err(io::Error::other)?;
+    let mut buf = (body.len() as u32).to_le_bytes().to_vec();
+    buf.extend(body);
+    Ok(buf)
+}
+
+/// 新建（或截断）序列的结果文件并写入 Header
+pub fn create(path: &Path, sequence_id: usize, pkt_number: usize, raw_data: Option<Vec<u8>>) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(&encode(&ResultRecord::Header { sequence_id, pkt_number, raw_data })?)
+}
+
+/// 追加一条记录。文件以追加模式打开且每条记录一次写入，多个线程可以同时向同一序列追加
+pub fn append(path: &Path, record: &ResultRecord) -> io::Result<()> {
+    let buf = encode(record)?;
+    let mut file = OpenOptions::new().append(true).open(path)?;
+    file.write_all(&buf)
+}
+
+/// 读出全部记录；末尾不完整的记录（写入中途退出）被忽略
+pub fn read(path: &Path) -> io::Result<Vec<ResultRecord>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut records = vec![];
+    loop {
+        let mut len = [0u8; 4];
+        if reader.read_exact(&mut len).is_err() {
+            break;
+        }
+        let mut body = vec![0u8; u32::from_le_bytes(len) as usize];
+        if reader.read_exact(&mut body).is_err() {
+            break;
+        }
+        records.push(rmp_serde::decode::from_slice(&body).map_err(io::Error::other)?);
+    }
+    Ok(records)
+}
+
+/// 结果文件已有 Footer，说明该序列已完整测完
+pub fn is_complete(path: &Path) -> bool {
+    match read(path) {
+        Ok(records) => records.iter().any(|r| matches!(r, ResultRecord::Footer { .. })),
+        Err(_) => false,
+    }
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// 把记录合并为 JSON 格式的序列结果，同一个包出现多次时以最后一条为准；
+/// 包内结果按偏移排序（adaptive 第二轮加密的偏移追加在后面）
+pub fn to_sequence_results(records: Vec<ResultRecord>) -> io::Result<SequenceCalibrationResults> {
+    let mut header = None;
+    let mut footer_time = None;
+    let mut packets = BTreeMap::new();
+    for record in records {
+        match record {
+            ResultRecord::Header { sequence_id, pkt_number, raw_data } => header = Some((sequence_id, pkt_number, raw_data)),
+            ResultRecord::Packet { packet_id, cal_time, results, var_edges, probed, truncated } => {
+                packets.insert(packet_id, (cal_time, results, var_edges, probed.map(|p| (p, truncated))));
+            }
+            ResultRecord::Footer { cal_time } => footer_time = Some(cal_time),
+        }
+    }
+    let (sequence_id, pkt_number, raw_data) =
+        header.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "result stream has no header"))?;
+    let mut results = SequenceCalibrationResults {
+        sequence_id,
+        cal_time: 0.0,
+        pkt_number,
+        raw_data: raw_data.map(|data| encode_hex(&data)),
+        packets_cali_result: vec![],
+        var_edges: vec![],
+        probed_offsets: vec![],
+    };
+    for (packet_id, (cal_time, mut packet_results, edges, probed)) in packets.into_iter() {
+        packet_results.sort_by_key(|r| r.offset);
+        results.cal_time += cal_time;
+        results.packets_cali_result.extend(packet_results);
+        results.var_edges.push(PacketVarEdges { packet_id, edges });
+        if let Some((offsets, truncated)) = probed {
+            results.probed_offsets.push(PacketProbedOffsets { packet_id, offsets, truncated });
+        }
+    }
+    if let Some(cal_time) = footer_time {
+        results.cal_time = cal_time;
+    }
+    Ok(results)
+}
+
+/// 把 JSON 格式的序列结果拆成记录，包的耗时无从得知，只保留在 Footer 中
+pub fn from_sequence_results(results: SequenceCalibrationResults) -> Vec<ResultRecord> {
+    let raw_data = results.raw_data.as_deref().and_then(decode_hex);
+    let mut records = vec![ResultRecord::Header {
+        sequence_id: results.sequence_id,
+        pkt_number: results.pkt_number,
+        raw_data,
+    }];
+    let mut packets: BTreeMap<usize, Vec<PacketCalibrationResult>> = BTreeMap::new();
+    for r in results.packets_cali_result {
+        packets.entry(r.packet_id).or_default().push(r);
+    }
+    let mut var_edges: BTreeMap<usize, Vec<usize>> = results.var_edges.into_iter().map(|v| (v.packet_id, v.edges)).collect();
+    let mut probed: BTreeMap<usize, PacketProbedOffsets> = results.probed_offsets.into_iter().map(|p| (p.packet_id, p)).collect();
+    for (packet_id, packet_results) in packets {
+        records.push(ResultRecord::Packet {
+            packet_id,
+            cal_time: 0.0,
+            results: packet_results,
+            var_edges: var_edges.remove(&packet_id).unwrap_or_default(),
+            truncated: probed.get(&packet_id).map(|p| p.truncated).unwrap_or(false),
+            probed: probed.remove(&packet_id).map(|p| p.offsets),
+        });
+    }
+    records.push(ResultRecord::Footer { cal_time: results.cal_time });
+    records
+}
+
+/// 采样探测的包：探测过的偏移不是完整的 0..负载长度，或因预算耗尽提前结束
+pub fn sampled_packets(results: &SequenceCalibrationResults) -> Vec<usize> {
+    let raw = results.raw_data.as_deref().and_then(decode_hex).unwrap_or_default();
+    let payloads = inference::split_raw_data(&raw);
+    results
+        .probed_offsets
+        .iter()
+        .filter(|p| {
+            let len = payloads.get(p.packet_id).map(|d| d.len()).unwrap_or(0);
+            let offsets: BTreeSet<usize> = p.offsets.iter().copied().collect();
+            p.truncated || !offsets.into_iter().eq(0..len)
+        })
+        .map(|p| p.packet_id)
+        .collect()
+}
+
+/// 写出 python_inference 格式的 JSON。python_inference 按位置索引每个算子的结果并假定偏移为连续的 0..len，
+/// 采样探测的包会得到错误的分段，此时拒绝导出
+pub fn write_json(results: &SequenceCalibrationResults, path: &Path) -> io::Result<()> {
+    let sampled = sampled_packets(results);
+    if !sampled.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("packets {:?} were sampled, python_inference needs every offset (use --format inferred)", sampled),
+        ));
+    }
+    let json_output = serde_json::to_string_pretty(results)?;
+    let mut file = File::create(path)?;
+    file.write_all(json_output.as_bytes())
+}
+
+/// 按扩展名读取一个结果文件：.json 为 python_inference 格式，其余视为结果流
+pub fn load(path: &Path) -> io::Result<SequenceCalibrationResults> {
+    if path.extension().map(|e| e == "json").unwrap_or(false) {
+        Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+    } else {
+        to_sequence_results(read(path)?)
+    }
+}
+
+pub fn write_stream(results: SequenceCalibrationResults, path: &Path) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    for record in from_sequence_results(results) {
+        file.write_all(&encode(&record)?)?;
+    }
+    Ok(())
+}
+
+fn is_result_file(path: &Path) -> bool {
+    let is_result = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with("calibration_results_sequence_"))
+        .unwrap_or(false);
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    is_result && (ext == STREAM_EXTENSION || ext == "json")
+}
+
+/// 展开命令行给出的文件与目录：目录中取所有序列结果文件，
+/// 同一序列同时存在 .msgs 与 .json 时只取 .msgs
+pub fn collect(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for path in paths.iter() {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+        let mut found: Vec<PathBuf> = vec![];
+        for entry in fs::read_dir(path)? {
+            let file = entry?.path();
+            if is_result_file(&file) {
+                found.push(file);
+            }
+        }
+        found.sort();
+        let streams: Vec<PathBuf> = found
+            .iter()
+            .filter(|f| f.extension().map(|e| e == STREAM_EXTENSION).unwrap_or(false))
+            .map(|f| f.with_extension(""))
+            .collect();
+        files.extend(
+            found
+                .into_iter()
+                .filter(|f| f.extension().map(|e| e == STREAM_EXTENSION).unwrap_or(false) || !streams.contains(&f.with_extension(""))),
+        );
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edge_delta::EdgeDelta;
+    use crate::ijon_slots::SlotChange;
+    use serde_json::json;
+
+    fn result(packet_id: usize, offset: usize, op: &str, cf_index: usize, cfc_index: usize) -> PacketCalibrationResult {
+        PacketCalibrationResult {
+            packet_id,
+            offset,
+            stable: true,
+            mutation_operator: op.to_string(),
+            cf_index,
+            vf_index: 0,
+            cfc_index,
+            rc_index: 0,
+            width: 1,
+            big_endian: None,
+            edge_delta: None,
+            slot_changes: vec![],
+        }
+    }
+
+    fn packet(packet_id: usize, cal_time: f32, results: Vec<PacketCalibrationResult>, var_edges: Vec<usize>, probed: Option<Vec<usize>>) -> ResultRecord {
+        ResultRecord::Packet { packet_id, cal_time, results, var_edges, probed, truncated: false }
+    }
+
+    /// python_inference 读取的 JSON 格式
+    fn expected_json() -> serde_json::Value {
+        json!({
+            "sequence_id": 7,
+            "cal_time": 4.0,
+            "pkt_number": 2,
+            "raw_data": "02006162010063",
+            "packets_cali_result": [
+                {"packet_id": 0, "offset": 0, "stable": true, "mutation_operator": "None", "cf_index": 0, "vf_index": 0,
+                 "cfc_index": 0, "rc_index": 0, "width": 1, "big_endian": null},
+                {"packet_id": 0, "offset": 1, "stable": true, "mutation_operator": "LBF", "cf_index": 1, "vf_index": 0,
+                 "cfc_index": 2, "rc_index": 0, "width": 1, "big_endian": null,
+                 "slot_changes": [{"slot": 3, "old": 1, "new": 2}]},
+                {"packet_id": 1, "offset": 0, "stable": true, "mutation_operator": "LBF", "cf_index": 3, "vf_index": 0,
+                 "cfc_index": 4, "rc_index": 0, "width": 1, "big_endian": null,
+                 "edge_delta": {"added": [4], "removed": [], "hit_changed": [9]}}
+            ],
+            "var_edges": [{"packet_id": 0, "edges": [3, 5]}, {"packet_id": 1, "edges": []}],
+            "probed_offsets": [{"packet_id": 1, "offsets": [0], "truncated": false}]
+        })
+    }
+
+    fn read_json(path: &Path) -> serde_json::Value {
+        serde_json::from_reader(File::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_stream_json_round_trip() {
+        let dir = std::env::temp_dir().join(format!("result_stream_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = stream_path(dir.to_str().unwrap(), 7);
+        create(&path, 7, 2, Some(vec![2, 0, b'a', b'b', 1, 0, b'c'])).unwrap();
+
+        let mut probe = result(0, 1, "LBF", 1, 2);
+        probe.slot_changes = vec![SlotChange { slot: 3, old: 1, new: 2 }];
+        let mut other = result(1, 0, "LBF", 3, 4);
+        other.edge_delta = Some(EdgeDelta { added: vec![4], removed: vec![], hit_changed: vec![9] });
+        append(&path, &packet(1, 0.25, vec![other], vec![], Some(vec![0]))).unwrap();
+        // 同一个包重复出现时以最后一条为准，包内按偏移排序；旧版记录没有 probed
+        append(&path, &packet(0, 9.0, vec![result(0, 0, "LBF", 9, 9)], vec![1], None)).unwrap();
+        append(&path, &packet(0, 1.5, vec![probe, result(0, 0, inference::BASELINE_OPERATOR, 0, 0)], vec![3, 5], None)).unwrap();
+        assert!(!is_complete(&path));
+        append(&path, &ResultRecord::Footer { cal_time: 4.0 }).unwrap();
+        // 写入中途退出留下的不完整记录被忽略
+        OpenOptions::new().append(true).open(&path).unwrap().write_all(&[16, 0, 0, 0, 1, 2]).unwrap();
+        assert!(is_complete(&path));
+        assert_eq!(read(&path).unwrap().len(), 5);
+
+        let json_path = path.with_extension("json");
+        write_json(&load(&path).unwrap(), &json_path).unwrap();
+        assert_eq!(read_json(&json_path), expected_json());
+
+        // JSON 转回结果流后内容不变
+        let msgs_path = dir.join("converted.msgs");
+        write_stream(load(&json_path).unwrap(), &msgs_path).unwrap();
+        assert!(is_complete(&msgs_path));
+        let converted = dir.join("converted.json");
+        write_json(&load(&msgs_path).unwrap(), &converted).unwrap();
+        assert_eq!(read_json(&converted), expected_json());
+
+        assert_eq!(collect(std::slice::from_ref(&dir)).unwrap(), vec![path]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_sampled_packets_refused() {
+        let path = std::env::temp_dir().join(format!("result_stream_sampled_{}.json", std::process::id()));
+        let mut results = SequenceCalibrationResults {
+            sequence_id: 0,
+            cal_time: 0.0,
+            pkt_number: 1,
+            raw_data: Some(encode_hex(&[4, 0, b'a', b'b', b'c', b'd'])),
+            packets_cali_result: vec![result(0, 0, inference::BASELINE_OPERATOR, 0, 0), result(0, 0, "LBF", 0, 0), result(0, 2, "LBF", 1, 1)],
+            var_edges: vec![],
+            probed_offsets: vec![PacketProbedOffsets { packet_id: 0, offsets: vec![0, 2], truncated: false }],
+        };
+        assert_eq!(sampled_packets(&results), vec![0]);
+        assert_eq!(write_json(&results, &path).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+
+        // adaptive 加密后覆盖了全部偏移（顺序无关），可以导出
+        results.probed_offsets[0].offsets = vec![0, 3, 1, 2];
+        assert!(sampled_packets(&results).is_empty());
+        // 提前截止的包即使偏移连续也拒绝
+        results.probed_offsets[0].truncated = true;
+        assert_eq!(sampled_packets(&results), vec![0]);
+        // first:K 只探测了前缀
+        results.probed_offsets[0] = PacketProbedOffsets { packet_id: 0, offsets: vec![0, 1], truncated: false };
+        assert_eq!(sampled_packets(&results), vec![0]);
+        // 旧版结果没有 probed_offsets，视为全部探测
+        results.probed_offsets.clear();
+        write_json(&results, &path).unwrap();
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(encode_hex(&[0x00, 0x7f, 0xff]), "007fff");
+        assert_eq!(decode_hex("007fff"), Some(vec![0x00, 0x7f, 0xff]));
+        assert_eq!(decode_hex("abc"), None);
+        assert_eq!(decode_hex("zz"), None);
+    }
+}
@@ -0,0 +1,175 @@
+//! 校准时的偏移采样策略：默认探测包内每个字节，长负载可以只探测前 K 个字节、按步长探测、
+//! 随机抽取若干偏移，或先按步长粗测、再在行为发生变化的相邻偏移之间逐字节加密（adaptive）。
+//!
+//! 策略写成逗号分隔的列表，例如 `stride:4`、`random:64`、`first:256,adaptive:8`；
+//! `first:K` 限定范围，其余选项决定范围内探测哪些偏移。
+
+use crate::romu::RomuPrng;
+
+use std::collections::BTreeSet;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OffsetMode {
+    All,
+    Stride(usize),
+    Random(usize),
+    Adaptive(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sampling {
+    pub first: Option<usize>,
+    pub mode: OffsetMode,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Self { first: None, mode: OffsetMode::All }
+    }
+}
+
+impl Sampling {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut sampling = Sampling::default();
+        for item in spec.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            let (name, arg) = match item.find(':') {
+                Some(i) => (&item[..i], Some(&item[i + 1..])),
+                None => (item, None),
+            };
+            let arg = match arg.map(|a| a.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => Some(n),
+                None => None,
+                _ => return Err(format!("invalid sampling option {:?}: expected a positive number", item)),
+            };
+            let mode = match (name, arg) {
+                ("all", None) => OffsetMode::All,
+                ("first", Some(k)) => {
+                    sampling.first = Some(k);
+                    continue;
+                }
+                ("stride", Some(n)) => OffsetMode::Stride(n),
+                ("random", Some(n)) => OffsetMode::Random(n),
+                ("adaptive", Some(n)) => OffsetMode::Adaptive(n),
+                _ => return Err(format!("unknown sampling option {:?} (all, first:K, stride:N, random:N, adaptive:N)", item)),
+            };
+            if sampling.mode != OffsetMode::All {
+                return Err(format!("sampling {:?} combines several of stride/random/adaptive", spec));
+            }
+            sampling.mode = mode;
+        }
+        Ok(sampling)
+    }
+
+    /// 长度为 len 的负载中参与采样的范围
+    pub fn limit(&self, len: usize) -> usize {
+        self.first.map(|k| std::cmp::min(k, len)).unwrap_or(len)
+    }
+
+    /// 首轮探测的偏移（升序）；seed 决定随机抽取的结果，同一个包每次得到相同的偏移
+    pub fn initial_offsets(&self, len: usize, seed: u64) -> Vec<usize> {
+        let limit = self.limit(len);
+        match self.mode {
+            OffsetMode::All => (0..limit).collect(),
+            OffsetMode::Stride(n) => (0..limit).step_by(n).collect(),
+            OffsetMode::Adaptive(n) => {
+                // 加上最后一个偏移，使末尾的区间也能被加密
+                let mut offsets: Vec<usize> = (0..limit).step_by(n).collect();
+                if limit > 0 && offsets.last() != Some(&(limit - 1)) {
+                    offsets.push(limit - 1);
+                }
+                offsets
+            }
+            OffsetMode::Random(n) => {
+                let mut rng = RomuPrng::new_from_u64(seed);
+                let mut offsets: Vec<usize> = (0..limit).collect();
+                let n = std::cmp::min(n, limit);
+                for i in 0..n {
+                    let j = i + (rng.next_u64() % (limit - i) as u64) as usize;
+                    offsets.swap(i, j);
+                }
+                offsets.truncate(n);
+                offsets.sort_unstable();
+                offsets
+            }
+        }
+    }
+
+    /// adaptive 的第二轮：相邻两个已测偏移的行为不同（changed 返回 true）时，探测二者之间的全部偏移
+    pub fn densify<F: Fn(usize, usize) -> bool>(&self, probed: &BTreeSet<usize>, changed: F) -> Vec<usize> {
+        if let OffsetMode::Adaptive(_) = self.mode {
+            let probed: Vec<usize> = probed.iter().copied().collect();
+            probed
+                .windows(2)
+                .filter(|w| w[1] > w[0] + 1 && changed(w[0], w[1]))
+                .flat_map(|w| w[0] + 1..w[1])
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(spec: &str) -> Sampling {
+        Sampling::parse(spec).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse(""), Sampling::default());
+        assert_eq!(parse("all"), Sampling::default());
+        assert_eq!(parse("stride:4"), Sampling { first: None, mode: OffsetMode::Stride(4) });
+        assert_eq!(parse(" first:8 , adaptive:4 "), Sampling { first: Some(8), mode: OffsetMode::Adaptive(4) });
+        assert_eq!(parse("random:3,first:2"), Sampling { first: Some(2), mode: OffsetMode::Random(3) });
+        for bad in ["stride:0", "first:0", "stride:x", "first", "stride", "all:2", "bogus", "stride:2,random:3", "adaptive:2,stride:2"].iter() {
+            assert!(Sampling::parse(bad).is_err(), "{} should be rejected", bad);
+        }
+    }
+
+    #[test]
+    fn test_initial_offsets() {
+        assert_eq!(parse("all").initial_offsets(4, 0), vec![0, 1, 2, 3]);
+        assert_eq!(parse("first:2").initial_offsets(4, 0), vec![0, 1]);
+        assert_eq!(parse("first:9").initial_offsets(4, 0), vec![0, 1, 2, 3]);
+        assert_eq!(parse("stride:3").initial_offsets(10, 0), vec![0, 3, 6, 9]);
+        assert_eq!(parse("first:5,stride:2").initial_offsets(10, 0), vec![0, 2, 4]);
+        // adaptive 总是包含范围内最后一个偏移，且不重复
+        assert_eq!(parse("adaptive:4").initial_offsets(10, 0), vec![0, 4, 8, 9]);
+        assert_eq!(parse("adaptive:3").initial_offsets(10, 0), vec![0, 3, 6, 9]);
+        assert_eq!(parse("first:6,adaptive:4").initial_offsets(10, 0), vec![0, 4, 5]);
+        assert_eq!(parse("adaptive:4").initial_offsets(1, 0), vec![0]);
+        assert!(parse("adaptive:4").initial_offsets(0, 0).is_empty());
+        assert!(parse("stride:4").initial_offsets(0, 0).is_empty());
+    }
+
+    #[test]
+    fn test_random_offsets() {
+        let random = parse("random:3");
+        let offsets = random.initial_offsets(10, 42);
+        assert_eq!(offsets.len(), 3);
+        assert!(offsets.windows(2).all(|w| w[0] < w[1]));
+        assert!(offsets.iter().all(|&o| o < 10));
+        // 同一个种子得到相同的偏移
+        assert_eq!(random.initial_offsets(10, 42), offsets);
+        // n 不小于范围时探测全部偏移
+        assert_eq!(random.initial_offsets(3, 7), vec![0, 1, 2]);
+        assert_eq!(parse("random:20").initial_offsets(5, 7), vec![0, 1, 2, 3, 4]);
+        assert_eq!(parse("first:4,random:9").initial_offsets(10, 7), vec![0, 1, 2, 3]);
+        assert!(random.initial_offsets(0, 7).is_empty());
+    }
+
+    #[test]
+    fn test_densify() {
+        let probed: BTreeSet<usize> = [0, 4, 8, 9].iter().copied().collect();
+        let adaptive = parse("adaptive:4");
+        assert_eq!(adaptive.densify(&probed, |a, b| (a, b) == (4, 8)), vec![5, 6, 7]);
+        assert_eq!(adaptive.densify(&probed, |_, _| true), vec![1, 2, 3, 5, 6, 7]);
+        assert!(adaptive.densify(&probed, |_, _| false).is_empty());
+        // 其他策略不做第二轮
+        assert!(parse("stride:4").densify(&probed, |_, _| true).is_empty());
+        assert!(parse("all").densify(&probed, |_, _| true).is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+//! 校准任务调度：把队列中的每个序列拆成 (sequence, packet) 任务，
+//! 所有分析线程共享同一个任务队列，每个包只被测量一次，
+//! 同一序列的结果在最后一个包完成时合并输出。
+//! 设置了序列预算时，每个包开始测量时分到该序列剩余预算在未完成的包之间的均分份额。
+
+use crate::analyzer::{PacketCalibrationResult, PacketProbedOffsets, PacketVarEdges, SequenceCalibrationResults};
+use crate::inference::{InferredPacket, InferredSequence};
+use crate::input::Input;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::RwLock;
+
+/// 一个待测量的包
+#[derive(Clone)]
+pub struct CalibrationJob {
+    pub sequence_id: usize,
+    pub packet_id: usize,
+    pub num_ops: usize,
+    pub entry: Input,
+}
+
+/// 单个包的测量产出
+pub struct PacketCalibration {
+    pub results: Vec<PacketCalibrationResult>,
+    pub inferred: Option<InferredPacket>,
+    pub cal_time: f32,
+    pub var_edges: Vec<usize>,
+    pub probed: Vec<usize>,
+    pub truncated: bool,
+    pub execs: u64,
+}
+
+/// 每个序列的校准预算，None 为不限
+#[derive(Clone, Copy, Default)]
+pub struct SequenceBudget {
+    pub secs: Option<f32>,
+    pub execs: Option<u64>,
+}
+
+/// 一个序列的合并结果
+pub struct SequenceCalibration {
+    pub results: SequenceCalibrationResults,
+    pub inferred: InferredSequence,
+}
+
+struct SequenceState {
+    entry: Input,
+    num_ops: usize,
+    raw_data: Option<String>,
+    cal_time: f32,
+    execs: u64,
+    remaining: usize,
+    packets: BTreeMap<usize, PacketCalibration>,
+}
+
+struct SchedulerData {
+    jobs: VecDeque<(usize, usize)>,
+    sequences: HashMap<usize, SequenceState>,
+    ready: bool, // 种子导入完成且任务已全部入队
+    failed: bool, // 负责导入的线程在入队完成前 panic
+}
+
+#[derive(Clone)]
+pub struct CalibrationScheduler {
+    resume: bool,
+    budget: SequenceBudget,
+    data: Arc<RwLock<SchedulerData>>,
+}
+
+impl CalibrationScheduler {
+    pub fn new(resume: bool, budget: SequenceBudget) -> Self {
+        Self {
+            resume,
+            budget,
+            data: Arc::new(RwLock::new(SchedulerData {
+                jobs: VecDeque::new(),
+                sequences: HashMap::new(),
+                ready: false,
+                failed: false,
+            })),
+        }
+    }
+
+    /// 是否从上一次运行的断点继续
+    pub fn resume(&self) -> bool {
+        self.resume
+    }
+
+    /// 登记一个序列，并为它的每个包生成一个任务
+    pub fn add_sequence(&self, sequence_id: usize, entry: Input, num_ops: usize, raw_data: Option<String>) {
+        let mut data = self.data.write().unwrap();
+        if num_ops == 0 {
+            return;
+        }
+        for packet_id in 0..num_ops {
+            data.jobs.push_back((sequence_id, packet_id));
+        }
+        data.sequences.insert(
+            sequence_id,
+            SequenceState {
+                entry,
+                num_ops,
+                raw_data,
+                cal_time: 0.0,
+                execs: 0,
+                remaining: num_ops,
+                packets: BTreeMap::new(),
+            },
+        );
+    }
+
+    /// 所有任务入队完毕，其余线程可以开始取任务
+    pub fn set_ready(&self) {
+        self.data.write().unwrap().ready = true;
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.data.read().unwrap().ready
+    }
+
+    /// 导入失败，等待中的线程不会再等到 ready
+    pub fn set_failed(&self) {
+        self.data.write().unwrap().failed = true;
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.data.read().unwrap().failed
+    }
+
+    pub fn num_pending_jobs(&self) -> usize {
+        self.data.read().unwrap().jobs.len()
+    }
+
+    /// 取出下一个任务，任务队列为空时返回 None
+    pub fn next_job(&self) -> Option<CalibrationJob> {
+        let mut data = self.data.write().unwrap();
+        let (sequence_id, packet_id) = data.jobs.pop_front()?;
+        let seq = &data.sequences[&sequence_id];
+        Some(CalibrationJob {
+            sequence_id,
+            packet_id,
+            num_ops: seq.num_ops,
+            entry: seq.entry.clone(),
+        })
+    }
+
+    /// job 所在序列剩余预算在未完成的包之间的均分份额
+    pub fn packet_budget(&self, job: &CalibrationJob) -> SequenceBudget {
+        let data = self.data.read().unwrap();
+        let seq = match data.sequences.get(&job.sequence_id) {
+            Some(seq) => seq,
+            None => return self.budget,
+        };
+        let share = std::cmp::max(seq.remaining, 1);
+        SequenceBudget {
+            secs: self.budget.secs.map(|secs| (secs - seq.cal_time).max(0.0) / share as f32),
+            execs: self.budget.execs.map(|execs| execs.saturating_sub(seq.execs) / share as u64),
+        }
+    }
+
+    /// 提交一个包的测量结果；若这是该序列最后一个完成的包，返回按包编号合并后的整条序列结果
+    pub fn finish_packet(&self, job: &CalibrationJob, packet: PacketCalibration) -> Option<SequenceCalibration> {
+        let mut data = self.data.write().unwrap();
+        let seq = data.sequences.get_mut(&job.sequence_id)?;
+        seq.cal_time += packet.cal_time;
+        seq.execs += packet.execs;
+        if seq.packets.insert(job.packet_id, packet).is_none() {
+            seq.remaining -= 1;
+        }
+        if seq.remaining > 0 {
+            return None;
+        }
+
+        let seq = data.sequences.remove(&job.sequence_id).unwrap();
+        let mut results = SequenceCalibrationResults {
+            sequence_id: job.sequence_id,
+            cal_time: seq.cal_time,
+            pkt_number: seq.num_ops,
+            raw_data: seq.raw_data,
+            packets_cali_result: vec![],
+            var_edges: vec![],
+            probed_offsets: vec![],
+        };
+        let mut inferred = InferredSequence { sequence_id: job.sequence_id, packets: vec![] };
+        for (packet_id, packet) in seq.packets.into_iter() {
+            results.packets_cali_result.extend(packet.results);
+            results.var_edges.push(PacketVarEdges { packet_id, edges: packet.var_edges });
+            results.probed_offsets.push(PacketProbedOffsets { packet_id, offsets: packet.probed, truncated: packet.truncated });
+            inferred.packets.extend(packet.inferred);
+        }
+        Some(SequenceCalibration { results, inferred })
+    }
+}
@@ -0,0 +1,419 @@
+//! 把原始消息序列编码为 spec 下合法的 graph：每条消息对应一个承载包的节点（原子为 DataVec<u8>），
+//! 节点需要的值（如连接句柄）由 spec 中不需要输入的节点先行产生。
+//!
+//! 准备 workdir 时按格式导入种子目录中的每一项：已编码的 .bin、AFLNet 的 replayable 文件
+//! （[u32 长度][消息] 的序列）、整个文件为一条消息的原始文件，以及每个文件一条消息的目录。
+
+use crate::replay::BIN_HEADER_LEN;
+use crate::structured_fuzzer::graph_mutator::atomic_data::AtomicSize;
+use crate::structured_fuzzer::graph_mutator::graph_storage::VecGraph;
+use crate::structured_fuzzer::graph_mutator::newtypes::{NodeTypeID, ValueTypeID};
+use crate::structured_fuzzer::graph_mutator::spec::{GraphSpec, NodeSpec};
+use crate::structured_fuzzer::GraphStorage;
+
+use std::convert::TryInto;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// 承载包的节点：给定名字时按名字查找，否则取第一个原子长度可变的节点
+pub fn find_packet_node(spec: &GraphSpec, name: Option<&str>) -> io::Result<NodeTypeID> {
+    let is_dynamic = |node: &NodeSpec| {
+        node.data
+            .map(|d| matches!(spec.get_data(d).unwrap().atomic_type.size(), AtomicSize::Dynamic()))
+            .unwrap_or(false)
+    };
+    let node = match name {
+        Some(name) => spec.node_specs.iter().find(|n| n.name == name),
+        None => spec.node_specs.iter().find(|n| is_dynamic(n)),
+    };
+    match node {
+        Some(node) if is_dynamic(node) => Ok(node.id),
+        Some(node) => Err(invalid(format!("node {} doesn't carry variable-length data", node.name))),
+        None => Err(invalid(format!("no packet node {:?} in the spec", name))),
+    }
+}
+
+/// 按 GraphBuilder 的规则分配连接编号：每种值各自从 1 开始递增，
+/// 作为输入的值被消耗，作为 passthrough 的值只被借用
+struct SeedBuilder<'a> {
+    spec: &'a GraphSpec,
+    next_id: Vec<u16>,
+    available: Vec<Vec<u16>>,
+    ops: Vec<u16>,
+    data: Vec<u8>,
+}
+
+impl<'a> SeedBuilder<'a> {
+    fn new(spec: &'a GraphSpec) -> Self {
+        let values = spec.value_specs.len();
+        Self { spec, next_id: vec![0; values], available: vec![vec![]; values], ops: vec![], data: vec![] }
+    }
+
+    /// 能产生 value 且自身不需要任何值的节点，优先选择不带数据的
+    fn producer(&self, value: ValueTypeID) -> Option<&'a NodeSpec> {
+        let mut candidates = self
+            .spec
+            .node_specs
+            .iter()
+            .filter(|n| n.generatable && n.inputs.is_empty() && n.passthroughs.is_empty() && n.outputs.contains(&value));
+        let first = candidates.next()?;
+        Some(std::iter::once(first).chain(candidates).find(|n| n.data.is_none()).unwrap_or(first))
+    }
+
+    /// 确保 node 需要的值都可用，缺少时先追加产生它的节点
+    fn provide_values(&mut self, node: &NodeSpec) -> io::Result<()> {
+        for (&value, &count) in node.required_values.iter() {
+            while self.available[value.as_usize()].len() < count {
+                let producer = self.producer(value).ok_or_else(|| {
+                    invalid(format!(
+                        "node {} needs value {} but no node without inputs produces it",
+                        node.name,
+                        self.spec.get_value(value).unwrap().name
+                    ))
+                })?;
+                let data = vec![0u8; producer.min_data_size(self.spec)];
+                self.append(producer, &data);
+            }
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, node: &NodeSpec, data: &[u8]) {
+        self.ops.push(node.id.as_u16());
+        for value in node.inputs.iter() {
+            let id = self.available[value.as_usize()].pop().unwrap();
+            self.ops.push(id);
+        }
+        for value in node.passthroughs.iter() {
+            let id = *self.available[value.as_usize()].last().unwrap();
+            self.ops.push(id);
+        }
+        for value in node.outputs.iter() {
+            let next = &mut self.next_id[value.as_usize()];
+            *next += 1;
+            self.ops.push(*next);
+            self.available[value.as_usize()].push(*next);
+        }
+        self.data.extend_from_slice(data);
+    }
+
+    /// 追加一条消息，超过 DataVec 长度上限的消息拆成多个节点
+    fn append_message(&mut self, node: &NodeSpec, message: &[u8]) -> io::Result<()> {
+        for chunk in message.chunks(u16::MAX as usize) {
+            self.provide_values(node)?;
+            let mut data = (chunk.len() as u16).to_le_bytes().to_vec();
+            data.extend_from_slice(chunk);
+            self.append(node, &data);
+        }
+        Ok(())
+    }
+}
+
+/// 把一个会话的消息依次编码为 packet_node 节点组成的 graph
+pub fn messages_to_graph(spec: &GraphSpec, packet_node: NodeTypeID, messages: &[Vec<u8>]) -> io::Result<VecGraph> {
+    let node = spec.get_node(packet_node).map_err(|e| invalid(format!("{:?}", e)))?;
+    let mut builder = SeedBuilder::new(spec);
+    for message in messages.iter() {
+        builder.append_message(node, message)?;
+    }
+    Ok(VecGraph::new(builder.ops, builder.data))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeedFormat {
+    Bin,
+    Replayable,
+    Raw,
+    PacketDir,
+}
+
+impl SeedFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            SeedFormat::Bin => "bin",
+            SeedFormat::Replayable => "replayable",
+            SeedFormat::Raw => "raw",
+            SeedFormat::PacketDir => "packet dir",
+        }
+    }
+}
+
+fn le64(b: &[u8]) -> u64 {
+    u64::from_le_bytes(b[..8].try_into().unwrap())
+}
+
+/// 文件头自洽（偏移与长度正好覆盖整个文件）时返回其中的 checksum
+pub fn bin_checksum(data: &[u8]) -> Option<u64> {
+    if data.len() < BIN_HEADER_LEN {
+        return None;
+    }
+    let (num_ops, num_data) = (le64(&data[8..]), le64(&data[16..]));
+    let (op_offset, data_offset) = (le64(&data[24..]), le64(&data[32..]));
+    let consistent = op_offset == BIN_HEADER_LEN as u64
+        && num_ops.checked_mul(2).and_then(|n| n.checked_add(op_offset)) == Some(data_offset)
+        && data_offset.checked_add(num_data) == Some(data.len() as u64);
+    if consistent { Some(le64(data)) } else { None }
+}
+
+/// 按 AFLNet replayable 格式拆分消息：[u32 长度][消息] 依次排列并正好覆盖整个文件，且消息均非空
+fn replayable_messages(data: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut messages = vec![];
+    let mut pos = 0;
+    while pos < data.len() {
+        let len = u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().unwrap()) as usize;
+        let message = data.get(pos + 4..(pos + 4).checked_add(len)?)?;
+        if message.is_empty() {
+            return None;
+        }
+        messages.push(message.to_vec());
+        pos += 4 + len;
+    }
+    if messages.is_empty() { None } else { Some(messages) }
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name().and_then(|n| n.to_str()).map(|n| n.starts_with('.')).unwrap_or(false)
+}
+
+/// 目录中的文件（不含隐藏文件与子目录），按文件名排序
+fn sorted_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && !is_hidden(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub fn detect(path: &Path) -> io::Result<SeedFormat> {
+    if path.is_dir() {
+        return Ok(SeedFormat::PacketDir);
+    }
+    let data = fs::read(path)?;
+    let format = if bin_checksum(&data).is_some() {
+        SeedFormat::Bin
+    } else if replayable_messages(&data).is_some() {
+        SeedFormat::Replayable
+    } else {
+        SeedFormat::Raw
+    };
+    Ok(format)
+}
+
+/// 读取一个种子中的消息，.bin 种子由调用方直接复制
+fn seed_messages(path: &Path, format: SeedFormat) -> io::Result<Vec<Vec<u8>>> {
+    let messages = match format {
+        SeedFormat::Bin => unreachable!(),
+        SeedFormat::Replayable => replayable_messages(&fs::read(path)?).unwrap(),
+        SeedFormat::Raw => vec![fs::read(path)?],
+        SeedFormat::PacketDir => {
+            let mut messages = vec![];
+            for file in sorted_files(path)? {
+                messages.push(fs::read(file)?);
+            }
+            messages
+        }
+    };
+    Ok(messages.into_iter().filter(|m| !m.is_empty()).collect())
+}
+
+/// 把 seed_path 中的每一项按检测出的格式转换为 seed_dir/seed_N.bin，返回导入的种子数。
+/// 无法导入的项（其他 spec 编码的 .bin、空文件、spec 中没有承载包的节点等）给出提示后跳过
+pub fn import_seeds(seed_path: &Path, seed_dir: &Path, spec: &GraphSpec, packet_node: Option<&str>) -> io::Result<usize> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(seed_path)? {
+        let path = entry?.path();
+        if !is_hidden(&path) {
+            entries.push(path);
+        }
+    }
+    entries.sort();
+    let node = find_packet_node(spec, packet_node);
+    let mut imported = 0;
+    for src in entries.iter() {
+        let dst = seed_dir.join(format!("seed_{}.bin", imported));
+        let res = detect(src).and_then(|format| {
+            if format == SeedFormat::Bin {
+                let checksum = bin_checksum(&fs::read(src)?).unwrap();
+                if checksum != spec.checksum {
+                    return Err(invalid(format!("encoded with another spec ({:x} != {:x})", checksum, spec.checksum)));
+                }
+                fs::copy(src, &dst)?;
+                return Ok((format, 0));
+            }
+            let messages = seed_messages(src, format)?;
+            if messages.is_empty() {
+                return Err(invalid("no messages".to_string()));
+            }
+            let node = *node.as_ref().map_err(|e| invalid(e.to_string()))?;
+            let graph = messages_to_graph(spec, node, &messages)?;
+            graph.write_to_file(dst.to_str().unwrap(), spec);
+            Ok((format, messages.len()))
+        });
+        match res {
+            Ok((SeedFormat::Bin, _)) => println!("[Seeds] {:?} (bin) -> {:?}", src, dst),
+            Ok((format, n)) => println!("[Seeds] {:?} ({}, {} messages) -> {:?}", src, format.name(), n, dst),
+            Err(e) => {
+                eprintln!("[Seeds] skipping {:?}: {}", src, e);
+                continue;
+            }
+        }
+        imported += 1;
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay::load_bin;
+    use crate::structured_fuzzer::graph_mutator::atomic_data::{DataInt, DataVec};
+    use std::sync::Arc;
+
+    /// connect 产生连接，packet 借用连接并携带变长数据，close 消耗连接
+    fn spec() -> GraphSpec {
+        let mut spec = GraphSpec::new();
+        let con = spec.value_type("con");
+        let byte = spec.data_type("u8", Arc::new(DataInt::new(1, vec![])));
+        let bytes = DataVec::new((0, 1 << 16), byte, vec![], &spec);
+        let bytes = spec.data_type("bytes", Arc::new(bytes));
+        spec.node_type("connect", None, vec![], vec![], vec![con]);
+        spec.node_type("packet", Some(bytes), vec![], vec![con], vec![]);
+        spec.node_type("close", Some(byte), vec![con], vec![], vec![]);
+        spec.checksum = 0x5eed;
+        spec
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("seed_import_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn replayable(messages: &[&[u8]]) -> Vec<u8> {
+        let mut data = vec![];
+        for m in messages.iter() {
+            data.extend_from_slice(&(m.len() as u32).to_le_bytes());
+            data.extend_from_slice(m);
+        }
+        data
+    }
+
+    /// graph 中每个节点的 (名字, 携带的消息)
+    fn nodes(graph: &VecGraph, spec: &GraphSpec) -> Vec<(String, Vec<u8>)> {
+        graph
+            .node_iter(spec)
+            .map(|node| (spec.get_node(node.id).unwrap().name.clone(), node.data.get(2..).unwrap_or(&[]).to_vec()))
+            .collect()
+    }
+
+    #[test]
+    fn test_find_packet_node() {
+        let spec = spec();
+        assert_eq!(find_packet_node(&spec, None).unwrap().as_u16(), 1);
+        assert_eq!(find_packet_node(&spec, Some("packet")).unwrap().as_u16(), 1);
+        assert!(find_packet_node(&spec, Some("close")).is_err());
+        assert!(find_packet_node(&spec, Some("missing")).is_err());
+    }
+
+    #[test]
+    fn test_messages_to_graph() {
+        let spec = spec();
+        let node = find_packet_node(&spec, None).unwrap();
+        let messages = vec![b"hello".to_vec(), vec![], b"world!".to_vec()];
+        let graph = messages_to_graph(&spec, node, &messages).unwrap();
+        // 先由 connect 产生 1 号连接，之后的包都借用它；空消息不产生节点
+        assert_eq!(graph.ops_as_slice(), &[0, 1, 1, 1, 1, 1]);
+        let expected = vec![
+            ("connect".to_string(), vec![]),
+            ("packet".to_string(), b"hello".to_vec()),
+            ("packet".to_string(), b"world!".to_vec()),
+        ];
+        assert_eq!(nodes(&graph, &spec), expected);
+
+        // 超过 DataVec 长度上限的消息拆成多个节点
+        let long = vec![0x41; u16::MAX as usize + 3];
+        let graph = messages_to_graph(&spec, node, &[long]).unwrap();
+        let lens: Vec<usize> = nodes(&graph, &spec).iter().map(|(_, m)| m.len()).collect();
+        assert_eq!(lens, vec![0, u16::MAX as usize, 3]);
+    }
+
+    #[test]
+    fn test_detect() {
+        let spec = spec();
+        let dir = temp_dir("detect");
+        let write = |name: &str, data: &[u8]| {
+            let path = dir.join(name);
+            fs::write(&path, data).unwrap();
+            path
+        };
+        let graph = messages_to_graph(&spec, find_packet_node(&spec, None).unwrap(), &[b"hi".to_vec()]).unwrap();
+        let bin = dir.join("seed.bin");
+        graph.write_to_file(bin.to_str().unwrap(), &spec);
+        assert_eq!(detect(&bin).unwrap(), SeedFormat::Bin);
+        assert_eq!(bin_checksum(&fs::read(&bin).unwrap()), Some(spec.checksum));
+
+        let replay = write("replayable", &replayable(&[b"USER a\r\n", b"PASS b\r\n"]));
+        assert_eq!(detect(&replay).unwrap(), SeedFormat::Replayable);
+        assert_eq!(seed_messages(&replay, SeedFormat::Replayable).unwrap(), vec![b"USER a\r\n".to_vec(), b"PASS b\r\n".to_vec()]);
+
+        let raw = write("raw", b"GET / HTTP/1.0\r\n\r\n");
+        assert_eq!(detect(&raw).unwrap(), SeedFormat::Raw);
+        assert_eq!(seed_messages(&raw, SeedFormat::Raw).unwrap(), vec![b"GET / HTTP/1.0\r\n\r\n".to_vec()]);
+        // 长度为 0 的消息不符合 replayable 格式
+        assert_eq!(detect(&write("empty_message", &[0, 0, 0, 0])).unwrap(), SeedFormat::Raw);
+        // 歧义：前 4 字节恰好是其余部分长度的原始文件会被当作 replayable
+        let ambiguous = write("ambiguous", b"\x05\x00\x00\x00hello");
+        assert_eq!(detect(&ambiguous).unwrap(), SeedFormat::Replayable);
+        assert_eq!(seed_messages(&ambiguous, SeedFormat::Replayable).unwrap(), vec![b"hello".to_vec()]);
+
+        let packets = dir.join("packets");
+        fs::create_dir_all(&packets).unwrap();
+        fs::write(packets.join("2"), b"second").unwrap();
+        fs::write(packets.join("1"), b"first").unwrap();
+        fs::write(packets.join(".hidden"), b"skipped").unwrap();
+        fs::write(packets.join("3"), b"").unwrap();
+        assert_eq!(detect(&packets).unwrap(), SeedFormat::PacketDir);
+        assert_eq!(seed_messages(&packets, SeedFormat::PacketDir).unwrap(), vec![b"first".to_vec(), b"second".to_vec()]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_import_seeds() {
+        let spec = spec();
+        let seeds = temp_dir("seeds");
+        let out = temp_dir("out");
+        fs::write(seeds.join("a_raw"), b"hello").unwrap();
+        fs::write(seeds.join("b_replayable"), replayable(&[b"one", b"two"])).unwrap();
+        fs::write(seeds.join("c_empty"), b"").unwrap();
+        let mut other = spec.clone();
+        other.checksum = 1;
+        let graph = messages_to_graph(&other, NodeTypeID::new(1), &[b"x".to_vec()]).unwrap();
+        graph.write_to_file(seeds.join("d_other.bin").to_str().unwrap(), &other);
+
+        assert_eq!(import_seeds(&seeds, &out, &spec, None).unwrap(), 2);
+        let packets = |i: usize| -> Vec<Vec<u8>> {
+            let graph = load_bin(&out.join(format!("seed_{}.bin", i)), &spec).unwrap();
+            nodes(&graph, &spec).into_iter().filter(|(n, _)| n == "packet").map(|(_, m)| m).collect()
+        };
+        assert_eq!(packets(0), vec![b"hello".to_vec()]);
+        assert_eq!(packets(1), vec![b"one".to_vec(), b"two".to_vec()]);
+        assert!(!out.join("seed_2.bin").exists());
+        // 截断或由其他 spec 写出的 .bin 返回错误而不是 panic
+        let data = fs::read(out.join("seed_1.bin")).unwrap();
+        fs::write(out.join("truncated.bin"), &data[..data.len() - 1]).unwrap();
+        assert!(load_bin(&out.join("truncated.bin"), &spec).is_err());
+        assert!(load_bin(&seeds.join("d_other.bin"), &spec).is_err());
+        let _ = fs::remove_dir_all(&seeds);
+        let _ = fs::remove_dir_all(&out);
+    }
+}
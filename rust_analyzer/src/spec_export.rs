@@ -0,0 +1,426 @@
+//! 由推断出的包格式生成细化的 spec.msgp。
+//!
+//! 字段布局相同的包归为同一种消息类型。每种消息类型对应一个节点，其原子是由 DataInt 字段组成的
+//! DataStruct：CONTROL/DELIMITER 字段带有观察到的取值（IntGenerator::Options），其余字段为普通整数。
+//! 包末尾的 DATA 字段长度可变，而 DataStruct 只能包含定长字段，因此作为紧随其后的
+//! `<msg>_payload` 节点（DataVec<u8>）输出。原 spec 的节点与原子全部保留、编号不变，
+//! 未能归类的包在转换后的种子中保持原样。
+
+use crate::inference::{FieldType, InferredPacket, InferredSequence};
+use crate::hash;
+use crate::structured_fuzzer::graph_mutator::generators::IntGenerator;
+use crate::structured_fuzzer::graph_mutator::graph_storage::{GraphStorage, VecGraph};
+use crate::structured_fuzzer::graph_mutator::spec::GraphSpec;
+use crate::structured_fuzzer::graph_mutator::spec_loader::{self, AtomLoader, NodeLoader, SpecLoader};
+
+use std::collections::BTreeSet;
+use std::io;
+
+/// 一个字段最多记录的取值个数，超过时视为自由取值
+const MAX_OPTIONS: usize = 16;
+
+/// 消息头的推断字段：(起始偏移, 结束偏移(含), 类型)
+type Layout = Vec<(usize, usize, FieldType)>;
+
+/// 消息头中的一个整数字段（宽度为 1/2/4/8，更宽的推断字段会被拆开）
+#[derive(Clone, Debug)]
+pub struct HeaderField {
+    pub name: String,
+    pub start: usize,
+    pub width: usize,
+    pub field_type: FieldType,
+    pub options: BTreeSet<u64>, // 仅 CONTROL/DELIMITER；为空表示不限制取值
+}
+
+impl HeaderField {
+    fn value(&self, payload: &[u8]) -> u64 {
+        let mut buf = [0u8; 8];
+        buf[..self.width].copy_from_slice(&payload[self.start..self.start + self.width]);
+        u64::from_le_bytes(buf)
+    }
+
+    fn is_constrained(&self) -> bool {
+        matches!(self.field_type, FieldType::Control | FieldType::Delimiter) && !self.options.is_empty()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MessageType {
+    pub name: String,
+    pub header: Vec<HeaderField>,
+    pub header_len: usize,
+    pub has_payload: bool, // 包末尾是否为变长的 DATA 字段
+    pub members: usize,
+}
+
+impl MessageType {
+    /// payload 能否按本类型解释，能解释时返回匹配上的受约束字段数
+    fn matches(&self, payload: &[u8]) -> Option<usize> {
+        if payload.len() < self.header_len || (!self.has_payload && payload.len() != self.header_len) {
+            return None;
+        }
+        let mut score = 0;
+        for field in self.header.iter().filter(|f| f.is_constrained()) {
+            if !field.options.contains(&field.value(payload)) {
+                return None;
+            }
+            score += 1;
+        }
+        Some(score)
+    }
+}
+
+/// 按 8/4/2/1 字节把 [start, start+len) 拆成整数字段
+fn split_field(prefix: &str, start: usize, len: usize, field_type: FieldType) -> Vec<HeaderField> {
+    let mut fields = vec![];
+    let mut off = start;
+    while off < start + len {
+        let rest = start + len - off;
+        let width = [8, 4, 2, 1].iter().copied().find(|&w| w <= rest).unwrap();
+        fields.push(HeaderField {
+            name: format!("{}_{}_{:x}", prefix, field_type.name().to_lowercase(), off),
+            start: off,
+            width,
+            field_type,
+            options: BTreeSet::new(),
+        });
+        off += width;
+    }
+    fields
+}
+
+/// 包的 (消息头字段区间, 是否有变长负载)：末尾延伸到包尾的 DATA 字段视为负载
+fn layout(packet: &InferredPacket) -> (Layout, bool) {
+    let mut fields: Layout = packet.fields.iter().map(|f| (f.start, f.end, f.field_type)).collect();
+    let has_payload = match fields.last() {
+        Some(&(_, end, FieldType::Data)) => end + 1 >= packet.data.len(),
+        _ => false,
+    };
+    if has_payload {
+        fields.pop();
+    }
+    (fields, has_payload)
+}
+
+/// 把所有序列中的包按布局归类为消息类型，并收集 CONTROL/DELIMITER 字段的取值
+pub fn message_types(sequences: &[InferredSequence]) -> Vec<MessageType> {
+    let mut types: Vec<(Layout, MessageType)> = vec![];
+    for packet in sequences.iter().flat_map(|s| s.packets.iter()) {
+        let (fields, has_payload) = layout(packet);
+        if fields.is_empty() {
+            continue;
+        }
+        let pos = match types.iter().position(|(l, t)| *l == fields && t.has_payload == has_payload) {
+            Some(pos) => pos,
+            None => {
+                let name = format!("msg{}", types.len());
+                let header: Vec<HeaderField> = fields
+                    .iter()
+                    .flat_map(|&(start, end, ty)| split_field(&name, start, end + 1 - start, ty))
+                    .collect();
+                let header_len = fields.last().map(|&(_, end, _)| end + 1).unwrap_or(0);
+                types.push((fields.clone(), MessageType { name, header, header_len, has_payload, members: 0 }));
+                types.len() - 1
+            }
+        };
+        let ty = &mut types[pos].1;
+        ty.members += 1;
+        for field in ty.header.iter_mut() {
+            if matches!(field.field_type, FieldType::Control | FieldType::Delimiter) {
+                let value = field.value(&packet.data);
+                field.options.insert(value);
+            }
+        }
+    }
+    let mut types: Vec<MessageType> = types.into_iter().map(|(_, t)| t).collect();
+    for ty in types.iter_mut() {
+        for field in ty.header.iter_mut() {
+            if field.options.len() > MAX_OPTIONS {
+                field.options.clear();
+            }
+        }
+    }
+    types
+}
+
+/// 细化后的 spec 以及每种消息类型的 (消息节点编号, 负载节点编号)
+pub struct RefinedSpec {
+    pub loader: SpecLoader,
+    pub types: Vec<MessageType>,
+    pub packet_node: usize,            // 原 spec 中承载整个包的节点
+    pub nodes: Vec<(u16, Option<u16>)>,
+}
+
+/// 原 spec 中承载包的节点：给定名字时按名字查找，否则取第一个原子为 Vec 的节点
+pub fn find_packet_node(loader: &SpecLoader, name: Option<&str>) -> Option<usize> {
+    match name {
+        Some(name) => loader.node_id(name),
+        None => loader.nodes.iter().position(|n| {
+            n.atom_id.map(|a| matches!(loader.atomics[a], AtomLoader::Vec { .. })).unwrap_or(false)
+        }),
+    }
+}
+
+/// 在原 spec 的基础上为每种消息类型添加原子与节点，checksum 取新 spec 内容的哈希
+pub fn refine(orig: &SpecLoader, packet_node: usize, types: Vec<MessageType>) -> io::Result<RefinedSpec> {
+    let mut loader = orig.clone();
+    let base = loader.nodes[packet_node].clone();
+    let size_range = match base.atom_id.map(|a| &loader.atomics[a]) {
+        Some(AtomLoader::Vec { size_range, .. }) => *size_range,
+        _ => (0, 0xffff),
+    };
+
+    let byte = loader.add_atom(AtomLoader::Int { name: "refined_u8".into(), size: 1, generators: vec![] });
+    let mut nodes = vec![];
+    for ty in types.iter() {
+        let mut fields = vec![];
+        for field in ty.header.iter() {
+            let generators = if field.is_constrained() {
+                vec![IntGenerator::Options { opts: field.options.iter().copied().collect() }]
+            } else {
+                vec![]
+            };
+            let id = loader.add_atom(AtomLoader::Int { name: field.name.clone(), size: field.width, generators });
+            fields.push((field.name.clone(), id));
+        }
+        let header = loader.add_atom(AtomLoader::Struct { name: format!("{}_hdr", ty.name), fields });
+        let node = loader.add_node(NodeLoader { name: ty.name.clone(), atom_id: Some(header), ..base.clone() });
+        let payload = if ty.has_payload {
+            let atom = loader.add_atom(AtomLoader::Vec {
+                name: format!("{}_payload", ty.name),
+                size_range,
+                dtype: byte,
+                generators: vec![],
+            });
+            let node = loader.add_node(NodeLoader {
+                name: format!("{}_payload", ty.name),
+                atom_id: Some(atom),
+                inputs: vec![],
+                borrows: vec![],
+                outputs: vec![],
+                is_interactive: base.is_interactive,
+            });
+            Some(node as u16)
+        } else {
+            None
+        };
+        nodes.push((node as u16, payload));
+    }
+
+    loader.checksum = 0;
+    let mut buf = vec![];
+    loader.write_to(&mut buf).map_err(io::Error::other)?;
+    loader.checksum = hash::hash64(&buf, buf.len());
+    Ok(RefinedSpec { loader, types, packet_node, nodes })
+}
+
+impl RefinedSpec {
+    pub fn write_to<W: io::Write>(&self, out: &mut W) -> io::Result<()> {
+        self.loader.write_to(out).map_err(io::Error::other)
+    }
+
+    pub fn graph_spec(&self) -> io::Result<GraphSpec> {
+        let mut buf = vec![];
+        self.write_to(&mut buf)?;
+        Ok(spec_loader::load_spec_from_read(&buf[..]))
+    }
+
+    /// 最匹配 payload 的消息类型（受约束字段匹配最多者）
+    fn classify(&self, payload: &[u8]) -> Option<usize> {
+        let mut best: Option<(usize, usize)> = None;
+        for (i, ty) in self.types.iter().enumerate() {
+            if let Some(score) = ty.matches(payload) {
+                if best.map(|(_, s)| score > s).unwrap_or(true) {
+                    best = Some((i, score));
+                }
+            }
+        }
+        best.map(|(i, _)| i)
+    }
+
+    /// 把按原 spec 编码的 graph 转换为按细化 spec 编码，返回新 graph 与成功归类的包数
+    pub fn convert(&self, graph: &VecGraph, orig: &GraphSpec) -> (VecGraph, usize) {
+        let mut ops = vec![];
+        let mut data = vec![];
+        let mut converted = 0;
+        for node in graph.node_iter(orig) {
+            let payload = if node.id.as_usize() == self.packet_node && node.data.len() >= 2 { Some(&node.data[2..]) } else { None };
+            match payload.and_then(|p| self.classify(p).map(|i| (p, i))) {
+                Some((payload, i)) => {
+                    let (msg_node, payload_node) = self.nodes[i];
+                    let header_len = self.types[i].header_len;
+                    ops.push(msg_node);
+                    ops.extend_from_slice(&node.ops[1..]);
+                    data.extend_from_slice(&payload[..header_len]);
+                    if let Some(payload_node) = payload_node {
+                        let tail = &payload[header_len..];
+                        ops.push(payload_node);
+                        data.extend_from_slice(&(tail.len() as u16).to_le_bytes());
+                        data.extend_from_slice(tail);
+                    }
+                    converted += 1;
+                }
+                None => {
+                    ops.extend_from_slice(node.ops);
+                    data.extend_from_slice(node.data);
+                }
+            }
+        }
+        (VecGraph::new(ops, data), converted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{PacketCalibrationResult, SequenceCalibrationResults};
+    use crate::inference::{infer_sequence, BASELINE_OPERATOR};
+    use crate::replay::load_bin;
+    use crate::seed_import::messages_to_graph;
+    use crate::structured_fuzzer::graph_mutator::newtypes::NodeTypeID;
+    use crate::structured_fuzzer::graph_mutator::spec_loader::EdgeLoader;
+
+    const OPS: [&str; 4] = ["LBF", "FBF", "ADD", "SUB"];
+
+    /// 逐偏移、逐算子的结果，cf(offset) 为 0 表示不敏感
+    fn results<F: Fn(usize) -> usize>(packet_id: usize, len: usize, cf: F) -> Vec<PacketCalibrationResult> {
+        let result = |offset: usize, op: &str, cf_index: usize| PacketCalibrationResult {
+            packet_id,
+            offset,
+            stable: true,
+            mutation_operator: op.to_string(),
+            cf_index,
+            vf_index: 0,
+            cfc_index: cf_index,
+            rc_index: 0,
+            width: 1,
+            big_endian: None,
+            edge_delta: None,
+            slot_changes: vec![],
+        };
+        let mut results = vec![result(0, BASELINE_OPERATOR, 0)];
+        for offset in 0..len {
+            results.extend(OPS.iter().map(|op| result(offset, op, cf(offset))));
+        }
+        results
+    }
+
+    fn sequence(sequence_id: usize, payloads: &[&[u8]], results: Vec<PacketCalibrationResult>) -> SequenceCalibrationResults {
+        let mut raw = vec![];
+        for p in payloads.iter() {
+            raw.extend_from_slice(&(p.len() as u16).to_le_bytes());
+            raw.extend_from_slice(p);
+        }
+        SequenceCalibrationResults {
+            sequence_id,
+            cal_time: 0.0,
+            pkt_number: payloads.len(),
+            raw_data: Some(raw.iter().map(|b| format!("{:02x}", b)).collect()),
+            packets_cali_result: results,
+            var_edges: vec![],
+            probed_offsets: vec![],
+        }
+    }
+
+    /// connect 产生连接，packet 借用连接并携带整个包
+    fn orig_spec() -> SpecLoader {
+        let mut loader = SpecLoader { checksum: 0x5eed, nodes: vec![], edges: vec![EdgeLoader { name: "con".into() }], atomics: vec![] };
+        let byte = loader.add_atom(AtomLoader::Int { name: "u8".into(), size: 1, generators: vec![] });
+        let bytes = loader.add_atom(AtomLoader::Vec { name: "bytes".into(), size_range: (0, 0xffff), dtype: byte, generators: vec![] });
+        let node = |name: &str, atom_id, borrows: Vec<u16>, outputs: Vec<u16>| NodeLoader {
+            name: name.into(),
+            atom_id,
+            inputs: vec![],
+            borrows,
+            outputs,
+            is_interactive: false,
+        };
+        loader.add_node(node("connect", None, vec![], vec![0]));
+        loader.add_node(node("packet", Some(bytes), vec![0], vec![]));
+        loader
+    }
+
+    /// "GET:"/"PUT:" 加变长负载的命令，以及没有负载的 "QUIT"
+    fn inferred() -> Vec<InferredSequence> {
+        let command = |o: usize| match o {
+            0..=2 => 5,
+            3 => 6,
+            _ => 0,
+        };
+        let mut first = results(0, 7, command);
+        first.extend(results(1, 4, |_| 7));
+        let second = results(0, 6, command);
+        vec![
+            infer_sequence(&sequence(0, &[b"GET:abc", b"QUIT"], first)),
+            infer_sequence(&sequence(1, &[b"PUT:zz"], second)),
+        ]
+    }
+
+    /// 每个节点的名字，以及把消息节点与其后的负载节点拼回去得到的包
+    fn packets(graph: &VecGraph, spec: &GraphSpec) -> (Vec<String>, Vec<Vec<u8>>) {
+        let mut names = vec![];
+        let mut packets: Vec<Vec<u8>> = vec![];
+        for node in graph.node_iter(spec) {
+            let name = spec.get_node(node.id).unwrap().name.clone();
+            if name == "packet" {
+                packets.push(node.data[2..].to_vec());
+            } else if name.ends_with("_payload") {
+                packets.last_mut().unwrap().extend_from_slice(&node.data[2..]);
+            } else if name.starts_with("msg") {
+                packets.push(node.data.to_vec());
+            }
+            names.push(name);
+        }
+        (names, packets)
+    }
+
+    #[test]
+    fn test_message_types() {
+        let types = message_types(&inferred());
+        assert_eq!(types.len(), 2);
+        let (cmd, quit) = (&types[0], &types[1]);
+        assert_eq!((cmd.header_len, cmd.has_payload, cmd.members), (4, true, 2));
+        let header: Vec<(usize, usize, FieldType)> = cmd.header.iter().map(|f| (f.start, f.width, f.field_type)).collect();
+        // 命令字与分隔符的区间不足以切分，合成一个 4 字节的 CONTROL 字段
+        assert_eq!(header, vec![(0, 4, FieldType::Control)]);
+        // 整数字段按小端取值
+        let le = |b: &[u8]| b.iter().rev().fold(0u64, |v, &x| (v << 8) | x as u64);
+        assert_eq!(cmd.header[0].options.iter().copied().collect::<Vec<u64>>(), vec![le(b"GET:"), le(b"PUT:")]);
+        assert_eq!(cmd.header[0].value(b"PUT:zz"), le(b"PUT:"));
+        assert_eq!((quit.header_len, quit.has_payload, quit.members), (4, false, 1));
+        assert_eq!(quit.header[0].width, 4);
+
+        assert_eq!(cmd.matches(b"GET:"), Some(1));
+        assert_eq!(cmd.matches(b"GET;x"), None);
+        assert_eq!(cmd.matches(b"GE"), None);
+        assert_eq!(quit.matches(b"QUIT"), Some(1));
+        assert_eq!(quit.matches(b"QUITS"), None);
+    }
+
+    #[test]
+    fn test_convert_round_trip() {
+        let orig = orig_spec();
+        let packet_node = find_packet_node(&orig, None).unwrap();
+        assert_eq!(packet_node, 1);
+        let refined = refine(&orig, packet_node, message_types(&inferred())).unwrap();
+        assert_ne!(refined.loader.checksum, orig.checksum);
+        // 原 spec 的节点编号不变
+        assert_eq!(&refined.loader.nodes[..2], &orig.nodes[..]);
+
+        let orig_spec = orig.clone().to_graph_spec();
+        let messages = vec![b"GET:abc".to_vec(), b"QUIT".to_vec(), b"HELLO".to_vec(), b"PUT:".to_vec()];
+        let seed = messages_to_graph(&orig_spec, NodeTypeID::new(packet_node as u16), &messages).unwrap();
+        let new_spec = refined.graph_spec().unwrap();
+        let (graph, converted) = refined.convert(&seed, &orig_spec);
+        assert_eq!(converted, 3);
+
+        let path = std::env::temp_dir().join(format!("spec_export_{}.bin", std::process::id()));
+        graph.write_to_file(path.to_str().unwrap(), &new_spec);
+        let loaded = load_bin(&path, &new_spec).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let (names, payloads) = packets(&loaded, &new_spec);
+        assert_eq!(names, vec!["connect", "msg0", "msg0_payload", "msg1", "packet", "msg0", "msg0_payload"]);
+        // 拼回的各包负载与转换前一致
+        assert_eq!(payloads, messages);
+    }
+}
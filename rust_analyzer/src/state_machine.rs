@@ -0,0 +1,228 @@
+//! 协议状态机提取：校准时第 k 个包的基准测量在「快照到第 k 个包之前、再执行第 k 个包」的前缀上进行，
+//! 其 (cf, vf) 类可以看作服务端处理完该前缀后的状态。把所有序列的逐前缀状态合并成一张图：
+//! 节点为 (cf, vf) 类（外加初始状态 init），边为消息类型（取自消息类型目录），并记录经过的次数。
+
+use crate::analyzer::SequenceCalibrationResults;
+use crate::catalog::Catalog;
+use crate::inference;
+use crate::result_stream;
+
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 没有归入任何消息类型的包（负载为空）在边上的标签
+const UNTYPED: &str = "-";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StateNode {
+    pub id: usize,
+    pub cf_index: Option<usize>, // 初始状态为 None
+    pub vf_index: Option<usize>,
+    pub visits: usize,
+    pub sequences: usize,           // 到达过该状态的序列数
+    pub shortest_path: Vec<String>, // 到达该状态的最短消息类型序列
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StateEdge {
+    pub from: usize,
+    pub to: usize,
+    pub message: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct StateMachine {
+    pub nodes: Vec<StateNode>,
+    pub edges: Vec<StateEdge>,
+}
+
+impl StateNode {
+    fn new(id: usize, class: Option<(usize, usize)>, path: Vec<String>) -> Self {
+        Self {
+            id,
+            cf_index: class.map(|c| c.0),
+            vf_index: class.map(|c| c.1),
+            visits: 0,
+            sequences: 0,
+            shortest_path: path,
+        }
+    }
+
+    fn label(&self) -> String {
+        match (self.cf_index, self.vf_index) {
+            (Some(cf), Some(vf)) => format!("cf {} / vf {}\\n{} visits", cf, vf, self.visits),
+            _ => format!("init\\n{} sequences", self.sequences),
+        }
+    }
+}
+
+/// 按消息类型目录给包打标签，合并所有序列的逐前缀状态。
+/// 某个包缺少基准测量时，该序列在此处截断
+pub fn build(sequences: &[(PathBuf, SequenceCalibrationResults)], catalog: &Catalog) -> StateMachine {
+    let mut nodes = vec![StateNode::new(0, None, vec![])];
+    let mut edges: Vec<StateEdge> = vec![];
+    let mut node_ids: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut edge_ids: HashMap<(usize, usize, String), usize> = HashMap::new();
+
+    for (_, results) in sequences.iter() {
+        let raw = results.raw_data.as_deref().and_then(result_stream::decode_hex).unwrap_or_default();
+        let payloads = inference::split_raw_data(&raw);
+        let mut state = 0;
+        let mut path: Vec<String> = vec![];
+        let mut visited = BTreeSet::new();
+        visited.insert(state);
+        nodes[state].visits += 1;
+        for packet_id in 0..results.pkt_number {
+            let base = match inference::baseline(results, packet_id) {
+                Some(base) => base,
+                None => break,
+            };
+            let payload = payloads.get(packet_id).map(|p| &p[..]).unwrap_or(&[]);
+            let message = catalog
+                .type_of(payload, base.cf_index)
+                .map(|t| t.name.clone())
+                .unwrap_or_else(|| UNTYPED.to_string());
+            path.push(message.clone());
+
+            let class = (base.cf_index, base.vf_index);
+            let next = *node_ids.entry(class).or_insert_with(|| {
+                nodes.push(StateNode::new(nodes.len(), Some(class), path.clone()));
+                nodes.len() - 1
+            });
+            if path.len() < nodes[next].shortest_path.len() {
+                nodes[next].shortest_path = path.clone();
+            }
+            nodes[next].visits += 1;
+            visited.insert(next);
+
+            let edge = *edge_ids.entry((state, next, message.clone())).or_insert_with(|| {
+                edges.push(StateEdge { from: state, to: next, message, count: 0 });
+                edges.len() - 1
+            });
+            edges[edge].count += 1;
+            state = next;
+        }
+        for id in visited {
+            nodes[id].sequences += 1;
+        }
+    }
+    StateMachine { nodes, edges }
+}
+
+impl StateMachine {
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph protocol {\n    node [shape=box];\n");
+        for node in self.nodes.iter() {
+            out += &format!("    s{} [label=\"{}\"];\n", node.id, node.label());
+        }
+        for edge in self.edges.iter() {
+            out += &format!("    s{} -> s{} [label=\"{} ({})\"];\n", edge.from, edge.to, edge.message, edge.count);
+        }
+        out += "}\n";
+        out
+    }
+
+    /// 在 dir 下写出 state_machine.json 与 state_machine.dot，返回写出的文件
+    pub fn write(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let json_path = dir.join("state_machine.json");
+        serde_json::to_writer_pretty(fs::File::create(&json_path)?, self)?;
+        let dot_path = dir.join("state_machine.dot");
+        fs::write(&dot_path, self.to_dot())?;
+        Ok(vec![json_path, dot_path])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::PacketCalibrationResult;
+    use crate::catalog::{self, DEFAULT_PREFIX_LEN};
+    use crate::inference::BASELINE_OPERATOR;
+
+    fn result(packet_id: usize, offset: usize, op: &str, cf_index: usize) -> PacketCalibrationResult {
+        PacketCalibrationResult {
+            packet_id,
+            offset,
+            stable: true,
+            mutation_operator: op.to_string(),
+            cf_index,
+            vf_index: 0,
+            cfc_index: cf_index,
+            rc_index: 0,
+            width: 1,
+            big_endian: None,
+            edge_delta: None,
+            slot_changes: vec![],
+        }
+    }
+
+    /// 每个包为 (负载, 基准 cf 类)，cf 为 None 的包没有基准测量
+    fn sequence(sequence_id: usize, packets: &[(&[u8], Option<usize>)]) -> (PathBuf, SequenceCalibrationResults) {
+        let mut raw = vec![];
+        let mut results = vec![];
+        for (packet_id, &(payload, cf)) in packets.iter().enumerate() {
+            raw.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+            raw.extend_from_slice(payload);
+            if let Some(cf) = cf {
+                results.push(result(packet_id, 0, BASELINE_OPERATOR, cf));
+            }
+            for offset in 0..payload.len() {
+                results.push(result(packet_id, offset, "LBF", cf.unwrap_or(0)));
+            }
+        }
+        let results = SequenceCalibrationResults {
+            sequence_id,
+            cal_time: 0.0,
+            pkt_number: packets.len(),
+            raw_data: Some(raw.iter().map(|b| format!("{:02x}", b)).collect()),
+            packets_cali_result: results,
+            var_edges: vec![],
+            probed_offsets: vec![],
+        };
+        (PathBuf::from(format!("{}.json", sequence_id)), results)
+    }
+
+    #[test]
+    fn test_build() {
+        let sequences = vec![
+            sequence(0, &[(b"AAAA", Some(1)), (b"BBBB", Some(2)), (b"CCCC", Some(3))]),
+            // 第 2 个包没有基准测量，序列在此截断，之后的 EEEE 不计入
+            sequence(1, &[(b"AAAA", Some(1)), (b"BBBB", Some(2)), (b"DDDD", None), (b"EEEE", Some(4))]),
+            // 与第一条序列到达同一状态，但路径更短
+            sequence(2, &[(b"CCCC", Some(3))]),
+        ];
+        let catalog = catalog::build(&sequences, DEFAULT_PREFIX_LEN);
+        let names: Vec<&str> = catalog.types.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["type0", "type1", "type2", "type3"]);
+        assert_eq!(catalog.types[3].prefix, "45454545");
+
+        let machine = build(&sequences, &catalog);
+        let nodes: Vec<(Option<usize>, usize, usize, Vec<&str>)> = machine
+            .nodes
+            .iter()
+            .map(|n| (n.cf_index, n.visits, n.sequences, n.shortest_path.iter().map(|s| s.as_str()).collect()))
+            .collect();
+        assert_eq!(
+            nodes,
+            vec![
+                (None, 3, 3, vec![]),
+                (Some(1), 2, 2, vec!["type0"]),
+                (Some(2), 2, 2, vec!["type0", "type1"]),
+                (Some(3), 2, 2, vec!["type2"]),
+            ]
+        );
+        assert!(machine.nodes.iter().enumerate().all(|(i, n)| n.id == i));
+        let edges: Vec<(usize, usize, &str, usize)> =
+            machine.edges.iter().map(|e| (e.from, e.to, e.message.as_str(), e.count)).collect();
+        assert_eq!(edges, vec![(0, 1, "type0", 2), (1, 2, "type1", 2), (2, 3, "type2", 1), (0, 3, "type2", 1)]);
+
+        let dot = machine.to_dot();
+        assert!(dot.contains("s0 [label=\"init\\n3 sequences\"];"));
+        assert!(dot.contains("s3 [label=\"cf 3 / vf 0\\n2 visits\"];"));
+        assert!(dot.contains("s0 -> s1 [label=\"type0 (2)\"];"));
+    }
+}
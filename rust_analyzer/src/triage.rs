@@ -0,0 +1,269 @@
+//! crash 分桶去重：按「规范化后的崩溃描述 + 退出类型 + 最后一个包的覆盖率哈希」归类，
+//! 每类一个 `crashes/<bucket>/` 目录，保存最小的代表输入、原始描述和命中统计，
+//! 所有类的汇总写在 `crashes/index.json`。
+
+use crate::fuzz_runner::ExitReason;
+use crate::hash;
+use crate::structured_fuzzer::graph_mutator::graph_storage::{GraphStorage, VecGraph};
+use crate::structured_fuzzer::GraphSpec;
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DESCRIPTION_LIMIT: usize = 200;
+
+/// 需要分桶的退出原因（非正常退出，FuzzerError 除外）
+pub fn is_triaged(reason: &ExitReason) -> bool {
+    !reason.is_normal() && *reason != ExitReason::FuzzerError
+}
+
+/// 由退出原因得到原始描述：Crash/InvalidWriteToPayload 为 aux buffer 的 misc 内容
+pub fn raw_description(reason: &ExitReason) -> &[u8] {
+    match reason {
+        ExitReason::Crash(desc) | ExitReason::InvalidWriteToPayload(desc) => desc,
+        _ => &[],
+    }
+}
+
+fn is_address(token: &str) -> bool {
+    let token = token.trim_matches(|c: char| !c.is_ascii_alphanumeric());
+    token.len() >= 6 && token.chars().all(|c| c.is_ascii_hexdigit()) && token.chars().any(|c| c.is_ascii_digit())
+}
+
+/// 规范化崩溃描述：取第一行非空内容，地址、十六进制数与十进制数分别替换为 `?`、`0x?`、`N`，
+/// 合并空白并截断，使同一个 bug 在不同输入下得到相同的描述
+pub fn normalize_description(desc: &[u8]) -> String {
+    let text = String::from_utf8_lossy(desc);
+    let line = text.lines().map(|l| l.trim()).find(|l| !l.is_empty()).unwrap_or("");
+    let tokens: Vec<String> = line
+        .split_whitespace()
+        .map(|token| if is_address(token) { "?".to_string() } else { token.to_string() })
+        .collect();
+    let line = tokens.join(" ");
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '0' && chars.get(i + 1) == Some(&'x') {
+            let mut j = i + 2;
+            while j < chars.len() && chars[j].is_ascii_hexdigit() {
+                j += 1;
+            }
+            out.push_str("0x?");
+            i = j;
+        } else if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            out.push('N');
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+    out.chars().take(DESCRIPTION_LIMIT).collect()
+}
+
+/// 最后一个包覆盖率的哈希：edges 为只在最后一个包执行时新增的 bitmap 下标（升序）
+pub fn coverage_hash(edges: &[usize]) -> u64 {
+    let bytes: Vec<u8> = edges.iter().flat_map(|e| (*e as u64).to_le_bytes().to_vec()).collect();
+    hash::hash64(&bytes, bytes.len())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CrashSignature {
+    pub exit_type: String,
+    pub description: String, // 规范化后的描述
+    pub cov_hash: u64,
+}
+
+impl CrashSignature {
+    pub fn new(reason: &ExitReason, last_packet_edges: &[usize]) -> Self {
+        Self {
+            exit_type: reason.name().to_string(),
+            description: normalize_description(raw_description(reason)),
+            cov_hash: coverage_hash(last_packet_edges),
+        }
+    }
+
+    /// 目录名：退出类型_签名哈希
+    pub fn bucket(&self) -> String {
+        let key = format!("{}\n{}\n{:016x}", self.exit_type, self.description, self.cov_hash);
+        format!("{}_{:016x}", self.exit_type, hash::hash64(key.as_bytes(), key.len()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrashBucket {
+    pub bucket: String,
+    pub signature: CrashSignature,
+    pub hits: usize,
+    pub first_seen: u64, // unix 时间戳（秒）
+    pub last_seen: u64,
+    pub input_nodes: usize, // 代表输入的节点数与数据长度，出现更小的输入时替换
+    pub input_bytes: usize,
+}
+
+/// 所有分析线程共享的 crash 分桶表
+#[derive(Clone)]
+pub struct CrashTriage {
+    dir: PathBuf,
+    buckets: Arc<Mutex<BTreeMap<String, CrashBucket>>>,
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, value)?;
+    Ok(())
+}
+
+impl CrashTriage {
+    /// 打开 workdir/crashes，已有 index.json 时（续跑）在其基础上继续计数
+    pub fn new(workdir: &str) -> Self {
+        let dir = Path::new(workdir).join("crashes");
+        let buckets = File::open(dir.join("index.json"))
+            .ok()
+            .and_then(|file| serde_json::from_reader::<_, Vec<CrashBucket>>(file).ok())
+            .map(|list| list.into_iter().map(|b| (b.bucket.clone(), b)).collect())
+            .unwrap_or_default();
+        Self { dir, buckets: Arc::new(Mutex::new(buckets)) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+
+    /// 记录一次 crash，返回所属的分桶以及是否为新的分桶
+    pub fn record(&self, signature: CrashSignature, input: &VecGraph, spec: &GraphSpec, raw_desc: &[u8]) -> io::Result<(CrashBucket, bool)> {
+        let mut buckets = self.buckets.lock().unwrap();
+        let name = signature.bucket();
+        let nodes = input.node_len(spec);
+        let bytes = input.data_len();
+        let time = now();
+        let is_new = !buckets.contains_key(&name);
+        let bucket = buckets.entry(name.clone()).or_insert_with(|| CrashBucket {
+            bucket: name.clone(),
+            signature,
+            hits: 0,
+            first_seen: time,
+            last_seen: time,
+            input_nodes: nodes,
+            input_bytes: bytes,
+        });
+        bucket.hits += 1;
+        bucket.last_seen = time;
+
+        let bucket_dir = self.dir.join(&name);
+        fs::create_dir_all(&bucket_dir)?;
+        if is_new || (nodes, bytes) < (bucket.input_nodes, bucket.input_bytes) {
+            bucket.input_nodes = nodes;
+            bucket.input_bytes = bytes;
+            input.write_to_file(bucket_dir.join("input.bin").to_str().unwrap(), spec);
+            fs::write(bucket_dir.join("description.log"), format!("{}\n", String::from_utf8_lossy(raw_desc)))?;
+        }
+        let bucket = bucket.clone();
+        write_json(&bucket_dir.join("bucket.json"), &bucket)?;
+        let index: Vec<&CrashBucket> = buckets.values().collect();
+        write_json(&self.dir.join("index.json"), &index)?;
+        Ok((bucket, is_new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn crash(desc: &str) -> ExitReason {
+        ExitReason::Crash(desc.as_bytes().to_vec())
+    }
+
+    fn spec() -> GraphSpec {
+        let mut spec = GraphSpec::new();
+        spec.node_type("op", None, vec![], vec![], vec![]);
+        spec.checksum = 0x7a;
+        spec
+    }
+
+    fn graph(nodes: usize) -> VecGraph {
+        VecGraph::new(vec![0; nodes], vec![])
+    }
+
+    #[test]
+    fn test_normalize_description() {
+        assert_eq!(
+            normalize_description(b"\n  SEGV at 7ffc1234abcd in parse+0x1f, len 42  \nframe #1 ..."),
+            "SEGV at ? in parse+0x?, len N"
+        );
+        assert_eq!(normalize_description(b"assert 0xDEAD failed"), "assert 0x? failed");
+        assert_eq!(normalize_description(b""), "");
+        assert_eq!(normalize_description("x".repeat(500).as_bytes()).len(), DESCRIPTION_LIMIT);
+        // 短的十六进制串（如函数名 abc/feed）不当作地址
+        assert_eq!(normalize_description(b"abc feed 123abc4"), "abc feed ?");
+    }
+
+    #[test]
+    fn test_bucket() {
+        let a = CrashSignature::new(&crash("SEGV at 7ffc1234abcd in parse+0x1f, len 42"), &[3, 9]);
+        let b = CrashSignature::new(&crash("SEGV at 55d0aa10ff00 in parse+0x2c, len 7\nother"), &[3, 9]);
+        assert_eq!(a, b);
+        assert_eq!(a.bucket(), b.bucket());
+        assert!(a.bucket().starts_with("crash_"));
+        // 最后一个包的覆盖率、描述或退出类型不同则分到不同的桶
+        assert_ne!(CrashSignature::new(&crash("SEGV at 7ffc1234abcd"), &[3]).bucket(), CrashSignature::new(&crash("SEGV at 7ffc1234abcd"), &[4]).bucket());
+        assert_ne!(CrashSignature::new(&crash("SEGV"), &[]).bucket(), CrashSignature::new(&crash("ABRT"), &[]).bucket());
+        assert_ne!(CrashSignature::new(&crash(""), &[]).bucket(), CrashSignature::new(&ExitReason::Timeout, &[]).bucket());
+        assert!(is_triaged(&ExitReason::Timeout));
+        assert!(is_triaged(&ExitReason::InvalidWriteToPayload(vec![])));
+        assert!(!is_triaged(&ExitReason::Normal(0)));
+        assert!(!is_triaged(&ExitReason::FuzzerError));
+    }
+
+    #[test]
+    fn test_record() {
+        let workdir = std::env::temp_dir().join(format!("triage_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&workdir);
+        let workdir = workdir.to_str().unwrap().to_string();
+        let spec = spec();
+        let triage = CrashTriage::new(&workdir);
+        assert_eq!(triage.len(), 0);
+        let signature = || CrashSignature::new(&crash("SEGV at 7ffc1234abcd"), &[1]);
+        let bucket_dir = Path::new(&workdir).join("crashes").join(signature().bucket());
+
+        let (bucket, is_new) = triage.record(signature(), &graph(3), &spec, b"SEGV at 7ffc1234abcd").unwrap();
+        assert!(is_new);
+        assert_eq!((bucket.hits, bucket.input_nodes), (1, 3));
+        // 更小的输入替换代表输入，更大的不替换
+        let (bucket, is_new) = triage.record(signature(), &graph(1), &spec, b"SEGV at 55d0aa10ff00").unwrap();
+        assert!(!is_new);
+        assert_eq!((bucket.hits, bucket.input_nodes), (2, 1));
+        let (bucket, _) = triage.record(signature(), &graph(2), &spec, b"SEGV at 000011112222").unwrap();
+        assert_eq!((bucket.hits, bucket.input_nodes), (3, 1));
+        let input = VecGraph::new_from_bin_file(bucket_dir.join("input.bin").to_str().unwrap(), &spec);
+        assert_eq!(input.node_len(&spec), 1);
+        assert_eq!(fs::read_to_string(bucket_dir.join("description.log")).unwrap(), "SEGV at 55d0aa10ff00\n");
+
+        let (other, is_new) = triage.record(CrashSignature::new(&ExitReason::Timeout, &[]), &graph(1), &spec, b"").unwrap();
+        assert!(is_new);
+        assert_eq!(triage.len(), 2);
+
+        // 续跑时从 index.json 恢复计数
+        let resumed = CrashTriage::new(&workdir);
+        assert_eq!(resumed.len(), 2);
+        let (bucket, is_new) = resumed.record(signature(), &graph(5), &spec, b"").unwrap();
+        assert!(!is_new);
+        assert_eq!((bucket.hits, bucket.input_nodes), (4, 1));
+        assert_eq!(resumed.record(other.signature, &graph(1), &spec, b"").unwrap().0.hits, 2);
+        let _ = fs::remove_dir_all(&workdir);
+    }
+}
@@ -4,7 +4,8 @@ use crate::graph_mutator::spec::GraphSpec;
 use crate::custom_dict::CustomDict;
 
 use crate::data_buff::DataBuff;
-use crate::primitive_mutator::mutator::{PrimitiveMutator, PrimitiveMutatorDefenite};
+use crate::primitive_mutator::calibration::CalibrationOperator;
+use crate::primitive_mutator::mutator::PrimitiveMutator;
 use crate::graph_mutator::generators::{IntGenerator, VecGenerator};
 use crate::random::distributions::Distributions;
 
@@ -60,46 +61,16 @@ pub trait AtomicDataType {
         mutator: &PrimitiveMutator,
         dist: &Distributions
     );
-    fn append_mutated_full_bit_flip(
+    ///添加经校准算子在 off 处变异后的数据，算子不适用于该偏移时原样复制
+    fn append_calibrated(
         &self,
         data: &[u8],
         storage: &mut dyn GraphMutationTarget,
         spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
+        op: &dyn CalibrationOperator,
         off: usize,
     );
 
-    fn append_mutated_lowest_bit_flip(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    );
-
-    fn append_mutated_addition(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    );
-
-
-
-    fn append_mutated_subtraction(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    );
-
-
-
     fn min_data_size(&self) -> usize {
         return self.size().min_data_size();
     }
@@ -181,69 +152,20 @@ impl AtomicDataType for DataInt {
     }
     
 
-    fn append_mutated_full_bit_flip(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        prim_mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        // 针对 DataInt，直接复制整个数据
-        let copy = storage.append_data(data).unwrap();
-        let mut buff = DataBuff::new(copy, copy.len());
-        // 随机选择一个合法的偏移，这里简单选择 0（也可以根据需要随机）
-        prim_mutator
-        .gen_full_bit_flip_at_offset(&mut buff, off)
-        .apply(&mut buff);
-    }
-
-    fn append_mutated_lowest_bit_flip(
+    fn append_calibrated(
         &self,
         data: &[u8],
         storage: &mut dyn GraphMutationTarget,
         _spec: &GraphSpec,
-        prim_mutator: &PrimitiveMutatorDefenite,
+        op: &dyn CalibrationOperator,
         off: usize,
     ) {
-        // 针对 DataInt，直接复制整个数据
         let copy = storage.append_data(data).unwrap();
-        let mut buff = DataBuff::new(copy, copy.len());
-        // 随机选择一个合法的偏移，这里简单选择 0（也可以根据需要随机）
-        prim_mutator.gen_lowest_bit_flip_at_offset(&mut buff, off)
-        .apply(&mut buff);
-    }
-
-    fn append_mutated_subtraction(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        prim_mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        // 针对 DataInt，直接复制整个数据
-        let copy = storage.append_data(data).unwrap();
-        let mut buff = DataBuff::new(copy, copy.len());
-        // 随机选择一个合法的偏移，这里简单选择 0（也可以根据需要随机）
-        prim_mutator.gen_subtraction_at_offset(&mut buff, off)
-        .apply(&mut buff);
-    }
-
-    fn append_mutated_addition(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        prim_mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        // 针对 DataInt，直接复制整个数据
-        let copy = storage.append_data(data).unwrap();
-        let mut buff = DataBuff::new(copy, copy.len());
-        // 随机选择一个合法的偏移，这里简单选择 0（也可以根据需要随机）
-        prim_mutator.gen_addition_at_offset(&mut buff, off)
-        .apply(&mut buff);
+        let len = copy.len();
+        let mut buff = DataBuff::new(copy, len);
+        if op.is_applicable(&buff, off) {
+            op.apply(&mut buff, off);
+        }
     }
 
 
@@ -357,12 +279,12 @@ impl AtomicDataType for DataVec {
         }
     }
 
-    fn append_mutated_full_bit_flip(
+    fn append_calibrated(
         &self,
         data: &[u8],
         storage: &mut dyn GraphMutationTarget,
         _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
+        op: &dyn CalibrationOperator,
         off: usize,
     ) {
         // 校验头部，并保持头部不变，变异仅针对负载部分（从索引2开始）
@@ -371,72 +293,11 @@ impl AtomicDataType for DataVec {
             data.len()
         );
         let copy = storage.append_data(data).unwrap();
-        // 负载部分长度：
         let payload_len = copy.len() - 2;
-        let mut buf = DataBuff::new(&mut copy[2..], payload_len);
-        // 下面以全比特翻转举例，其他算子类似调用
-        mutator
-        .gen_full_bit_flip_at_offset(&mut buf, off)
-        .apply(&mut buf);
-    }
-
-    fn append_mutated_lowest_bit_flip(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        // 同 DataVec 上处理头部，直接对 payload 部分执行最低位变异
-        assert_eq!(
-            data[0] as usize + (((data[1] as usize)) << 8) + 2,
-            data.len()
-        );
-        let copy = storage.append_data(data).unwrap();
-        let payload_len = copy.len() - 2;
-        let mut buf = DataBuff::new(&mut copy[2..], payload_len);
-        mutator.gen_lowest_bit_flip_at_offset(&mut buf, off)
-        .apply(&mut buf);
-    }
-
-    fn append_mutated_subtraction(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        assert_eq!(
-            data[0] as usize + (((data[1] as usize)) << 8) + 2,
-            data.len()
-        );
-        let copy = storage.append_data(data).unwrap();
-        let payload_len = copy.len() - 2;
-        let mut buf = DataBuff::new(&mut copy[2..], payload_len);
-        mutator.gen_subtraction_at_offset(&mut buf, off)
-        .apply(&mut buf);
-    }
-
-    fn append_mutated_addition(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        assert_eq!(
-            data[0] as usize + (((data[1] as usize)) << 8) + 2,
-            data.len()
-        );
-        let copy = storage.append_data(data).unwrap();
-        let payload_len = copy.len() - 2;
-        let mut buf = DataBuff::new(&mut copy[2..], payload_len);
-        mutator.gen_addition_at_offset(&mut buf, off)
-        .apply(&mut buf);
-
+        let mut buff = DataBuff::new(&mut copy[2..], payload_len);
+        if op.is_applicable(&buff, off) {
+            op.apply(&mut buff, off);
+        }
     }
 
 
@@ -523,66 +384,22 @@ impl AtomicDataType for DataStruct {
         mutator.mutate(&mut DataBuff::new(&mut copy, len), Some(dict), dist);
     }
 
-    fn append_mutated_full_bit_flip(
+    fn append_calibrated(
         &self,
         data: &[u8],
         storage: &mut dyn GraphMutationTarget,
         _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
+        op: &dyn CalibrationOperator,
         off: usize,
     ) {
-        let mut copy = storage.append_data(data).unwrap();
-        let len = copy.len();
-        let mut buff = DataBuff::new(&mut copy, len);
-        mutator.gen_full_bit_flip_at_offset(&mut buff, off)
-        .apply(&mut buff);
-
-    }
-
-    fn append_mutated_lowest_bit_flip(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        let mut copy = storage.append_data(data).unwrap();
-        let len = copy.len();
-        let mut buff = DataBuff::new(&mut copy, len);
-        mutator.gen_lowest_bit_flip_at_offset(&mut buff, off)
-        .apply(&mut buff);
-    }
-
-    fn append_mutated_subtraction(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        let mut copy = storage.append_data(data).unwrap();
+        let copy = storage.append_data(data).unwrap();
         let len = copy.len();
-        let mut buff = DataBuff::new(&mut copy, len);
-        mutator.gen_subtraction_at_offset(&mut buff, off)
-        .apply(&mut buff);
+        let mut buff = DataBuff::new(copy, len);
+        if op.is_applicable(&buff, off) {
+            op.apply(&mut buff, off);
+        }
     }
 
-    fn append_mutated_addition(
-        &self,
-        data: &[u8],
-        storage: &mut dyn GraphMutationTarget,
-        _spec: &GraphSpec,
-        mutator: &PrimitiveMutatorDefenite,
-        off: usize,
-    ) {
-        let mut copy = storage.append_data(data).unwrap();
-        let len = copy.len();
-        let mut buff = DataBuff::new(&mut copy, len);
-        mutator.gen_addition_at_offset(&mut buff, off)
-        .apply(&mut buff);
-    }
 
     fn data_inspect(&self, data:&[u8], spec: &GraphSpec) -> String{ 
         let mut res = "{\\l".to_string();
@@ -5,7 +5,8 @@ use crate::graph_mutator::spec::{GraphSpec, NodeSpec};
 use crate::custom_dict::CustomDict;
 
 use crate::data_buff::DataBuff;
-use crate::primitive_mutator::mutator::{PrimitiveMutator,PrimitiveMutatorDefenite};
+use crate::primitive_mutator::calibration::CalibrationOperator;
+use crate::primitive_mutator::mutator::PrimitiveMutator;
 use crate::random::distributions::Distributions;
 use crate::mutator::MutatorSnapshotState;
 
@@ -467,12 +468,12 @@ impl GraphBuilder {
         return Ok(());
     }
     
-    /// 为节点添加全比特翻转变异数据
-    pub fn append_node_mutated_full_bit_flip<S: GraphStorage>(
+    /// 为节点添加经校准算子变异后的数据，off 为数据中的偏移位置
+    pub fn append_node_calibrated<S: GraphStorage>(
         &mut self,
         node: &GraphNode,
         off: usize,
-        mutator: &PrimitiveMutatorDefenite,
+        op: &dyn CalibrationOperator,
         graph: &mut S,
         dist: &Distributions
     ) {
@@ -484,10 +485,8 @@ impl GraphBuilder {
             // 如果节点定义中有 data 字段，则获取对应的 Data 对象
             if let Some(dtype) = ntype.data {
                 if let Ok(dat) = self.spec.get_data(dtype) {
-                    // 调用相应的接口方法，实现全比特翻转变异，
-                    // off 参数表示在数据中的偏移位置
-                    dat.atomic_type.append_mutated_full_bit_flip(
-                        node.data, graph, &self.spec, mutator, off
+                    dat.atomic_type.append_calibrated(
+                        node.data, graph, &self.spec, op, off
                     );
                 } else {
                     panic!("Node {} has invalid data type {:?}", ntype.name, ntype.data);
@@ -495,85 +494,4 @@ impl GraphBuilder {
             }
         }
     }
-
-    /// 为节点添加最低位翻转变异数据
-    pub fn append_node_mutated_lowest_bit_flip<S: GraphStorage>(
-        &mut self,
-        node: &GraphNode,
-        off: usize,
-        mutator: &PrimitiveMutatorDefenite,
-        graph: &mut S,
-        dist: &Distributions
-    ) {
-        if self.can_append_node(node, graph) {
-            self.append_slice(&node.ops, graph, dist);
-            let ntype = self.spec.get_node(node.id).unwrap();
-            if let Some(dtype) = ntype.data {
-                if let Ok(dat) = self.spec.get_data(dtype) {
-                    dat.atomic_type.append_mutated_lowest_bit_flip(
-                        node.data, graph, &self.spec, mutator, off
-                    );
-                } else {
-                    panic!("Node {} has invalid data type {:?}", ntype.name, ntype.data);
-                }
-            }
-        }
-    }
-
-
-
-
-
-    /// 为节点添加数值加法扰动变异数据
-    pub fn append_node_mutated_addition<S: GraphStorage>(
-        &mut self,
-        node: &GraphNode,
-        off: usize,
-        mutator: &PrimitiveMutatorDefenite,
-        graph: &mut S,
-        dist: &Distributions
-    ) {
-        if self.can_append_node(node, graph) {
-            self.append_slice(&node.ops, graph, dist);
-            let ntype = self.spec.get_node(node.id).unwrap();
-            if let Some(dtype) = ntype.data {
-                if let Ok(dat) = self.spec.get_data(dtype) {
-                    dat.atomic_type.append_mutated_addition(
-                        node.data, graph, &self.spec, mutator, off
-                    );
-                } else {
-                    panic!("Node {} has invalid data type {:?}", ntype.name, ntype.data);
-                }
-            }
-        }
-    }
-
-    /// 为节点添加数值减法扰动变异数据
-    pub fn append_node_mutated_subtraction<S: GraphStorage>(
-        &mut self,
-        node: &GraphNode,
-        off: usize,
-        mutator: &PrimitiveMutatorDefenite,
-        graph: &mut S,
-        dist: &Distributions
-    ) {
-        if self.can_append_node(node, graph) {
-            self.append_slice(&node.ops, graph, dist);
-            let ntype = self.spec.get_node(node.id).unwrap();
-            if let Some(dtype) = ntype.data {
-                if let Ok(dat) = self.spec.get_data(dtype) {
-                    dat.atomic_type.append_mutated_subtraction(
-                        node.data, graph, &self.spec, mutator, off
-                    );
-                } else {
-                    panic!("Node {} has invalid data type {:?}", ntype.name, ntype.data);
-                }
-            }
-        }
-    }
-
-
-
-
-
 }
@@ -1,7 +1,7 @@
 //use rmps::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
 
-use std::io::Read;
+use std::io::{Read, Write};
 use std::sync::Arc;
 
 use crate::graph_mutator::atomic_data::{DataInt, DataStruct, DataVec};
@@ -9,19 +9,19 @@ use crate::graph_mutator::newtypes::{AtomicTypeID, ValueTypeID};
 use crate::graph_mutator::spec::GraphSpec;
 use crate::graph_mutator::generators::{IntGenerator, VecGeneratorLoader};
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
-struct EdgeLoader {
-    name: String,
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct EdgeLoader {
+    pub name: String,
 }
 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
-struct NodeLoader {
-    name: String,
-    atom_id: Option<usize>,
-    inputs: Vec<u16>,
-    borrows: Vec<u16>,
-    outputs: Vec<u16>,
-    is_interactive: bool,
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct NodeLoader {
+    pub name: String,
+    pub atom_id: Option<usize>,
+    pub inputs: Vec<u16>,
+    pub borrows: Vec<u16>,
+    pub outputs: Vec<u16>,
+    pub is_interactive: bool,
 }
 
 
@@ -29,9 +29,9 @@ struct NodeLoader {
 /// 
 /// 
 /// 
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
 #[serde(tag = "type")]
-enum AtomLoader {
+pub enum AtomLoader {
     Struct {
         name: String,
         fields: Vec<(String, usize)>,
@@ -52,19 +52,19 @@ enum AtomLoader {
 /// 用于记录并转化输入数据为节点、边、原子的数据结构，
 /// 
 /// 本数据结构用于转化为specgraph
-#[derive(Debug, PartialEq, Deserialize, Serialize)]
-struct SpecLoader {
-    checksum: u64,
-    nodes: Vec<NodeLoader>,
-    edges: Vec<EdgeLoader>,
-    atomics: Vec<AtomLoader>,
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct SpecLoader {
+    pub checksum: u64,
+    pub nodes: Vec<NodeLoader>,
+    pub edges: Vec<EdgeLoader>,
+    pub atomics: Vec<AtomLoader>,
 }
 
 /// 主要实现to_graph_spec的方法
 impl SpecLoader {
 
     ///根据已有的数据创建GraphSpec，并返回之
-    fn to_graph_spec(mut self) -> GraphSpec {
+    pub fn to_graph_spec(mut self) -> GraphSpec {
         let mut g = GraphSpec::new();
         g.checksum = self.checksum;
         self.atoms_to_graphspec(&mut g);
@@ -73,6 +73,28 @@ impl SpecLoader {
         return g;
     }
 
+    ///添加一个原子数据类型，返回其编号（AtomicTypeID）。引用的其他原子必须已经添加
+    pub fn add_atom(&mut self, atom: AtomLoader) -> usize {
+        self.atomics.push(atom);
+        return self.atomics.len() - 1;
+    }
+
+    ///添加一个节点类型，返回其编号（NodeTypeID）
+    pub fn add_node(&mut self, node: NodeLoader) -> usize {
+        self.nodes.push(node);
+        return self.nodes.len() - 1;
+    }
+
+    ///按名字查找节点类型
+    pub fn node_id(&self, name: &str) -> Option<usize> {
+        return self.nodes.iter().position(|n| n.name == name);
+    }
+
+    ///序列化为 spec.msgp，可由 load_spec_from_read 读回
+    pub fn write_to<W: Write>(&self, out: &mut W) -> Result<(), rmp_serde::encode::Error> {
+        return rmp_serde::encode::write_named(out, self);
+    }
+
     ///遍历 SpecLoader 中的原子数据类型集合 atomics，根据每个原子数据类型的具体种类（结构体、整数、向量），
     /// 
     /// 创建相应的 DataStruct、DataInt 或 DataVec 实例，
@@ -146,6 +168,11 @@ impl SpecLoader {
     }
 }
 
+/// 对传入的数据data进行反序列化，得到未转换的SpecLoader，用于在已有 spec 的基础上生成新的 spec
+pub fn load_spec_loader_from_read<R: Read>(data: R) -> SpecLoader {
+    return rmp_serde::from_read(data).unwrap();
+}
+
 /// 对传入的数据data进行反序列化，得到对应的SpecLoader，并构建SpecLoader对应的测试用例的specgraph
 pub fn load_spec_from_read<R: Read>(data: R) -> GraphSpec {
     let l: SpecLoader = rmp_serde::from_read(data).unwrap();
@@ -156,13 +183,54 @@ pub fn load_spec_from_read<R: Read>(data: R) -> GraphSpec {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::graph_mutator::graph_storage::VecGraph;
+    use crate::graph_mutator::graph_storage::{GraphStorage, VecGraph};
     use crate::primitive_mutator::mutator::PrimitiveMutator;
     use crate::GraphBuilder;
     use crate::mutator::MutatorSnapshotState;
     use std::fs::File;
     use std::rc::Rc;
 
+    #[test]
+    fn test_write_refined_spec() {
+        let mut loader = SpecLoader { checksum: 1337, nodes: vec![], edges: vec![], atomics: vec![] };
+        let opcode = loader.add_atom(AtomLoader::Int {
+            name: "opcode".into(),
+            size: 1,
+            generators: vec![IntGenerator::Options { opts: vec![1, 2] }],
+        });
+        let length = loader.add_atom(AtomLoader::Int { name: "length".into(), size: 2, generators: vec![] });
+        let header = loader.add_atom(AtomLoader::Struct {
+            name: "hdr".into(),
+            fields: vec![("opcode".into(), opcode), ("length".into(), length)],
+        });
+        let byte = loader.add_atom(AtomLoader::Int { name: "u8".into(), size: 1, generators: vec![] });
+        let payload = loader.add_atom(AtomLoader::Vec { name: "payload".into(), size_range: (0, 16), dtype: byte, generators: vec![] });
+        for (name, atom) in [("msg", header), ("msg_payload", payload)].iter() {
+            loader.add_node(NodeLoader {
+                name: name.to_string(),
+                atom_id: Some(*atom),
+                inputs: vec![],
+                borrows: vec![],
+                outputs: vec![],
+                is_interactive: false,
+            });
+        }
+        assert_eq!(loader.node_id("msg_payload"), Some(1));
+
+        let mut buf = vec![];
+        loader.write_to(&mut buf).unwrap();
+        assert_eq!(load_spec_loader_from_read(&buf[..]), loader);
+
+        let spec = load_spec_from_read(&buf[..]);
+        assert_eq!(spec.checksum, 1337);
+        assert_eq!(spec.get_data(AtomicTypeID::new(header)).unwrap().atomic_type.size().as_usize(), 3);
+        let mut graph = VecGraph::new(vec![0, 1], vec![2, 0x10, 0, 2, 0, 0x41, 0x42]);
+        let nodes = graph.node_iter(&spec).map(|n| n.data.len()).collect::<Vec<_>>();
+        assert_eq!(nodes, vec![3, 4]);
+        graph.clear();
+        assert_eq!(graph.node_len(&spec), 0);
+    }
+
     // #[test]
     // fn test_export() {
     //     let val = SpecLoader {
@@ -7,7 +7,8 @@ use crate::graph_mutator::graph_iter::GraphNode;
 use crate::graph_mutator::graph_storage::GraphStorage;
 use crate::graph_mutator::graph_storage::VecGraph;
 use crate::graph_mutator::spec::GraphSpec;
-use crate::primitive_mutator::mutator::{PrimitiveMutator, PrimitiveMutatorDefenite};
+use crate::primitive_mutator::calibration::CalibrationOperator;
+use crate::primitive_mutator::mutator::PrimitiveMutator;
 use crate::random::distributions::Distributions;
 use crate::custom_dict::CustomDict;
 
@@ -463,18 +464,15 @@ impl Mutator {
 pub struct DetMutator{
     pub spec: Rc<GraphSpec>,
     builder: GraphBuilder,
-    mutator: PrimitiveMutatorDefenite,
 }
 
 impl DetMutator{
     pub fn new(spec: GraphSpec) -> Self {
         let spec = Rc::new(spec);                       //导入spec，并转化为引用计数智能指针，方便后续引用spec
-        let mutator = PrimitiveMutatorDefenite::new();          //新建一个PrimitiveMutator
         let builder = GraphBuilder::new(spec.clone());  //根据传入的spec，构建specgraph
         return Self {
             spec,
             builder,
-            mutator,
         };
     }
 
@@ -541,79 +539,23 @@ impl DetMutator{
         }
     }
 
-    /// 全比特翻转变异：对节点数据内 off 位置处的字节进行全比特取反（^ 0xff）。
-    pub fn mutate_data_full_bit_flip<S: GraphStorage>(
-        &mut self,
-        orig: &VecGraph,
-        snapshot: &MutatorSnapshotState,
-        storage: &mut S,
-        dist: &Distributions,
-        off: usize,
-    ) {
-        self.builder.start(storage, snapshot);
-        if let Some(n) = orig.node_iter(&self.spec.clone()).skip(snapshot.skip_nodes).next() {
-            if !self.builder.is_full(storage) {
-                self.builder
-                    .append_node_mutated_full_bit_flip(&n, off, &self.mutator, storage, dist);
-            }
-        }
-    }
-
-    /// 最低位翻转变异：对节点数据内 off 位置处的字节进行最低位翻转（例如 ^ 0xfe）。
-    pub fn mutate_data_lowest_bit_flip<S: GraphStorage>(
-        &mut self,
-        orig: &VecGraph,
-        snapshot: &MutatorSnapshotState,
-        storage: &mut S,
-        dist: &Distributions,
-        off: usize,
-    ) {
-        self.builder.start(storage, snapshot);
-        if let Some(n) = orig.node_iter(&self.spec.clone()).skip(snapshot.skip_nodes).next() {
-            if !self.builder.is_full(storage) {
-                self.builder
-                    .append_node_mutated_lowest_bit_flip(&n, off, &self.mutator, storage, dist);
-            }
-        }
-    }
-    
-
-    /// 数值加法扰动变异：对节点数据内 off 位置处的字节进行 wrapping 加法（+ 0x20）。
-    pub fn mutate_data_addition<S: GraphStorage>(
+    /// 校准变异：用 op 对下一个节点数据内 off 位置处的字节进行确定性变异。
+    pub fn mutate_data_calibrated<S: GraphStorage>(
         &mut self,
         orig: &VecGraph,
         snapshot: &MutatorSnapshotState,
         storage: &mut S,
         dist: &Distributions,
+        op: &dyn CalibrationOperator,
         off: usize,
     ) {
         self.builder.start(storage, snapshot);
         if let Some(n) = orig.node_iter(&self.spec.clone()).skip(snapshot.skip_nodes).next() {
             if !self.builder.is_full(storage) {
                 self.builder
-                    .append_node_mutated_addition(&n, off, &self.mutator, storage, dist);
+                    .append_node_calibrated(&n, off, op, storage, dist);
             }
         }
     }
 
-    /// 数值减法扰动变异：对节点数据内 off 位置处的字节进行 (x ^ 0x01).wrapping_sub(0x10) 操作。
-    pub fn mutate_data_subtraction<S: GraphStorage>(
-        &mut self,
-        orig: &VecGraph,
-        snapshot: &MutatorSnapshotState,
-        storage: &mut S,
-        dist: &Distributions,
-        off: usize,
-    ) {
-        self.builder.start(storage, snapshot);
-        if let Some(n) = orig.node_iter(&self.spec.clone()).skip(snapshot.skip_nodes).next() {
-            if !self.builder.is_full(storage) {
-                self.builder
-                    .append_node_mutated_subtraction(&n, off, &self.mutator, storage, dist);
-            }
-        }
-    }
-
-    
-    
 }
\ No newline at end of file
@@ -0,0 +1,304 @@
+use std::sync::Arc;
+
+use crate::data_buff::DataBuff;
+use crate::primitive_mutator::inplace_mutation::InplaceMutation;
+use crate::primitive_mutator::mutator::{
+    PrimitiveMutatorDefenite, INTERESTING_U16, INTERESTING_U32, INTERESTING_U64,
+};
+
+/// 校准用的确定性探测算子。
+///
+/// 每个算子在包负载的某个偏移处做一次固定的变异，`name` 会原样写入测量结果的
+/// `mutation_operator` 字段。新增算子只需实现该 trait 并注册到 `CalibrationRegistry`。
+pub trait CalibrationOperator: Send + Sync {
+    /// 算子名，例如 "LBF"
+    fn name(&self) -> &str;
+
+    /// 一次变异覆盖的字节数
+    fn span(&self) -> usize {
+        return 1;
+    }
+
+    /// 多字节算子写入时的字节序，单字节算子为 None
+    fn big_endian(&self) -> Option<bool> {
+        return None;
+    }
+
+    /// 能否在 buff 的 offset 处执行，默认要求 span 个字节都在缓冲区内
+    fn is_applicable(&self, buff: &DataBuff, offset: usize) -> bool {
+        return offset + self.span() <= buff.len();
+    }
+
+    /// 在 buff 的 offset 处执行变异
+    fn apply(&self, buff: &mut DataBuff, offset: usize);
+}
+
+type ByteProbe = fn(&PrimitiveMutatorDefenite, &DataBuff, usize) -> InplaceMutation;
+
+/// 基于 `PrimitiveMutatorDefenite` 的单字节探测
+pub struct ByteOperator {
+    name: &'static str,
+    probe: ByteProbe,
+}
+
+impl ByteOperator {
+    pub fn new(name: &'static str, probe: ByteProbe) -> Self {
+        return Self { name, probe };
+    }
+}
+
+impl CalibrationOperator for ByteOperator {
+    fn name(&self) -> &str {
+        return self.name;
+    }
+
+    fn apply(&self, buff: &mut DataBuff, offset: usize) {
+        let mutation = (self.probe)(&PrimitiveMutatorDefenite::new(), buff, offset);
+        mutation.apply(buff);
+    }
+}
+
+/// 多字节整数探测写入的取值
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IntProbeValue {
+    Zero,
+    LenPlus1,  // 负载长度 + 1
+    LenMinus1, // 负载长度 - 1
+    Interesting(u64),
+}
+
+/// 以 u16/u32/u64 小端或大端覆写 offset 处整数的探测，用于识别长度字段与整数宽度
+pub struct IntOperator {
+    name: String,
+    width: usize,
+    big_endian: bool,
+    value: IntProbeValue,
+}
+
+impl IntOperator {
+    /// 名字形如 U16LE_ZERO、U32BE_LENP1、U16LE_IFF80
+    pub fn new(width: usize, big_endian: bool, value: IntProbeValue) -> Self {
+        let suffix = match value {
+            IntProbeValue::Zero => "ZERO".to_string(),
+            IntProbeValue::LenPlus1 => "LENP1".to_string(),
+            IntProbeValue::LenMinus1 => "LENM1".to_string(),
+            IntProbeValue::Interesting(v) => format!("I{:X}", v),
+        };
+        let name = format!("U{}{}_{}", width * 8, if big_endian { "BE" } else { "LE" }, suffix);
+        return Self { name, width, big_endian, value };
+    }
+
+    pub fn value(&self) -> IntProbeValue {
+        return self.value;
+    }
+}
+
+impl CalibrationOperator for IntOperator {
+    fn name(&self) -> &str {
+        return &self.name;
+    }
+
+    fn span(&self) -> usize {
+        return self.width;
+    }
+
+    fn big_endian(&self) -> Option<bool> {
+        return Some(self.big_endian);
+    }
+
+    fn apply(&self, buff: &mut DataBuff, offset: usize) {
+        let val = match self.value {
+            IntProbeValue::Zero => 0,
+            IntProbeValue::LenPlus1 => buff.len() as u64 + 1,
+            IntProbeValue::LenMinus1 => (buff.len() as u64).wrapping_sub(1),
+            IntProbeValue::Interesting(v) => v,
+        };
+        let mutation = PrimitiveMutatorDefenite::new().gen_int_at_offset(buff, offset, self.width, self.big_endian, val);
+        mutation.apply(buff);
+    }
+}
+
+/// 默认使用的算子组
+pub const DEFAULT_GROUP: &str = "byte";
+
+/// 按名字管理所有可用的校准算子
+pub struct CalibrationRegistry {
+    operators: Vec<Arc<dyn CalibrationOperator>>,
+    groups: Vec<(String, Vec<String>)>, // 组名 -> 算子名，选择算子时可直接使用组名
+}
+
+impl CalibrationRegistry {
+    pub fn new() -> Self {
+        return Self { operators: vec![], groups: vec![] };
+    }
+
+    /// 默认的四个单字节算子（"byte" 组，顺序与原先的测量顺序一致），
+    /// 以及需要显式选择的 "u16"/"u32"/"u64" 整数探测组和 "len" 长度探测组
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(ByteOperator::new("LBF", PrimitiveMutatorDefenite::gen_lowest_bit_flip_at_offset)));
+        registry.register(Arc::new(ByteOperator::new("FBF", PrimitiveMutatorDefenite::gen_full_bit_flip_at_offset)));
+        registry.register(Arc::new(ByteOperator::new("ADD", PrimitiveMutatorDefenite::gen_addition_at_offset)));
+        registry.register(Arc::new(ByteOperator::new("SUB", PrimitiveMutatorDefenite::gen_subtraction_at_offset)));
+        registry.register_group(DEFAULT_GROUP, &["LBF", "FBF", "ADD", "SUB"]);
+
+        let interesting: [(usize, Vec<u64>); 3] = [
+            (2, INTERESTING_U16.iter().map(|&v| v as u64).collect()),
+            (4, INTERESTING_U32.iter().map(|&v| v as u64).collect()),
+            (8, INTERESTING_U64.to_vec()),
+        ];
+        let mut len_group = vec![];
+        for (width, values) in interesting.iter() {
+            let mut group = vec![];
+            for &big_endian in [false, true].iter() {
+                let mut values = values.iter().filter(|&&v| v != 0).map(|&v| IntProbeValue::Interesting(v)).collect::<Vec<_>>();
+                values.insert(0, IntProbeValue::LenMinus1);
+                values.insert(0, IntProbeValue::LenPlus1);
+                values.insert(0, IntProbeValue::Zero);
+                for value in values {
+                    let op = IntOperator::new(*width, big_endian, value);
+                    if !matches!(value, IntProbeValue::Interesting(_)) {
+                        len_group.push(op.name().to_string());
+                    }
+                    group.push(op.name().to_string());
+                    registry.register(Arc::new(op));
+                }
+            }
+            let names = group.iter().map(|n| n.as_str()).collect::<Vec<_>>();
+            registry.register_group(&format!("u{}", width * 8), &names);
+        }
+        let names = len_group.iter().map(|n| n.as_str()).collect::<Vec<_>>();
+        registry.register_group("len", &names);
+        return registry;
+    }
+
+    /// 注册算子，同名算子会被替换
+    pub fn register(&mut self, op: Arc<dyn CalibrationOperator>) {
+        if let Some(pos) = self.operators.iter().position(|o| o.name() == op.name()) {
+            self.operators[pos] = op;
+        } else {
+            self.operators.push(op);
+        }
+    }
+
+    /// 注册算子组，同名组会被替换
+    pub fn register_group(&mut self, name: &str, members: &[&str]) {
+        let members = members.iter().map(|m| m.to_string()).collect();
+        if let Some(pos) = self.groups.iter().position(|(g, _)| g == name) {
+            self.groups[pos].1 = members;
+        } else {
+            self.groups.push((name.to_string(), members));
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn CalibrationOperator>> {
+        return self.operators.iter().find(|o| o.name() == name).cloned();
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        return self.operators.iter().map(|o| o.name()).collect();
+    }
+
+    pub fn group_names(&self) -> Vec<&str> {
+        return self.groups.iter().map(|(g, _)| g.as_str()).collect();
+    }
+
+    /// 按算子名或组名选出本次使用的算子，重复的算子只保留第一次出现；
+    /// names 为空时使用默认组
+    pub fn select(&self, names: &[String]) -> Result<Vec<Arc<dyn CalibrationOperator>>, String> {
+        let default = [DEFAULT_GROUP.to_string()];
+        let names = if names.is_empty() { &default[..] } else { names };
+        let mut selected: Vec<Arc<dyn CalibrationOperator>> = vec![];
+        for name in names.iter() {
+            let members = match self.groups.iter().find(|(g, _)| g == name) {
+                Some((_, members)) => members.clone(),
+                None => vec![name.clone()],
+            };
+            for member in members.iter() {
+                match self.get(member) {
+                    Some(op) => {
+                        if !selected.iter().any(|o| o.name() == op.name()) {
+                            selected.push(op);
+                        }
+                    }
+                    None => return Err(format!(
+                        "unknown calibration operator {} (groups: {})",
+                        member,
+                        self.group_names().join(", ")
+                    )),
+                }
+            }
+        }
+        return Ok(selected);
+    }
+}
+
+impl Default for CalibrationRegistry {
+    fn default() -> Self {
+        return Self::with_defaults();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_operators() {
+        let registry = CalibrationRegistry::with_defaults();
+        assert_eq!(&registry.names()[..4], &["LBF", "FBF", "ADD", "SUB"]);
+
+        let data = vec![0x41u8, 0x42, 0x43];
+        let expected = [0x42u8 ^ 0x01, 0x42 ^ 0xff, 0x42u8.wrapping_add(0x10), 0x42u8.wrapping_sub(0x10)];
+        for (op, exp) in registry.select(&[]).unwrap().iter().zip(expected.iter()) {
+            let mut copy = data.clone();
+            let len = copy.len();
+            let mut buff = DataBuff::new(&mut copy, len);
+            assert!(op.is_applicable(&buff, 1));
+            assert!(!op.is_applicable(&buff, 3));
+            op.apply(&mut buff, 1);
+            assert_eq!(buff.read_u8(1), *exp, "operator {}", op.name());
+            assert_eq!(buff.read_u8(0), 0x41);
+            assert_eq!(buff.read_u8(2), 0x43);
+        }
+    }
+
+    #[test]
+    fn test_select_operators() {
+        let registry = CalibrationRegistry::with_defaults();
+        let ops = registry.select(&["SUB".to_string(), "LBF".to_string()]).unwrap();
+        assert_eq!(ops.iter().map(|o| o.name()).collect::<Vec<_>>(), vec!["SUB", "LBF"]);
+        assert!(registry.select(&["NOPE".to_string()]).is_err());
+
+        let defaults = registry.select(&[]).unwrap();
+        assert_eq!(defaults.iter().map(|o| o.name()).collect::<Vec<_>>(), vec!["LBF", "FBF", "ADD", "SUB"]);
+
+        let ops = registry.select(&["len".to_string(), "U16LE_ZERO".to_string()]).unwrap();
+        assert_eq!(ops.len(), 18);
+        assert!(ops.iter().all(|o| o.span() > 1 && o.big_endian().is_some()));
+        let u16_ops = registry.select(&["u16".to_string()]).unwrap();
+        assert!(u16_ops.iter().all(|o| o.span() == 2));
+        assert!(u16_ops.iter().any(|o| o.name() == "U16BE_IFF80"));
+    }
+
+    #[test]
+    fn test_int_operators() {
+        let mut data = vec![0u8; 6];
+        let mut buff = DataBuff::new(&mut data, 6);
+
+        let op = IntOperator::new(2, true, IntProbeValue::LenPlus1);
+        assert_eq!(op.name(), "U16BE_LENP1");
+        assert!(op.is_applicable(&buff, 4));
+        assert!(!op.is_applicable(&buff, 5));
+        op.apply(&mut buff, 4);
+        assert_eq!(buff.as_slice(), &[0, 0, 0, 0, 0, 7]);
+
+        let op = IntOperator::new(4, false, IntProbeValue::LenMinus1);
+        op.apply(&mut buff, 0);
+        assert_eq!(buff.as_slice(), &[5, 0, 0, 0, 0, 7]);
+
+        let op = IntOperator::new(2, false, IntProbeValue::Zero);
+        op.apply(&mut buff, 4);
+        assert_eq!(buff.as_slice(), &[5, 0, 0, 0, 0, 0]);
+    }
+}
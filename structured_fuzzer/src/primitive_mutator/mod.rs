@@ -1,3 +1,4 @@
+pub mod calibration;
 pub mod inplace_mutation;
 pub mod mutator;
 pub mod size_changing_mutation;
@@ -10,8 +10,8 @@ use crate::primitive_mutator::size_changing_mutation::{
 };
 use crate::random::distributions::Distributions;
 
-const INTERESTING_U8: [u8; 9] = [(-128i8) as u8, (-1i8) as u8, 0, 1, 16, 32, 64, 100, 127];
-const INTERESTING_U16: [u16; 19] = [
+pub const INTERESTING_U8: [u8; 9] = [(-128i8) as u8, (-1i8) as u8, 0, 1, 16, 32, 64, 100, 127];
+pub const INTERESTING_U16: [u16; 19] = [
     (-128i16) as u16,
     (-1i16) as u16,
     0,
@@ -32,7 +32,7 @@ const INTERESTING_U16: [u16; 19] = [
     4096,
     32767,
 ];
-const INTERESTING_U32: [u32; 27] = [
+pub const INTERESTING_U32: [u32; 27] = [
     (-128i32) as u32,
     (-1i32) as u32,
     0,
@@ -61,7 +61,7 @@ const INTERESTING_U32: [u32; 27] = [
     100663045,
     2147483647,
 ];
-const INTERESTING_U64: [u64; 30] = [
+pub const INTERESTING_U64: [u64; 30] = [
     (-128i64) as u64,
     (-1i64) as u64,
     0,
@@ -502,6 +502,33 @@ impl PrimitiveMutatorDefenite {
         }
     }
 
+    /// 5. 多字节整数探测
+    /// 操作：把 offset 处宽度为 width（2/4/8）字节的整数覆写为 val，big_endian 为 true 时按大端写入
+    /// 用于识别长度字段、整数字段的宽度与字节序
+    pub fn gen_int_at_offset(
+        &self,
+        buff: &DataBuff,
+        offset: usize,
+        width: usize,
+        big_endian: bool,
+        val: u64,
+    ) -> InplaceMutation {
+        assert!(
+            offset + width <= buff.len(),
+            "Offset {} width {} out of bounds (buffer length {})",
+            offset,
+            width,
+            buff.len()
+        );
+        // DataBuff 默认按小端读写，flip_endian 即为大端
+        match width {
+            2 => InplaceMutation::InterestingU16 { offset, val: val as u16, flip_endian: big_endian },
+            4 => InplaceMutation::InterestingU32 { offset, val: val as u32, flip_endian: big_endian },
+            8 => InplaceMutation::InterestingU64 { offset, val, flip_endian: big_endian },
+            _ => panic!("unsupported integer width {}", width),
+        }
+    }
+
 }
 
 
@@ -688,6 +715,28 @@ mod tests {
 
     
 
+    /// 测试多字节整数探测：按指定宽度与字节序写入，其余字节不变
+    #[test]
+    fn test_int_probe_endianness() {
+        let mutator = PrimitiveMutatorDefenite::new();
+        let base = vec![0xaau8; 12];
+        for (width, big_endian, val, expected) in [
+            (2, false, 0x0102u64, vec![0x02u8, 0x01]),
+            (2, true, 0x0102, vec![0x01, 0x02]),
+            (4, false, 0x01020304, vec![0x04, 0x03, 0x02, 0x01]),
+            (4, true, 0x01020304, vec![0x01, 0x02, 0x03, 0x04]),
+            (8, true, 0x0102030405060708, vec![1, 2, 3, 4, 5, 6, 7, 8]),
+        ].iter() {
+            let mut data = base.clone();
+            let mut buff = DataBuff::new(&mut data, 12);
+            mutator.gen_int_at_offset(&buff, 3, *width, *big_endian, *val).apply(&mut buff);
+            assert_eq!(&buff.as_slice()[3..3 + width], &expected[..]);
+            assert_eq!(buff.read_u8(2), 0xaa);
+            assert_eq!(buff.read_u8(3 + width), 0xaa);
+            assert_eq!(buff.len(), 12);
+        }
+    }
+
     //  测试 MutationDefinite::FullBitFlip 随机 500 次
     //  操作：in_data[offset] = in_data[offset] ^ 0xff
     // #[test]